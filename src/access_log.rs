@@ -0,0 +1,77 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::Mutex;
+use tracing::warn;
+
+// One line of the ACCESS_LOG_PATH sink.
+#[derive(Serialize)]
+pub(crate) struct AccessRecord<'a> {
+    pub req_id: &'a str,
+    // from an incoming W3C traceparent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<&'a str>,
+    pub ts_ms: i64,
+    pub method: &'a str,
+    pub uri: &'a str,
+    pub status: u16,
+    pub dur_ms: u64,
+    pub client_ip: Option<&'a str>,
+    pub resp_ct: Option<&'a str>,
+    pub resp_len: Option<u64>,
+}
+
+impl AccessRecord<'_> {
+    pub(crate) fn to_line(&self) -> Vec<u8> {
+        let mut line = serde_json::to_vec(self).unwrap_or_default();
+        line.push(b'\n');
+        line
+    }
+}
+
+// Append-only JSON-lines file behind a shared buffered writer; a background task flushes it.
+pub(crate) struct AccessLog {
+    writer: Mutex<BufWriter<tokio::fs::File>>,
+}
+
+impl AccessLog {
+    pub(crate) async fn open(path: &Path, flush_every: Duration) -> std::io::Result<Arc<Self>> {
+        let file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        let log = Arc::new(Self {
+            writer: Mutex::new(BufWriter::new(file)),
+        });
+        let weak = Arc::downgrade(&log);
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval(flush_every);
+            loop {
+                tick.tick().await;
+                let Some(log) = weak.upgrade() else {
+                    break;
+                };
+                log.flush().await;
+            }
+        });
+        Ok(log)
+    }
+
+    pub(crate) async fn write(&self, line: Vec<u8>) {
+        let mut w = self.writer.lock().await;
+        if let Err(e) = w.write_all(&line).await {
+            warn!(target: "fakehub", "access log write failed: {}", e);
+        }
+    }
+
+    pub(crate) async fn flush(&self) {
+        let mut w = self.writer.lock().await;
+        if let Err(e) = w.flush().await {
+            warn!(target: "fakehub", "access log flush failed: {}", e);
+        }
+    }
+}
@@ -1,7 +1,14 @@
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
 use std::time::Duration;
 
+use tokio::sync::RwLock;
+
+use crate::access_log::AccessLog;
+use crate::caches::IpLog;
+use crate::utils::repo_json::RepoDefaults;
+
 #[derive(Clone)]
 pub struct AppState {
     pub root: Arc<PathBuf>,
@@ -15,9 +22,136 @@ pub struct AppState {
     pub log_json_body: bool,
     pub ip_log_retention_secs: u64,
     pub ip_log_per_ip_cap: usize,
-    // cache options
+    pub ip_log_max_ips: usize,
+    // per-IP request log, shared by every clone of the state
+    pub ip_log: Arc<RwLock<IpLog>>,
+    // RATE_LIMIT requests per RATE_WINDOW_SECS per IP, checked against `ip_log`; 0 disables
+    pub rate_limit: usize,
+    pub rate_window_secs: u64,
+    // cap on buffered request bodies (MAX_JSON_BODY_BYTES); larger ones get 413
+    pub max_json_body_bytes: usize,
+    // JSON-lines access log (ACCESS_LOG_PATH), independent of log_requests
+    pub access_log: Option<Arc<AccessLog>>,
+    // cache options; CACHE_TTL_MS is the default for every per-cache TTL below
     pub cache_ttl: Duration,
+    pub sidecar_cache_ttl: Duration,
+    pub siblings_cache_ttl: Duration,
+    pub paths_info_cache_ttl: Duration,
+    // also covers the SHA-1 digest cache
+    pub sha256_cache_ttl: Duration,
+    pub sidecar_cache_cap: usize,
     pub paths_info_cache_cap: usize,
     pub siblings_cache_cap: usize,
     pub sha256_cache_cap: usize,
+    pub blake3_cache_cap: usize,
+    // read chunk for file streaming and hashing (CHUNK_SIZE_BYTES)
+    pub chunk_size: usize,
+    // resolve options
+    pub resolve_content_disposition: bool,
+    // chaos knobs: RESOLVE_DELAY_MS before the first byte, RESOLVE_THROTTLE_BPS body pacing (0 = off)
+    pub resolve_delay: Duration,
+    pub resolve_throttle_bps: u64,
+    // RESOLVE_CACHE_MAX_AGE: Cache-Control max-age (seconds) on resolve file responses
+    pub resolve_cache_max_age: u64,
+    // FAULT_INJECT_RATE (0.0-1.0) of resolve requests fail with FAULT_INJECT_STATUS
+    pub fault_inject_rate: f64,
+    pub fault_inject_status: axum::http::StatusCode,
+    // ETAG_RECOMPUTE: hash non-LFS files on disk instead of trusting the sidecar oid
+    pub etag_recompute: bool,
+    // ETAG_FALLBACK_COMPUTE: content SHA-1 for on-disk files the sidecar does not list
+    pub etag_fallback_compute: bool,
+    // SIDECAR_DIR: `<dir>/<repo>.paths-info.json[.gz]` is looked up before the in-repo sidecar
+    pub sidecar_dir: Option<Arc<PathBuf>>,
+    // ALLOW_SYMLINK_ESCAPE: symlinks under the root may point outside it (see `try_secure_join`)
+    pub allow_symlink_escape: bool,
+    // ALLOW_WRITES: accept PUT uploads on resolve paths (still needs a valid token)
+    pub allow_writes: bool,
+    // LFS_THRESHOLD_BYTES: preupload answers "lfs" and uploads get an `lfs` block at this size
+    pub lfs_threshold: u64,
+    // MAX_UPLOAD_BYTES: PUT bodies (and proxied files) over this get 413
+    pub max_upload_bytes: u64,
+    // PROXY_MODE: resolve misses are fetched from this upstream (HF_REMOTE_ENDPOINT) and kept
+    pub proxy_endpoint: Option<Arc<String>>,
+    // HF_TOKEN sent upstream as a Bearer token in proxy mode
+    pub proxy_token: Option<Arc<String>>,
+    // XET_STUB: answer xet-read-token probes with a placeholder token (no chunks are served)
+    pub xet_stub: bool,
+    // FAKE_HUB_AUTHOR / FAKE_HUB_PIPELINE_TAG / FAKE_HUB_LIBRARY for synthesized repo JSON
+    pub repo_defaults: Arc<RepoDefaults>,
+    // identity reported by /api/whoami-v2
+    pub hub_user: Arc<String>,
+    // FAKE_HUB_TOKEN: the only accepted Bearer token when set; any non-empty one otherwise
+    pub hub_token: Option<Arc<String>>,
+    // CORS options
+    pub cors_enabled: bool,
+    pub cors_allow_origin: Arc<String>,
+    // Prometheus /metrics
+    pub metrics_enabled: bool,
+    // gzip/zstd for JSON API responses
+    pub compression_enabled: bool,
+    // admin routes require X-Admin-Token when set
+    pub admin_token: Option<Arc<String>>,
+    // MAINTENANCE at startup, flipped at runtime by POST /admin/maintenance
+    pub maintenance: Arc<AtomicBool>,
+}
+
+#[cfg(test)]
+impl AppState {
+    // Build a state rooted at `root` with small caches and logging off.
+    pub fn for_tests(root: PathBuf) -> Self {
+        Self {
+            root: Arc::new(root),
+            log_requests: false,
+            log_body_max: 1024,
+            log_headers_mode_all: false,
+            log_resp_headers: false,
+            log_redact: true,
+            log_body_all: false,
+            log_json_body: false,
+            ip_log_retention_secs: 1_800,
+            ip_log_per_ip_cap: 200,
+            ip_log_max_ips: 10_000,
+            ip_log: Arc::new(RwLock::new(IpLog::default())),
+            rate_limit: 0,
+            rate_window_secs: 60,
+            max_json_body_bytes: 1_048_576,
+            access_log: None,
+            cache_ttl: Duration::from_millis(2000),
+            sidecar_cache_ttl: Duration::from_millis(2000),
+            siblings_cache_ttl: Duration::from_millis(2000),
+            paths_info_cache_ttl: Duration::from_millis(2000),
+            sha256_cache_ttl: Duration::from_millis(2000),
+            sidecar_cache_cap: 64,
+            paths_info_cache_cap: 64,
+            siblings_cache_cap: 64,
+            sha256_cache_cap: 64,
+            blake3_cache_cap: 64,
+            chunk_size: crate::CHUNK_SIZE,
+            resolve_content_disposition: true,
+            resolve_delay: Duration::ZERO,
+            resolve_throttle_bps: 0,
+            resolve_cache_max_age: 3600,
+            fault_inject_rate: 0.0,
+            fault_inject_status: axum::http::StatusCode::SERVICE_UNAVAILABLE,
+            etag_recompute: false,
+            etag_fallback_compute: false,
+            sidecar_dir: None,
+            allow_symlink_escape: false,
+            allow_writes: false,
+            lfs_threshold: 10 * 1024 * 1024,
+            max_upload_bytes: 1024 * 1024 * 1024,
+            proxy_endpoint: None,
+            proxy_token: None,
+            xet_stub: false,
+            repo_defaults: Arc::new(RepoDefaults::default()),
+            hub_user: Arc::new("local-user".to_string()),
+            hub_token: None,
+            cors_enabled: true,
+            cors_allow_origin: Arc::new("*".to_string()),
+            metrics_enabled: false,
+            compression_enabled: true,
+            admin_token: None,
+            maintenance: Arc::new(AtomicBool::new(false)),
+        }
+    }
 }
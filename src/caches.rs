@@ -1,5 +1,5 @@
 use std::collections::{HashMap, VecDeque};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 
 use serde_json::Value;
@@ -8,10 +8,43 @@ use tokio::sync::RwLock;
 // In-memory sidecar cache
 pub type SidecarMap = std::sync::Arc<HashMap<String, Value>>; // rel_path (posix) -> entry (Arc for cheap clones)
 
+#[derive(Clone)]
+pub struct SidecarEntry {
+    pub map: SidecarMap,
+    pub at: Instant,
+}
+
+// key: (abs_path, mtime_secs, size)
+pub type SidecarKey = (PathBuf, u64, u64);
+
 #[derive(Default)]
 pub struct SidecarCache {
-    // key: (abs_path, mtime_secs, size)
-    pub inner: HashMap<(PathBuf, u64, u64), SidecarMap>,
+    pub inner: HashMap<SidecarKey, SidecarEntry>,
+    pub evict_q: VecDeque<(SidecarKey, Instant)>,
+}
+
+impl SidecarCache {
+    // Insert, first dropping the oldest live entry once `cap` is reached. Queue entries whose
+    // key was since replaced or evicted are skipped, and pruned when they outnumber `cap`.
+    pub fn insert_bounded(&mut self, key: SidecarKey, entry: SidecarEntry, cap: usize) {
+        if !self.inner.contains_key(&key) && self.inner.len() >= cap.max(1) {
+            while let Some((old_k, old_at)) = self.evict_q.pop_front() {
+                if let Some(e) = self.inner.get(&old_k)
+                    && e.at == old_at
+                {
+                    self.inner.remove(&old_k);
+                    break;
+                }
+            }
+        }
+        self.evict_q.push_back((key.clone(), entry.at));
+        self.inner.insert(key, entry);
+        if self.evict_q.len() > 2 * cap.max(1) {
+            let inner = &self.inner;
+            self.evict_q
+                .retain(|(k, at)| inner.get(k).is_some_and(|e| e.at == *at));
+        }
+    }
 }
 
 pub static SIDECAR_CACHE: once_cell::sync::Lazy<RwLock<SidecarCache>> =
@@ -21,46 +54,34 @@ pub static SIDECAR_CACHE: once_cell::sync::Lazy<RwLock<SidecarCache>> =
 pub struct SiblingsEntry {
     pub siblings: Vec<Value>,
     pub total: u64,
+    // content-derived commit sha; None when the sidecar is empty
+    pub sha: Option<String>,
     pub at: Instant,
 }
 
+#[derive(Default)]
 pub struct SiblingsCache {
     pub inner: HashMap<String, SiblingsEntry>,
     pub evict_q: VecDeque<(String, Instant)>,
 }
 
-impl Default for SiblingsCache {
-    fn default() -> Self {
-        Self {
-            inner: HashMap::new(),
-            evict_q: VecDeque::new(),
-        }
-    }
-}
-
 pub static SIBLINGS_CACHE: once_cell::sync::Lazy<RwLock<SiblingsCache>> =
     once_cell::sync::Lazy::new(|| RwLock::new(SiblingsCache::default()));
 
 #[derive(Clone)]
 pub struct PathsInfoEntry {
     pub items: Vec<Value>,
+    // Set only for paginated requests; `Some(None)` is the last page.
+    pub next_cursor: Option<Option<String>>,
     pub at: Instant,
 }
 
+#[derive(Default)]
 pub struct PathsInfoCache {
     pub inner: HashMap<String, PathsInfoEntry>,
     pub evict_q: VecDeque<(String, Instant)>,
 }
 
-impl Default for PathsInfoCache {
-    fn default() -> Self {
-        Self {
-            inner: HashMap::new(),
-            evict_q: VecDeque::new(),
-        }
-    }
-}
-
 pub static PATHS_INFO_CACHE: once_cell::sync::Lazy<RwLock<PathsInfoCache>> =
     once_cell::sync::Lazy::new(|| RwLock::new(PathsInfoCache::default()));
 
@@ -72,12 +93,99 @@ pub struct Sha256Entry {
 
 pub type Sha256Key = (PathBuf, u64, u64);
 
+#[derive(Default)]
 pub struct Sha256Cache {
     pub inner: HashMap<Sha256Key, Sha256Entry>,
     pub evict_q: VecDeque<(Sha256Key, Instant)>,
 }
 
-impl Default for Sha256Cache {
+pub type DigestCacheLock = once_cell::sync::Lazy<RwLock<Sha256Cache>>;
+
+pub static SHA256_CACHE: DigestCacheLock =
+    once_cell::sync::Lazy::new(|| RwLock::new(Sha256Cache::default()));
+
+// Content SHA-1 used for computed ETags (ETAG_RECOMPUTE, ETAG_FALLBACK_COMPUTE); same shape
+// as the sha256 cache.
+pub static SHA1_CACHE: DigestCacheLock =
+    once_cell::sync::Lazy::new(|| RwLock::new(Sha256Cache::default()));
+
+#[derive(Clone)]
+pub struct Blake3Entry {
+    pub sum: String,
+    pub at: Instant,
+}
+
+pub type Blake3Key = (PathBuf, u64, u64);
+
+#[derive(Default)]
+pub struct Blake3Cache {
+    pub inner: HashMap<Blake3Key, Blake3Entry>,
+    pub evict_q: VecDeque<(Blake3Key, Instant)>,
+}
+
+pub static BLAKE3_CACHE: once_cell::sync::Lazy<RwLock<Blake3Cache>> =
+    once_cell::sync::Lazy::new(|| RwLock::new(Blake3Cache::default()));
+
+// Parsed safetensors headers: per-dtype element counts, None for files that are not
+// safetensors. Keyed like the sidecar cache so rewritten files are parsed again.
+pub type SafetensorsCounts = Option<std::sync::Arc<std::collections::BTreeMap<String, u64>>>;
+
+#[derive(Clone)]
+pub struct SafetensorsEntry {
+    pub counts: SafetensorsCounts,
+    pub at: Instant,
+}
+
+pub type SafetensorsKey = (PathBuf, u64, u64);
+
+#[derive(Default)]
+pub struct SafetensorsCache {
+    pub inner: HashMap<SafetensorsKey, SafetensorsEntry>,
+    pub evict_q: VecDeque<(SafetensorsKey, Instant)>,
+}
+
+impl SafetensorsCache {
+    // Same FIFO bound as the digest caches: the oldest live entry goes once `cap` is reached.
+    pub fn insert_bounded(&mut self, key: SafetensorsKey, counts: SafetensorsCounts, cap: usize) {
+        if !self.inner.contains_key(&key) && self.inner.len() >= cap.max(1) {
+            while let Some((old_k, old_at)) = self.evict_q.pop_front() {
+                if let Some(e) = self.inner.get(&old_k)
+                    && e.at == old_at
+                {
+                    self.inner.remove(&old_k);
+                    break;
+                }
+            }
+        }
+        let at = Instant::now();
+        self.evict_q.push_back((key.clone(), at));
+        self.inner.insert(key, SafetensorsEntry { counts, at });
+        if self.evict_q.len() > 2 * cap.max(1) {
+            let inner = &self.inner;
+            self.evict_q
+                .retain(|(k, at)| inner.get(k).is_some_and(|e| e.at == *at));
+        }
+    }
+}
+
+pub static SAFETENSORS_CACHE: once_cell::sync::Lazy<RwLock<SafetensorsCache>> =
+    once_cell::sync::Lazy::new(|| RwLock::new(SafetensorsCache::default()));
+
+// Small documents parsed from one repo file (`.refs.json`, `.repo-meta.json`), keyed like the sidecar cache so
+// an edited file is parsed again. `doc` is None when the file is malformed.
+pub type ParsedFileKey = (PathBuf, u64, u64);
+
+pub struct ParsedFileEntry<T> {
+    pub doc: Option<std::sync::Arc<T>>,
+    pub at: Instant,
+}
+
+pub struct ParsedFileCache<T> {
+    pub inner: HashMap<ParsedFileKey, ParsedFileEntry<T>>,
+    pub evict_q: VecDeque<(ParsedFileKey, Instant)>,
+}
+
+impl<T> Default for ParsedFileCache<T> {
     fn default() -> Self {
         Self {
             inner: HashMap::new(),
@@ -86,8 +194,102 @@ impl Default for Sha256Cache {
     }
 }
 
-pub static SHA256_CACHE: once_cell::sync::Lazy<RwLock<Sha256Cache>> =
-    once_cell::sync::Lazy::new(|| RwLock::new(Sha256Cache::default()));
+impl<T> ParsedFileCache<T> {
+    // Same FIFO bound as the safetensors cache.
+    pub fn insert_bounded(
+        &mut self,
+        key: ParsedFileKey,
+        doc: Option<std::sync::Arc<T>>,
+        cap: usize,
+    ) {
+        if !self.inner.contains_key(&key) && self.inner.len() >= cap.max(1) {
+            while let Some((old_k, old_at)) = self.evict_q.pop_front() {
+                if let Some(e) = self.inner.get(&old_k)
+                    && e.at == old_at
+                {
+                    self.inner.remove(&old_k);
+                    break;
+                }
+            }
+        }
+        let at = Instant::now();
+        self.evict_q.push_back((key.clone(), at));
+        self.inner.insert(key, ParsedFileEntry { doc, at });
+        if self.evict_q.len() > 2 * cap.max(1) {
+            let inner = &self.inner;
+            self.evict_q
+                .retain(|(k, at)| inner.get(k).is_some_and(|e| e.at == *at));
+        }
+    }
+}
+
+pub static REFS_CACHE: once_cell::sync::Lazy<RwLock<ParsedFileCache<Value>>> =
+    once_cell::sync::Lazy::new(|| RwLock::new(ParsedFileCache::default()));
+
+pub static REPO_META_CACHE: once_cell::sync::Lazy<
+    RwLock<ParsedFileCache<crate::utils::repo_json::RepoMeta>>,
+> = once_cell::sync::Lazy::new(|| RwLock::new(ParsedFileCache::default()));
+
+// Last /admin/stats inventory, keyed by the root it was computed for.
+pub struct HubStatsEntry {
+    pub root: PathBuf,
+    pub stats: Value,
+    pub at: Instant,
+}
+
+pub static HUB_STATS_CACHE: once_cell::sync::Lazy<RwLock<Option<HubStatsEntry>>> =
+    once_cell::sync::Lazy::new(|| RwLock::new(None));
+
+// Drop every entry derived from files under `repo_dir` (canonical, as from secure_join):
+// sidecar, siblings, paths-info, per-file digests and safetensors headers, plus the hub
+// inventory. Queue entries left behind in `evict_q` no longer match and are skipped when
+// popped.
+pub async fn evict_repo(repo_dir: &Path) {
+    let under = |p: &str| Path::new(p).starts_with(repo_dir);
+    SIDECAR_CACHE
+        .write()
+        .await
+        .inner
+        .retain(|(p, _, _), _| !p.starts_with(repo_dir));
+    SIBLINGS_CACHE
+        .write()
+        .await
+        .inner
+        .retain(|k, _| !k.split_once(':').is_some_and(|(_, p)| under(p)));
+    PATHS_INFO_CACHE
+        .write()
+        .await
+        .inner
+        .retain(|k, _| !k.split('|').next().is_some_and(under));
+    for cache in [&SHA256_CACHE, &SHA1_CACHE] {
+        cache
+            .write()
+            .await
+            .inner
+            .retain(|(p, _, _), _| !p.starts_with(repo_dir));
+    }
+    BLAKE3_CACHE
+        .write()
+        .await
+        .inner
+        .retain(|(p, _, _), _| !p.starts_with(repo_dir));
+    SAFETENSORS_CACHE
+        .write()
+        .await
+        .inner
+        .retain(|(p, _, _), _| !p.starts_with(repo_dir));
+    REFS_CACHE
+        .write()
+        .await
+        .inner
+        .retain(|(p, _, _), _| !p.starts_with(repo_dir));
+    REPO_META_CACHE
+        .write()
+        .await
+        .inner
+        .retain(|(p, _, _), _| !p.starts_with(repo_dir));
+    *HUB_STATS_CACHE.write().await = None;
+}
 
 #[derive(Clone)]
 pub struct IpAccessEntry {
@@ -97,20 +299,86 @@ pub struct IpAccessEntry {
     pub status: u16,
 }
 
-pub type IpAccessMap = HashMap<String, VecDeque<IpAccessEntry>>;
+// One client's slice of the IP log: the requests /admin/ip-log shows, plus the times of the
+// requests RATE_LIMIT admitted. Rejections never land in `admitted`, so they cannot push
+// counted requests out.
+#[derive(Default)]
+pub struct IpBucket {
+    pub entries: VecDeque<IpAccessEntry>,
+    pub admitted: VecDeque<i64>,
+}
+
+impl IpBucket {
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty() && self.admitted.is_empty()
+    }
+
+    // When this client was last seen, logged or admitted; 0 for an empty bucket.
+    pub fn last_at_ms(&self) -> i64 {
+        let logged = self.entries.back().map(|e| e.at_ms);
+        logged.max(self.admitted.back().copied()).unwrap_or(0)
+    }
+}
+
+pub type IpAccessMap = HashMap<String, IpBucket>;
 
-pub static IP_LOG: once_cell::sync::Lazy<RwLock<IpAccessMap>> =
-    once_cell::sync::Lazy::new(|| RwLock::new(HashMap::new()));
+// The per-IP log behind /admin/ip-log and RATE_LIMIT, one per AppState. `evict_q` lists
+// (last seen, ip) in the order clients were seen, so the least recently active bucket is
+// found from the front; entries whose bucket was seen again since, or removed, are skipped
+// when popped.
+#[derive(Default)]
+pub struct IpLog {
+    pub inner: IpAccessMap,
+    pub evict_q: VecDeque<(i64, String)>,
+}
 
-pub fn prune_ip_bucket(bucket: &mut VecDeque<IpAccessEntry>, now_ms: i64, retention_ms: i64) {
+pub fn prune_ip_bucket(bucket: &mut IpBucket, now_ms: i64, retention_ms: i64) {
     if retention_ms <= 0 {
         return;
     }
     let cutoff = now_ms.saturating_sub(retention_ms);
-    while let Some(front) = bucket.front() {
-        if front.at_ms >= cutoff {
-            break;
+    while bucket.entries.front().is_some_and(|e| e.at_ms < cutoff) {
+        bucket.entries.pop_front();
+    }
+    while bucket.admitted.front().is_some_and(|&at| at < cutoff) {
+        bucket.admitted.pop_front();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sidecar_cache_stays_within_cap() {
+        let mut cache = SidecarCache::default();
+        let entry = || SidecarEntry {
+            map: std::sync::Arc::new(HashMap::new()),
+            at: Instant::now(),
+        };
+        for i in 0..20u64 {
+            cache.insert_bounded((PathBuf::from(format!("/r{i}")), i, i), entry(), 8);
+            assert!(cache.inner.len() <= 8);
+        }
+        // The newest entries survive; the oldest went first
+        assert!(cache.inner.contains_key(&(PathBuf::from("/r19"), 19, 19)));
+        assert!(!cache.inner.contains_key(&(PathBuf::from("/r0"), 0, 0)));
+        // Reloading one key repeatedly neither evicts others nor grows the queue
+        for _ in 0..50 {
+            cache.insert_bounded((PathBuf::from("/r19"), 19, 19), entry(), 8);
+        }
+        assert_eq!(cache.inner.len(), 8);
+        assert!(cache.evict_q.len() <= 16);
+    }
+
+    #[test]
+    fn safetensors_cache_stays_within_cap() {
+        let mut cache = SafetensorsCache::default();
+        for i in 0..20u64 {
+            cache.insert_bounded((PathBuf::from(format!("/s{i}")), i, i), None, 8);
+            assert!(cache.inner.len() <= 8);
         }
-        bucket.pop_front();
+        assert!(cache.inner.contains_key(&(PathBuf::from("/s19"), 19, 19)));
+        assert!(!cache.inner.contains_key(&(PathBuf::from("/s0"), 0, 0)));
     }
 }
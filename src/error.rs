@@ -0,0 +1,218 @@
+use axum::Json;
+use std::borrow::Cow;
+
+use axum::http::{HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use serde_json::json;
+
+use crate::utils::repo_json::RepoKind;
+
+// Every error body is `{"error": <code>, "detail": <msg>}`. `error` is a stable machine
+// string clients can branch on; `detail` stays human-readable and may change.
+#[derive(Debug)]
+pub(crate) enum AppError {
+    RepoNotFound(RepoKind),
+    FileNotFound,
+    // URL shape matches no route
+    NotFound,
+    // under /api but no API handler matched
+    UnknownApiRoute,
+    PathForbidden,
+    // control characters, or a backslash on Unix, in a request path
+    InvalidPath,
+    // PUT upload while ALLOW_WRITES is off
+    WritesDisabled,
+    SidecarMissing(&'static str),
+    SidecarMalformed(&'static str),
+    EtagUnavailable,
+    RangeNotSatisfiable,
+    HashFailed(&'static str),
+    WriteFailed(&'static str),
+    BadRequest(&'static str),
+    InvalidBatch(&'static str),
+    PayloadTooLarge,
+    Unauthorized(&'static str),
+    // 401 without a valid token, 403 when one was sent but is wrong
+    GatedRepo {
+        kind: RepoKind,
+        repo_id: String,
+        authenticated: bool,
+    },
+    NotReady(String),
+    // repo create target already exists (and exist_ok was not set)
+    RepoExists(RepoKind),
+    // RATE_LIMIT exceeded; seconds until the client may retry
+    RateLimited {
+        retry_after: u64,
+    },
+    // FAULT_INJECT_RATE draw hit; carries FAULT_INJECT_STATUS
+    InjectedFault(StatusCode),
+    // PROXY_MODE fetch from HF_REMOTE_ENDPOINT failed
+    UpstreamFailed(&'static str),
+    // MAINTENANCE / POST /admin/maintenance: repo routes are down
+    Maintenance,
+}
+
+impl AppError {
+    pub(crate) fn status(&self) -> StatusCode {
+        match self {
+            Self::RepoNotFound(_) | Self::FileNotFound | Self::NotFound | Self::UnknownApiRoute => {
+                StatusCode::NOT_FOUND
+            }
+            Self::PathForbidden | Self::WritesDisabled => StatusCode::FORBIDDEN,
+            Self::SidecarMissing(_)
+            | Self::SidecarMalformed(_)
+            | Self::EtagUnavailable
+            | Self::HashFailed(_)
+            | Self::WriteFailed(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::RangeNotSatisfiable => StatusCode::RANGE_NOT_SATISFIABLE,
+            Self::BadRequest(_) | Self::InvalidPath => StatusCode::BAD_REQUEST,
+            Self::InvalidBatch(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Self::GatedRepo {
+                authenticated: false,
+                ..
+            } => StatusCode::UNAUTHORIZED,
+            Self::GatedRepo { .. } => StatusCode::FORBIDDEN,
+            Self::NotReady(_) | Self::Maintenance => StatusCode::SERVICE_UNAVAILABLE,
+            Self::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            Self::RepoExists(_) => StatusCode::CONFLICT,
+            Self::InjectedFault(status) => *status,
+            Self::UpstreamFailed(_) => StatusCode::BAD_GATEWAY,
+        }
+    }
+
+    pub(crate) fn code(&self) -> &'static str {
+        match self {
+            Self::RepoNotFound(_) => "repo_not_found",
+            Self::FileNotFound => "file_not_found",
+            Self::NotFound => "not_found",
+            Self::UnknownApiRoute => "unknown_api_route",
+            Self::PathForbidden => "path_forbidden",
+            Self::InvalidPath => "invalid_path",
+            Self::WritesDisabled => "writes_disabled",
+            Self::SidecarMissing(_) => "sidecar_missing",
+            Self::SidecarMalformed(_) => "sidecar_malformed",
+            Self::EtagUnavailable => "etag_unavailable",
+            Self::RangeNotSatisfiable => "range_not_satisfiable",
+            Self::HashFailed(_) => "hash_failed",
+            Self::WriteFailed(_) => "write_failed",
+            Self::BadRequest(_) => "bad_request",
+            Self::InvalidBatch(_) => "invalid_batch_request",
+            Self::PayloadTooLarge => "payload_too_large",
+            Self::Unauthorized(_) => "unauthorized",
+            Self::GatedRepo { .. } => "gated_repo",
+            Self::NotReady(_) => "not_ready",
+            Self::RateLimited { .. } => "rate_limited",
+            Self::RepoExists(_) => "repo_exists",
+            Self::InjectedFault(_) => "injected_fault",
+            Self::UpstreamFailed(_) => "upstream_failed",
+            Self::Maintenance => "maintenance",
+        }
+    }
+
+    pub(crate) fn detail(&self) -> Cow<'_, str> {
+        let msg = match self {
+            Self::RepoNotFound(RepoKind::Model) => "Repository not found",
+            Self::RepoNotFound(RepoKind::Dataset) => "Dataset not found",
+            Self::RepoNotFound(RepoKind::Space) => "Space not found",
+            Self::RepoExists(RepoKind::Model) => "You already created this model repo",
+            Self::RepoExists(RepoKind::Dataset) => "You already created this dataset repo",
+            Self::RepoExists(RepoKind::Space) => "You already created this space repo",
+            Self::FileNotFound => "File not found",
+            Self::NotFound => "Not Found",
+            Self::UnknownApiRoute => "No API route matches this path",
+            Self::PathForbidden => "Path not allowed",
+            Self::InvalidPath => "Path contains control characters or backslashes",
+            Self::WritesDisabled => "Uploads are disabled (set ALLOW_WRITES=1)",
+            Self::EtagUnavailable => "ETag not available",
+            Self::RangeNotSatisfiable => "Requested range not satisfiable",
+            Self::PayloadTooLarge => "Request body too large",
+            Self::RateLimited { .. } => "Too many requests",
+            Self::InjectedFault(_) => "Injected fault, retry later",
+            Self::Maintenance => "The Hub is down for maintenance, retry later",
+            Self::SidecarMissing(msg)
+            | Self::SidecarMalformed(msg)
+            | Self::HashFailed(msg)
+            | Self::WriteFailed(msg)
+            | Self::UpstreamFailed(msg)
+            | Self::BadRequest(msg)
+            | Self::InvalidBatch(msg)
+            | Self::Unauthorized(msg) => msg,
+            Self::NotReady(msg) => msg,
+            Self::GatedRepo {
+                kind,
+                repo_id,
+                authenticated,
+            } => {
+                let noun = match kind {
+                    RepoKind::Model => "model",
+                    RepoKind::Dataset => "dataset",
+                    RepoKind::Space => "space",
+                };
+                return Cow::Owned(if *authenticated {
+                    format!(
+                        "Access to {noun} {repo_id} is restricted and you are not in the authorized list. Visit the repo page to ask for access."
+                    )
+                } else {
+                    format!(
+                        "Access to {noun} {repo_id} is restricted. You must have access to it and be authenticated to access it. Please log in."
+                    )
+                });
+            }
+        };
+        Cow::Borrowed(msg)
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let detail = self.detail();
+        let body = json!({"error": self.code(), "detail": detail});
+        let mut resp = (self.status(), Json(body)).into_response();
+        // huggingface_hub keys GatedRepoError off these headers (bodies are dropped on HEAD)
+        if matches!(self, Self::GatedRepo { .. })
+            && let Ok(msg) = HeaderValue::from_str(&detail)
+        {
+            let h = resp.headers_mut();
+            h.insert("X-Error-Code", HeaderValue::from_static("GatedRepo"));
+            h.insert("X-Error-Message", msg);
+        }
+        match self {
+            Self::RateLimited { retry_after } => {
+                resp.headers_mut()
+                    .insert("Retry-After", HeaderValue::from(retry_after));
+            }
+            Self::InjectedFault(_) => {
+                resp.headers_mut()
+                    .insert("Retry-After", HeaderValue::from_static("1"));
+            }
+            Self::Maintenance => {
+                resp.headers_mut()
+                    .insert("Retry-After", HeaderValue::from_static("60"));
+            }
+            _ => {}
+        }
+        resp
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn body_carries_code_and_detail() {
+        let resp = AppError::RepoNotFound(RepoKind::Dataset).into_response();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(
+            v,
+            json!({"error": "repo_not_found", "detail": "Dataset not found"})
+        );
+    }
+}
@@ -4,41 +4,59 @@ use std::hash::{Hash, Hasher};
 use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::{Duration, Instant, UNIX_EPOCH};
+use std::sync::atomic::AtomicBool;
+use std::time::{Duration, Instant};
 
-use axum::body::Bytes;
+use axum::Router;
 use axum::extract::Request as AxRequest;
-use axum::http::StatusCode;
-use axum::response::{IntoResponse, Response};
-use axum::routing::get;
-use axum::{Json, Router};
+use axum::routing::{any, delete, get, post};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 use serde::Deserialize;
 use serde_json::{Value, json};
 
 use time::{UtcOffset, macros::format_description};
-use tracing::info;
+use tracing::{info, warn};
 use tracing_subscriber::fmt::time::OffsetTime;
 use tracing_subscriber::{EnvFilter, Registry, fmt, layer::SubscriberExt};
 
 #[global_allocator]
 static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
+mod access_log;
 mod app_state;
 mod caches;
+mod error;
+mod metrics;
 mod middleware;
+mod proxy;
 mod resolve;
+mod route_timing;
 mod routes_admin;
+mod routes_auth;
 mod routes_blake3;
 mod routes_datasets;
+mod routes_health;
+mod routes_lfs;
+mod routes_list;
 mod routes_models;
+mod routes_repo_api;
+mod routes_repos;
+mod routes_spaces;
+mod routes_xet;
+mod tls;
+mod upload;
 mod utils;
 
 use app_state::AppState;
-use caches::{PATHS_INFO_CACHE, PathsInfoEntry};
+use caches::{PATHS_INFO_CACHE, PathsInfoEntry, SidecarMap};
+use error::AppError;
+use utils::repo_json::RepoDefaults;
 // Only import what is used to avoid warnings
-use utils::sidecar::get_sidecar_map;
+use utils::sidecar::load_sidecar_map;
 
-pub(crate) const CHUNK_SIZE: usize = 262_144; // 256 KiB per read chunk
+pub(crate) const CHUNK_SIZE: usize = 262_144; // 256 KiB per read chunk, default for CHUNK_SIZE_BYTES
+const CHUNK_SIZE_RANGE: std::ops::RangeInclusive<usize> = 4_096..=16 * 1024 * 1024;
 
 #[tokio::main]
 async fn main() {
@@ -47,6 +65,18 @@ async fn main() {
     let root = env::var("FAKE_HUB_ROOT").unwrap_or_else(|_| "fake_hub".to_string());
     let root_abs = dunce::canonicalize(&root).unwrap_or_else(|_| PathBuf::from(&root));
 
+    let access_log = match env::var("ACCESS_LOG_PATH").ok().filter(|s| !s.is_empty()) {
+        Some(p) => match access_log::AccessLog::open(Path::new(&p), Duration::from_secs(1)).await {
+            Ok(log) => Some(log),
+            Err(e) => {
+                eprintln!("[fake-hub] cannot open ACCESS_LOG_PATH {p:?}: {e}");
+                std::process::exit(2);
+            }
+        },
+        None => None,
+    };
+
+    let cache_ttl = cache_ttl_or("CACHE_TTL_MS", Duration::from_millis(2_000));
     let state = AppState {
         root: Arc::new(root_abs.clone()),
         log_requests: !matches!(
@@ -88,12 +118,34 @@ async fn main() {
                 .unwrap_or(200);
             cap.max(1)
         },
-        cache_ttl: Duration::from_millis(
-            env::var("CACHE_TTL_MS")
+        rate_limit: env::var("RATE_LIMIT")
+            .ok()
+            .and_then(|s| s.parse::<usize>().ok())
+            .unwrap_or(0),
+        rate_window_secs: env::var("RATE_WINDOW_SECS")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or(60)
+            .max(1),
+        max_json_body_bytes: env::var("MAX_JSON_BODY_BYTES")
+            .ok()
+            .and_then(|s| s.parse::<usize>().ok())
+            .unwrap_or(1_048_576)
+            .max(1),
+        access_log,
+        ip_log_max_ips: {
+            let cap = env::var("IP_LOG_MAX_IPS")
                 .ok()
-                .and_then(|s| s.parse::<u64>().ok())
-                .unwrap_or(2_000),
-        ),
+                .and_then(|s| s.parse::<usize>().ok())
+                .unwrap_or(10_000);
+            cap.max(1)
+        },
+        ip_log: Arc::new(tokio::sync::RwLock::new(caches::IpLog::default())),
+        cache_ttl,
+        sidecar_cache_ttl: cache_ttl_or("SIDECAR_CACHE_TTL_MS", cache_ttl),
+        siblings_cache_ttl: cache_ttl_or("SIBLINGS_CACHE_TTL_MS", cache_ttl),
+        paths_info_cache_ttl: cache_ttl_or("PATHS_INFO_CACHE_TTL_MS", cache_ttl),
+        sha256_cache_ttl: cache_ttl_or("SHA256_CACHE_TTL_MS", cache_ttl),
         paths_info_cache_cap: env::var("PATHS_INFO_CACHE_CAP")
             .ok()
             .and_then(|s| s.parse::<usize>().ok())
@@ -102,80 +154,394 @@ async fn main() {
             .ok()
             .and_then(|s| s.parse::<usize>().ok())
             .unwrap_or(256),
+        sidecar_cache_cap: env::var("SIDECAR_CACHE_CAP")
+            .ok()
+            .and_then(|s| s.parse::<usize>().ok())
+            .unwrap_or(512),
         sha256_cache_cap: env::var("SHA256_CACHE_CAP")
             .ok()
             .and_then(|s| s.parse::<usize>().ok())
             .unwrap_or(1024),
+        blake3_cache_cap: env::var("BLAKE3_CACHE_CAP")
+            .ok()
+            .and_then(|s| s.parse::<usize>().ok())
+            .unwrap_or(1024),
+        chunk_size: env::var("CHUNK_SIZE_BYTES")
+            .ok()
+            .and_then(|s| s.parse::<usize>().ok())
+            .filter(|n| CHUNK_SIZE_RANGE.contains(n))
+            .unwrap_or(CHUNK_SIZE),
+        resolve_content_disposition: !matches!(
+            env::var("RESOLVE_CONTENT_DISPOSITION").as_deref(),
+            Ok("0") | Ok("false") | Ok("False")
+        ),
+        resolve_delay: Duration::from_millis(
+            env::var("RESOLVE_DELAY_MS")
+                .ok()
+                .and_then(|s| s.parse::<u64>().ok())
+                .unwrap_or(0),
+        ),
+        resolve_throttle_bps: env::var("RESOLVE_THROTTLE_BPS")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or(0),
+        resolve_cache_max_age: env::var("RESOLVE_CACHE_MAX_AGE")
+            .ok()
+            .and_then(|s| s.trim().parse::<u64>().ok())
+            .unwrap_or(3600),
+        fault_inject_rate: env::var("FAULT_INJECT_RATE")
+            .ok()
+            .and_then(|s| s.trim().parse::<f64>().ok())
+            .filter(|r| r.is_finite())
+            .map(|r| r.clamp(0.0, 1.0))
+            .unwrap_or(0.0),
+        fault_inject_status: env::var("FAULT_INJECT_STATUS")
+            .ok()
+            .and_then(|s| s.trim().parse::<u16>().ok())
+            .and_then(|c| axum::http::StatusCode::from_u16(c).ok())
+            .filter(|c| c.is_server_error())
+            .unwrap_or(axum::http::StatusCode::SERVICE_UNAVAILABLE),
+        etag_recompute: matches!(
+            env::var("ETAG_RECOMPUTE").as_deref(),
+            Ok("1") | Ok("true") | Ok("True")
+        ),
+        etag_fallback_compute: matches!(
+            env::var("ETAG_FALLBACK_COMPUTE").as_deref(),
+            Ok("1") | Ok("true") | Ok("True")
+        ),
+        sidecar_dir: env_nonempty("SIDECAR_DIR")
+            .map(|dir| Arc::new(dunce::canonicalize(&dir).unwrap_or_else(|_| PathBuf::from(&dir)))),
+        allow_symlink_escape: matches!(
+            env::var("ALLOW_SYMLINK_ESCAPE").as_deref(),
+            Ok("1") | Ok("true") | Ok("True")
+        ),
+        allow_writes: matches!(
+            env::var("ALLOW_WRITES").as_deref(),
+            Ok("1") | Ok("true") | Ok("True")
+        ),
+        lfs_threshold: env::var("LFS_THRESHOLD_BYTES")
+            .ok()
+            .and_then(|s| s.trim().parse::<u64>().ok())
+            .unwrap_or(10 * 1024 * 1024),
+        max_upload_bytes: env::var("MAX_UPLOAD_BYTES")
+            .ok()
+            .and_then(|s| s.trim().parse::<u64>().ok())
+            .unwrap_or(50 * 1024 * 1024 * 1024),
+        proxy_endpoint: matches!(
+            env::var("PROXY_MODE").as_deref(),
+            Ok("1") | Ok("true") | Ok("True")
+        )
+        .then(|| {
+            Arc::new(
+                env_nonempty("HF_REMOTE_ENDPOINT")
+                    .unwrap_or_else(|| "https://huggingface.co".to_string()),
+            )
+        }),
+        proxy_token: env_nonempty("HF_TOKEN").map(Arc::new),
+        xet_stub: matches!(
+            env::var("XET_STUB").as_deref(),
+            Ok("1") | Ok("true") | Ok("True")
+        ),
+        repo_defaults: Arc::new(RepoDefaults {
+            author: env_nonempty("FAKE_HUB_AUTHOR"),
+            pipeline_tag: env_nonempty("FAKE_HUB_PIPELINE_TAG"),
+            library_name: env_nonempty("FAKE_HUB_LIBRARY"),
+        }),
+        hub_user: Arc::new(
+            env::var("FAKE_HUB_USER")
+                .ok()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .unwrap_or_else(|| "local-user".to_string()),
+        ),
+        hub_token: env::var("FAKE_HUB_TOKEN")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .map(Arc::new),
+        cors_enabled: !matches!(
+            env::var("CORS_ENABLED").as_deref(),
+            Ok("0") | Ok("false") | Ok("False")
+        ),
+        cors_allow_origin: Arc::new(
+            env::var("CORS_ALLOW_ORIGIN")
+                .ok()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .unwrap_or_else(|| "*".to_string()),
+        ),
+        metrics_enabled: matches!(
+            env::var("METRICS_ENABLED").as_deref(),
+            Ok("1") | Ok("true") | Ok("True")
+        ),
+        compression_enabled: !matches!(
+            env::var("COMPRESSION_ENABLED").as_deref(),
+            Ok("0") | Ok("false") | Ok("False")
+        ),
+        admin_token: env::var("ADMIN_TOKEN")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .map(Arc::new),
+        maintenance: Arc::new(AtomicBool::new(matches!(
+            env::var("MAINTENANCE").as_deref(),
+            Ok("1") | Ok("true") | Ok("True")
+        ))),
     };
 
+    if state.allow_symlink_escape {
+        warn!(target: "fakehub", "[fake-hub] ALLOW_SYMLINK_ESCAPE on: symlinks under FAKE_HUB_ROOT may point outside it");
+    }
+    // SIDECAR_DIR: sidecars for read-only repo trees live at <SIDECAR_DIR>/<repo>.paths-info.json
+    if let Some(dir) = &state.sidecar_dir {
+        info!(target: "fakehub", "[fake-hub] SIDECAR_DIR = {} (checked before in-repo sidecars)", dir.display());
+    }
+
+    if state.rate_limit > 0 && state.rate_window_secs > state.ip_log_retention_secs {
+        warn!(target: "fakehub", "[fake-hub] RATE_LIMIT needs RATE_WINDOW_SECS within IP_LOG_RETENTION_SECS; limits will undercount");
+    }
+
+    if state.log_requests {
+        route_timing::spawn_reporter(Duration::from_secs(60));
+    }
+    // IP_LOG_SWEEP_SECS (default: a quarter of the retention window; 0 = off)
+    let ip_log_sweep_secs = env::var("IP_LOG_SWEEP_SECS")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or(state.ip_log_retention_secs / 4);
+    if ip_log_sweep_secs > 0 {
+        middleware::spawn_ip_log_sweeper(
+            state.ip_log.clone(),
+            Duration::from_secs(ip_log_sweep_secs),
+            state.ip_log_retention_secs,
+        );
+    }
+
+    // Optional fixture self-check; runs in the background and only reports
+    if matches!(
+        env::var("SIDECAR_VERIFY").as_deref(),
+        Ok("1") | Ok("true") | Ok("True")
+    ) {
+        let verify_root = root_abs.clone();
+        let verify_state = state.clone();
+        tokio::spawn(async move {
+            let (repos, problems) =
+                utils::sidecar::verify_sidecars(&verify_state, &verify_root).await;
+            if problems > 0 {
+                warn!(target: "fakehub", "[sidecar-verify] {} repos checked, {} mismatched or missing entries", repos, problems);
+            } else {
+                info!(target: "fakehub", "[sidecar-verify] {} repos checked, all sidecar sizes match", repos);
+            }
+        });
+    }
+
     // Startup log (respect LOG_REDACT)
     if state.log_redact {
         info!(target: "fakehub", "[fake-hub] FAKE_HUB_ROOT configured (redacted)");
     } else {
         info!(target: "fakehub", "[fake-hub] FAKE_HUB_ROOT = {}", root_abs.display());
     }
+    info!(target: "fakehub",
+        "[fake-hub] RESOLVE_DELAY_MS = {}, RESOLVE_THROTTLE_BPS = {} (0 = off)",
+        state.resolve_delay.as_millis(), state.resolve_throttle_bps
+    );
+    if let Some(endpoint) = &state.proxy_endpoint {
+        warn!(target: "fakehub", "[fake-hub] PROXY_MODE on: missing files are fetched from {} and stored under FAKE_HUB_ROOT", endpoint);
+    }
+    if state.allow_writes {
+        warn!(target: "fakehub", "[fake-hub] ALLOW_WRITES on: token holders may PUT files into repos");
+    }
+    if state.fault_inject_rate > 0.0 {
+        warn!(target: "fakehub",
+            "[fake-hub] FAULT_INJECT_RATE = {}: resolve requests may fail with {}",
+            state.fault_inject_rate, state.fault_inject_status.as_u16()
+        );
+    }
 
     // Build router
     let mut router = Router::new()
+        .route("/api/whoami-v2", get(routes_auth::get_whoami_v2))
         .route("/api/blake3/{*repo}", get(routes_blake3::get_repo_blake3))
+        .route("/api/models", get(routes_list::get_models_list))
+        .route("/api/datasets", get(routes_list::get_datasets_list))
         // Datasets catch-all under /api/datasets
         .route(
             "/api/datasets/{*rest}",
             get(routes_datasets::get_dataset_catchall_get)
                 .post(routes_datasets::get_dataset_paths_info_post),
         )
+        // Spaces catch-all under /api/spaces
+        .route(
+            "/api/spaces/{*rest}",
+            get(routes_spaces::get_space_catchall_get)
+                .post(routes_spaces::get_space_paths_info_post),
+        )
         // Models catch-all under /api/models
         .route(
             "/api/models/{*rest}",
             get(routes_models::get_model_catchall_get)
                 .post(routes_models::get_model_paths_info_post),
         )
-        // Resolve route fallback: GET and HEAD
-        .route(
-            "/{*rest}",
-            get(resolve::resolve_catchall).head(resolve::resolve_catchall),
-        );
+        .route("/api/repos/create", post(routes_repos::post_repo_create))
+        .route("/api/repos/delete", delete(routes_repos::delete_repo))
+        // Anything else under /api is an API 404, never a resolve lookup
+        .route("/api/{*rest}", any(unknown_api_route));
 
-    router = router.route("/admin/ip-log", get(routes_admin::get_ip_log));
+    router = router
+        .route("/admin/ip-log", get(routes_admin::get_ip_log))
+        .route(
+            "/admin/ip-log/summary",
+            get(routes_admin::get_ip_log_summary),
+        )
+        .route("/admin/caches/stats", get(routes_admin::get_cache_stats))
+        .route("/admin/config", get(routes_admin::get_config))
+        .route("/admin/maintenance", post(routes_admin::post_maintenance))
+        .route("/admin/stats", get(routes_admin::get_hub_stats));
+    if state.metrics_enabled {
+        router = router.route("/metrics", get(metrics::get_metrics));
+    }
+    // API/admin answers change underneath clients: never let caches reuse them unrevalidated
+    router = router.layer(axum::middleware::from_fn(middleware::no_cache_mw));
+    // Compress API JSON only; the resolve route is added after the layer so file bodies are untouched
+    if state.compression_enabled {
+        router = router.layer(middleware::json_compression_layer());
+    }
+    // Resolve route fallback: GET and HEAD, plus OPTIONS for browser preflight and PUT
+    // uploads (refused unless ALLOW_WRITES)
+    router = router.route(
+        "/{*rest}",
+        get(resolve::resolve_catchall)
+            .head(resolve::resolve_catchall)
+            .options(resolve::resolve_options)
+            .put(upload::resolve_put),
+    );
 
     let state_for_layer = state.clone();
     let app = router
         .with_state(state.clone())
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::maintenance_mw,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::cors_mw,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::metrics_mw,
+        ))
         .layer(axum::middleware::from_fn_with_state(
             state_for_layer,
             middleware::log_requests_mw,
-        ));
+        ))
+        // Probes are merged after the layers so they skip logging, metrics and CORS
+        .merge(
+            Router::new()
+                .route("/healthz", get(routes_health::get_healthz))
+                .route("/readyz", get(routes_health::get_readyz))
+                .with_state(state.clone()),
+        );
+
+    // Optional TLS: serve HTTPS only when both cert and key paths are set
+    let tls_cert = env::var("TLS_CERT_PATH").ok().filter(|s| !s.is_empty());
+    let tls_key = env::var("TLS_KEY_PATH").ok().filter(|s| !s.is_empty());
+    let tls_config = match (tls_cert, tls_key) {
+        (Some(cert), Some(key)) => {
+            match tls::load_rustls_config(Path::new(&cert), Path::new(&key)).await {
+                Ok(c) => Some(c),
+                Err(e) => {
+                    eprintln!("[fake-hub] TLS setup failed: {e}");
+                    std::process::exit(2);
+                }
+            }
+        }
+        (None, None) => None,
+        _ => {
+            warn!(target: "fakehub", "[fake-hub] TLS_CERT_PATH and TLS_KEY_PATH must both be set; serving plain HTTP");
+            None
+        }
+    };
+    let scheme = if tls_config.is_some() {
+        "https"
+    } else {
+        "http"
+    };
 
     // Bind server
-    let host = "0.0.0.0";
-    let port: u16 = 8000;
-    let listener = tokio::net::TcpListener::bind((host, port))
+    let host = env::var("FAKE_HUB_HOST")
+        .ok()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| "0.0.0.0".to_string());
+    let port: u16 = match env::var("FAKE_HUB_PORT") {
+        Ok(s) => match s.trim().parse() {
+            Ok(p) => p,
+            Err(_) => {
+                eprintln!("[fake-hub] invalid FAKE_HUB_PORT {s:?}: expected an integer in 0-65535");
+                std::process::exit(2);
+            }
+        },
+        Err(_) => 8000,
+    };
+    let listener = tokio::net::TcpListener::bind((host.as_str(), port))
         .await
         .expect("bind server");
     // Print accessible URLs: bound addr + loopback + best-effort LAN IP
     let bound = listener.local_addr().ok();
-    let loopback_url = format!("http://127.0.0.1:{port}");
+    // FAKE_HUB_PORT=0 binds an ephemeral port; advertise the one actually taken
+    let port = bound.map_or(port, |b| b.port());
+    let loopback_url = format!("{scheme}://127.0.0.1:{port}");
     let lan_ip = local_ipv4_guess();
     match (bound, lan_ip) {
         (Some(b), Some(ip)) => info!(target: "fakehub",
-            "[fake-hub] Listening on http://{} (local: {}, lan: http://{}:{})",
+            "[fake-hub] Listening on {scheme}://{} (local: {}, lan: {scheme}://{}:{})",
             b, loopback_url, ip, port
         ),
         (Some(b), None) => info!(target: "fakehub",
-            "[fake-hub] Listening on http://{} (local: {})",
+            "[fake-hub] Listening on {scheme}://{} (local: {})",
             b, loopback_url
         ),
         (None, Some(ip)) => info!(target: "fakehub",
-            "[fake-hub] Listening (lan: http://{}:{}, local: {})",
+            "[fake-hub] Listening (lan: {scheme}://{}:{}, local: {})",
             ip, port, loopback_url
         ),
-        _ => info!(target: "fakehub", "[fake-hub] Listening on {host}:{port}"),
+        _ => info!(target: "fakehub", "[fake-hub] Listening on {scheme}://{host}:{port}"),
+    }
+    // PRELOAD=1: warm the sidecar cache once the port is bound (PRELOAD_CONCURRENCY, default 8)
+    if matches!(
+        env::var("PRELOAD").as_deref(),
+        Ok("1") | Ok("true") | Ok("True")
+    ) {
+        let concurrency = env::var("PRELOAD_CONCURRENCY")
+            .ok()
+            .and_then(|s| s.parse::<usize>().ok())
+            .unwrap_or(8);
+        let preload_root = root_abs.clone();
+        let preload_state = state.clone();
+        tokio::spawn(async move {
+            let started = Instant::now();
+            let (warmed, failed) =
+                utils::sidecar::preload_sidecars(&preload_state, &preload_root, concurrency).await;
+            info!(target: "fakehub",
+                "[preload] warmed {} repos in {} ms ({} skipped)",
+                warmed, started.elapsed().as_millis(), failed
+            );
+        });
     }
     let make_service = app.into_make_service_with_connect_info::<SocketAddr>();
 
-    axum::serve(listener, make_service)
-        .await
-        .expect("server run");
+    // Both paths serve HTTP/1.1 and HTTP/2: ALPN picks the protocol under TLS, and the plain
+    // listener detects an h2c (prior knowledge) preface per connection.
+    if let Some(config) = tls_config {
+        let std_listener = listener.into_std().expect("convert listener");
+        axum_server::from_tcp_rustls(std_listener, config)
+            .serve(make_service)
+            .await
+            .expect("server run");
+    } else {
+        axum::serve(listener, make_service)
+            .await
+            .expect("server run");
+    }
 }
 
 fn init_tracing() {
@@ -192,6 +558,23 @@ fn init_tracing() {
     tracing::subscriber::set_global_default(subscriber).ok();
 }
 
+// Trimmed env value, None when unset or blank.
+fn env_nonempty(key: &str) -> Option<String> {
+    env::var(key)
+        .ok()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+// Milliseconds from `key`, or `default` when unset or unparseable.
+fn cache_ttl_or(key: &str, default: Duration) -> Duration {
+    env::var(key)
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .map(Duration::from_millis)
+        .unwrap_or(default)
+}
+
 // Best-effort LAN IPv4 detection without extra crates.
 // Uses UDP connect trick; no packets are sent until write, but OS selects an egress interface.
 fn local_ipv4_guess() -> Option<std::net::Ipv4Addr> {
@@ -202,73 +585,277 @@ fn local_ipv4_guess() -> Option<std::net::Ipv4Addr> {
         SocketAddr::from((std::net::IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1)), 80)),
     ];
     for dest in candidates {
-        if let Ok(s) = UdpSocket::bind("0.0.0.0:0") {
-            if s.connect(dest).is_ok() {
-                if let Ok(local) = s.local_addr() {
-                    if let std::net::IpAddr::V4(v4) = local.ip() {
-                        if !v4.is_loopback() && !v4.is_unspecified() {
-                            return Some(v4);
-                        }
-                    }
-                }
-            }
+        if let Ok(s) = UdpSocket::bind("0.0.0.0:0")
+            && s.connect(dest).is_ok()
+            && let Ok(local) = s.local_addr()
+            && let std::net::IpAddr::V4(v4) = local.ip()
+            && !v4.is_loopback()
+            && !v4.is_unspecified()
+        {
+            return Some(v4);
         }
     }
     None
 }
 
+// Query parameters accepted by the /api/{models,datasets} catch-all GET routes.
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct RepoApiQuery {
+    #[serde(default)]
+    pub limit: Option<usize>,
+    // tree listing: stat files and add their real `lastModified`
+    #[serde(default)]
+    pub with_mtime: Option<String>,
+    // tree listing: `expand=0` drops everything but path/type/size
+    #[serde(default)]
+    pub expand: Option<String>,
+    // tree listing: `recursive=1` lists every file below the path instead of one level
+    #[serde(default)]
+    pub recursive: Option<String>,
+}
+
+impl RepoApiQuery {
+    pub(crate) fn with_mtime(&self) -> bool {
+        matches!(
+            self.with_mtime.as_deref(),
+            Some("1") | Some("true") | Some("True")
+        )
+    }
+
+    pub(crate) fn recursive(&self) -> bool {
+        matches!(
+            self.recursive.as_deref(),
+            Some("1") | Some("true") | Some("True")
+        )
+    }
+
+    pub(crate) fn expand(&self) -> bool {
+        !matches!(
+            self.expand.as_deref(),
+            Some("0") | Some("false") | Some("False")
+        )
+    }
+}
+
+// Fallback for /api paths no API handler matched.
+pub(crate) async fn unknown_api_route() -> AppError {
+    AppError::UnknownApiRoute
+}
+
+// Index of the `tree` segment in `{repo_id}/tree/{revision}[/{path...}]`; only a `tree` that
+// follows an existing repo counts, so `org/tree` itself or a `tree` dir in the path is not it.
+pub(crate) fn tree_segment(parts: &[&str], is_repo: impl Fn(&str) -> bool) -> Option<usize> {
+    (1..parts.len().saturating_sub(1))
+        .find(|&i| parts[i] == "tree" && is_repo(&parts[..i].join("/")))
+}
+
+// Index of the `file-metadata` segment in `{repo_id}/file-metadata/{revision}/{path...}`,
+// anchored on an existing repo like `tree_segment`.
+pub(crate) fn file_metadata_segment(
+    parts: &[&str],
+    is_repo: impl Fn(&str) -> bool,
+) -> Option<usize> {
+    (1..parts.len().saturating_sub(2))
+        .find(|&i| parts[i] == "file-metadata" && is_repo(&parts[..i].join("/")))
+}
+
+// Sub-path after the tree revision, normalized with `normalize_rel` and '/'-joined ("" for
+// the repo root); None when it would climb out of the repo.
+pub(crate) fn tree_sub_path(segments: &[&str]) -> Option<String> {
+    let norm = utils::paths::normalize_rel(&segments.join("/"))?;
+    let parts: Vec<String> = norm
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    Some(parts.join("/"))
+}
+
+// Slim tree records down to PATHS_INFO_BASE_KEYS (the unexpanded tree shape).
+pub(crate) fn slim_tree_records(records: &mut [Value]) {
+    for rec in records.iter_mut() {
+        if let Some(obj) = rec.as_object_mut() {
+            obj.retain(|k, _| PATHS_INFO_BASE_KEYS.contains(&k.as_str()));
+        }
+    }
+}
+
+// Build a paths-info file record from a sidecar entry; None when the size is missing.
+fn sidecar_file_record(path: &str, sc: &Value) -> Option<Value> {
+    let size_i64 = sc.get("size").and_then(|v| v.as_i64()).or_else(|| {
+        sc.get("lfs")
+            .and_then(|v| v.get("size"))
+            .and_then(|v| v.as_i64())
+    })?;
+    let mut rec = serde_json::Map::new();
+    rec.insert("path".to_string(), json!(path));
+    rec.insert("type".to_string(), json!("file"));
+    rec.insert("size".to_string(), json!(size_i64));
+    if let Some(oid) = sc.get("oid").and_then(|v| v.as_str()) {
+        rec.insert("oid".to_string(), json!(oid));
+    }
+    if let Some(lfs) = sc.get("lfs").and_then(|v| v.as_object()) {
+        let mut ldict = serde_json::Map::new();
+        if let Some(loid) = lfs.get("oid").and_then(|v| v.as_str()) {
+            ldict.insert("oid".to_string(), json!(loid));
+        }
+        let lfs_size = lfs.get("size").and_then(|v| v.as_i64()).unwrap_or(size_i64);
+        ldict.insert("size".to_string(), json!(lfs_size));
+        rec.insert("lfs".to_string(), Value::Object(ldict));
+    }
+    Some(Value::Object(rec))
+}
+
+// Directory marker for `rel_dir` followed by every sidecar file under it (sorted by path).
+// None when any entry lacks a size.
+pub(crate) fn sidecar_dir_records(sc_map: &SidecarMap, rel_dir: &str) -> Option<Vec<Value>> {
+    let prefix = if rel_dir.is_empty() {
+        String::new()
+    } else {
+        format!("{}/", rel_dir)
+    };
+    let mut keys: Vec<&String> = sc_map
+        .keys()
+        .filter(|k| prefix.is_empty() || k.starts_with(&prefix))
+        .collect();
+    keys.sort();
+    let mut out = vec![json!({"path": rel_dir, "type": "directory"})];
+    for k in keys {
+        out.push(sidecar_file_record(k, &sc_map[k])?);
+    }
+    Some(out)
+}
+
 #[derive(Debug, Deserialize)]
 struct PathsInfoBody {
     #[serde(default)]
     paths: Option<Vec<String>>,
     #[serde(default)]
-    expand: Option<bool>,
+    expand: Option<ExpandArg>,
+    #[serde(default)]
+    limit: Option<usize>,
+    #[serde(default)]
+    cursor: Option<String>,
+}
+
+// `expand` is either a flag or a list of extra record keys to include (e.g. ["lfs"]).
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ExpandArg {
+    Flag(bool),
+    Fields(Vec<String>),
+}
+
+// Keys every file record keeps regardless of the requested expand fields.
+const PATHS_INFO_BASE_KEYS: &[&str] = &["path", "type", "size"];
+
+// `limit`/`cursor` may also come from the query string; body values win.
+#[derive(Debug, Default, Deserialize)]
+struct PathsInfoQuery {
+    limit: Option<usize>,
+    cursor: Option<String>,
+}
+
+// Opaque pagination cursor: URL-safe base64 of the last returned path.
+pub(crate) fn encode_cursor(path: &str) -> String {
+    URL_SAFE_NO_PAD.encode(path.as_bytes())
+}
+
+pub(crate) fn decode_cursor(cursor: &str) -> Option<String> {
+    let raw = URL_SAFE_NO_PAD.decode(cursor.trim_end_matches('=')).ok()?;
+    String::from_utf8(raw).ok()
+}
+
+// The single paths-info record for `rel` (`path`, `type`, `size`, `oid`, `lfs`); 404 when
+// the sidecar does not list it as a file.
+pub(crate) async fn file_metadata_response(
+    state: &AppState,
+    base_dir: &Path,
+    rel: &str,
+) -> Result<Value, AppError> {
+    let sc_map = load_sidecar_map(state, base_dir).await?;
+    let Some(sc) = sc_map.get(rel) else {
+        return Err(AppError::FileNotFound);
+    };
+    sidecar_file_record(rel, sc).ok_or(AppError::SidecarMissing("Sidecar missing size"))
+}
+
+// Wrap a page as `{"items", "next_cursor"}`; unpaginated requests keep the bare array.
+fn paths_info_body(items: Vec<Value>, next_cursor: Option<Option<String>>) -> Value {
+    match next_cursor {
+        Some(next) => json!({"items": items, "next_cursor": next}),
+        None => Value::Array(items),
+    }
 }
 
 pub(crate) async fn paths_info_response(
     state: &AppState,
     base_dir: &Path,
     req: AxRequest,
-) -> Result<Vec<Value>, Response> {
+) -> Result<Value, AppError> {
     // parse JSON body if any
-    let (_parts, body) = req.into_parts();
-    let body_bytes = axum::body::to_bytes(body, usize::MAX)
-        .await
-        .unwrap_or_else(|_| Bytes::new());
+    let (parts, body) = req.into_parts();
+    let query = axum::extract::Query::<PathsInfoQuery>::try_from_uri(&parts.uri)
+        .map(|q| q.0)
+        .unwrap_or_default();
+    let mut limit = query.limit;
+    let mut cursor = query.cursor;
+    let body_bytes =
+        utils::body::read_body_limited(&parts.headers, body, state.max_json_body_bytes).await?;
     let mut paths: Vec<String> = Vec::new();
     let mut expand = true;
-    if !body_bytes.is_empty() {
-        if let Ok(body) = serde_json::from_slice::<PathsInfoBody>(&body_bytes) {
-            if let Some(p) = body.paths {
-                paths = p.into_iter().filter(|s| !s.is_empty()).collect();
-            }
-            if let Some(e) = body.expand {
-                expand = e;
+    // Some(list) restricts records to the base keys plus the listed ones; unknown names are ignored.
+    let mut fields: Option<Vec<String>> = None;
+    if !body_bytes.is_empty()
+        && let Ok(body) = serde_json::from_slice::<PathsInfoBody>(&body_bytes)
+    {
+        if let Some(p) = body.paths {
+            paths = p.into_iter().filter(|s| !s.is_empty()).collect();
+        }
+        match body.expand {
+            Some(ExpandArg::Flag(e)) => expand = e,
+            Some(ExpandArg::Fields(f)) => {
+                let mut f: Vec<String> = f.into_iter().filter(|s| !s.is_empty()).collect();
+                f.sort();
+                f.dedup();
+                fields = Some(f);
             }
+            None => {}
+        }
+        if body.limit.is_some() {
+            limit = body.limit;
+        }
+        if body.cursor.is_some() {
+            cursor = body.cursor;
         }
     }
+    if limit == Some(0) {
+        return Err(AppError::BadRequest("limit must be >= 1"));
+    }
+    let after = match cursor.as_deref().filter(|c| !c.is_empty()) {
+        Some(c) => match decode_cursor(c) {
+            Some(p) => Some(p),
+            None => return Err(AppError::BadRequest("Invalid cursor")),
+        },
+        None => None,
+    };
+    let paginated = limit.is_some() || after.is_some();
 
     // Build cache key; base_dir comes from secure_join and is already canonical
     let base_abs = base_dir.to_path_buf();
-    let sidecar = base_abs.join(".paths-info.json");
-    let (sc_mtime, sc_size) = sidecar
-        .metadata()
-        .ok()
-        .and_then(|m| {
-            m.modified()
-                .ok()
-                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
-                .map(|d| (d.as_secs(), m.len()))
-        })
+    let (sc_mtime, sc_size) = utils::sidecar::find_sidecar(state, &base_abs)
+        .map(|sc| (sc.mtime, sc.size))
         .unwrap_or((0, 0));
     let mut paths_sorted = paths.clone();
     paths_sorted.sort();
     paths_sorted.dedup();
     let mut hasher = std::collections::hash_map::DefaultHasher::new();
     expand.hash(&mut hasher);
+    fields.hash(&mut hasher);
     for p in &paths_sorted {
         p.hash(&mut hasher);
     }
+    limit.hash(&mut hasher);
+    after.hash(&mut hasher);
     let req_sig = hasher.finish();
     let cache_key = format!(
         "{}|{}|{}|{}",
@@ -281,37 +868,31 @@ pub(crate) async fn paths_info_response(
     if let Some(hit) = {
         let cache = PATHS_INFO_CACHE.read().await;
         cache.inner.get(&cache_key).cloned()
-    } {
-        if Instant::now().duration_since(hit.at) < state.cache_ttl {
-            // LRU refresh on hit
-            let fresh = Instant::now();
-            let mut cachew = PATHS_INFO_CACHE.write().await;
-            let cloned_items = if let Some(entry) = cachew.inner.get_mut(&cache_key) {
-                entry.at = fresh;
-                Some(entry.items.clone())
-            } else {
-                None
-            };
-            cachew.evict_q.push_back((cache_key.clone(), fresh));
-            if let Some(items) = cloned_items {
-                return Ok(items);
-            }
-            return Ok(hit.items);
+    } && Instant::now().duration_since(hit.at) < state.paths_info_cache_ttl
+    {
+        metrics::PATHS_INFO_CACHE.hit();
+        // LRU refresh on hit
+        let fresh = Instant::now();
+        let mut cachew = PATHS_INFO_CACHE.write().await;
+        let cloned = if let Some(entry) = cachew.inner.get_mut(&cache_key) {
+            entry.at = fresh;
+            Some((entry.items.clone(), entry.next_cursor.clone()))
+        } else {
+            None
+        };
+        cachew.evict_q.push_back((cache_key.clone(), fresh));
+        if let Some((items, next)) = cloned {
+            return Ok(paths_info_body(items, next));
         }
+        return Ok(paths_info_body(hit.items, hit.next_cursor));
     }
+    metrics::PATHS_INFO_CACHE.miss();
 
     let mut results: Vec<Value> = Vec::new();
-    let sc_map = get_sidecar_map(&base_abs).await.unwrap_or_default();
+    let sc_map = load_sidecar_map(state, &base_abs).await?;
     if paths.is_empty() {
         if expand {
-            if let Some(vals) = utils::fs_walk::collect_paths_info_from_sidecar(&base_abs).await {
-                results = vals;
-            } else {
-                return Err(http_error(
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "Sidecar missing or incomplete",
-                ));
-            }
+            results = utils::fs_walk::collect_paths_info_from_sidecar(state, &base_abs).await?;
         } else {
             results.push(json!({"path": "", "type": "directory"}));
         }
@@ -320,16 +901,9 @@ pub(crate) async fn paths_info_response(
             let trimmed = p.trim();
             if trimmed.is_empty() || trimmed == "/" || trimmed == "." {
                 if expand {
-                    if let Some(vals) =
-                        utils::fs_walk::collect_paths_info_from_sidecar(&base_abs).await
-                    {
-                        results.extend(vals);
-                    } else {
-                        return Err(http_error(
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            "Sidecar missing or incomplete",
-                        ));
-                    }
+                    results.extend(
+                        utils::fs_walk::collect_paths_info_from_sidecar(state, &base_abs).await?,
+                    );
                 } else {
                     results.push(json!({"path": "", "type": "directory"}));
                 }
@@ -339,104 +913,22 @@ pub(crate) async fn paths_info_response(
             let rel_norm = norm_rel.replace('\\', "/");
             if expand {
                 if let Some(sc) = sc_map.get(&rel_norm) {
-                    let Some(size_i64) = sc.get("size").and_then(|v| v.as_i64()).or_else(|| {
-                        sc.get("lfs")
-                            .and_then(|v| v.get("size"))
-                            .and_then(|v| v.as_i64())
-                    }) else {
-                        return Err(http_error(
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            "Sidecar missing size",
-                        ));
+                    let Some(rec) = sidecar_file_record(&rel_norm, sc) else {
+                        return Err(AppError::SidecarMissing("Sidecar missing size"));
                     };
-                    let mut rec = serde_json::Map::new();
-                    rec.insert("path".to_string(), json!(rel_norm));
-                    rec.insert("type".to_string(), json!("file"));
-                    rec.insert("size".to_string(), json!(size_i64));
-                    if let Some(oid) = sc.get("oid").and_then(|v| v.as_str()) {
-                        rec.insert("oid".to_string(), json!(oid));
-                    }
-                    if let Some(lfs) = sc.get("lfs").and_then(|v| v.as_object()) {
-                        let mut ldict = serde_json::Map::new();
-                        if let Some(loid) = lfs.get("oid").and_then(|v| v.as_str()) {
-                            ldict.insert("oid".to_string(), json!(loid));
-                        }
-                        let lfs_size = lfs.get("size").and_then(|v| v.as_i64()).unwrap_or(size_i64);
-                        ldict.insert("size".to_string(), json!(lfs_size));
-                        rec.insert("lfs".to_string(), Value::Object(ldict));
-                    }
-                    results.push(Value::Object(rec));
+                    results.push(rec);
                 } else {
-                    results.push(json!({"path": rel_norm.clone(), "type": "directory"}));
-                    let prefix = if rel_norm.is_empty() {
-                        String::new()
-                    } else {
-                        format!("{}/", rel_norm)
+                    let Some(recs) = sidecar_dir_records(&sc_map, &rel_norm) else {
+                        return Err(AppError::SidecarMissing("Sidecar missing size"));
                     };
-                    for (k, v) in sc_map.iter() {
-                        if prefix.is_empty() || k.starts_with(&prefix) {
-                            let Some(size_i64) =
-                                v.get("size").and_then(|x| x.as_i64()).or_else(|| {
-                                    v.get("lfs")
-                                        .and_then(|x| x.get("size"))
-                                        .and_then(|x| x.as_i64())
-                                })
-                            else {
-                                return Err(http_error(
-                                    StatusCode::INTERNAL_SERVER_ERROR,
-                                    "Sidecar missing size",
-                                ));
-                            };
-                            let mut rec = serde_json::Map::new();
-                            rec.insert("path".to_string(), json!(k));
-                            rec.insert("type".to_string(), json!("file"));
-                            rec.insert("size".to_string(), json!(size_i64));
-                            if let Some(oid) = v.get("oid").and_then(|x| x.as_str()) {
-                                rec.insert("oid".to_string(), json!(oid));
-                            }
-                            if let Some(lfs) = v.get("lfs").and_then(|x| x.as_object()) {
-                                let mut ldict = serde_json::Map::new();
-                                if let Some(loid) = lfs.get("oid").and_then(|x| x.as_str()) {
-                                    ldict.insert("oid".to_string(), json!(loid));
-                                }
-                                let lfs_size =
-                                    lfs.get("size").and_then(|x| x.as_i64()).unwrap_or(size_i64);
-                                ldict.insert("size".to_string(), json!(lfs_size));
-                                rec.insert("lfs".to_string(), Value::Object(ldict));
-                            }
-                            results.push(Value::Object(rec));
-                        }
-                    }
+                    results.extend(recs);
                 }
             } else {
                 if let Some(sc) = sc_map.get(&rel_norm) {
-                    let Some(size_i64) = sc.get("size").and_then(|v| v.as_i64()).or_else(|| {
-                        sc.get("lfs")
-                            .and_then(|v| v.get("size"))
-                            .and_then(|v| v.as_i64())
-                    }) else {
-                        return Err(http_error(
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            "Sidecar missing size",
-                        ));
+                    let Some(rec) = sidecar_file_record(&rel_norm, sc) else {
+                        return Err(AppError::SidecarMissing("Sidecar missing size"));
                     };
-                    let mut rec = serde_json::Map::new();
-                    rec.insert("path".to_string(), json!(rel_norm));
-                    rec.insert("type".to_string(), json!("file"));
-                    rec.insert("size".to_string(), json!(size_i64));
-                    if let Some(oid) = sc.get("oid").and_then(|v| v.as_str()) {
-                        rec.insert("oid".to_string(), json!(oid));
-                    }
-                    if let Some(lfs) = sc.get("lfs").and_then(|v| v.as_object()) {
-                        let mut ldict = serde_json::Map::new();
-                        if let Some(loid) = lfs.get("oid").and_then(|v| v.as_str()) {
-                            ldict.insert("oid".to_string(), json!(loid));
-                        }
-                        let lfs_size = lfs.get("size").and_then(|v| v.as_i64()).unwrap_or(size_i64);
-                        ldict.insert("size".to_string(), json!(lfs_size));
-                        rec.insert("lfs".to_string(), Value::Object(ldict));
-                    }
-                    results.push(Value::Object(rec));
+                    results.push(rec);
                 } else {
                     results.push(json!({"path": rel_norm, "type": "directory"}));
                 }
@@ -453,6 +945,38 @@ pub(crate) async fn paths_info_response(
             unique.push(it);
         }
     }
+    if let Some(fields) = &fields {
+        for it in unique.iter_mut() {
+            if let Some(obj) = it.as_object_mut() {
+                obj.retain(|k, _| {
+                    PATHS_INFO_BASE_KEYS.contains(&k.as_str()) || fields.iter().any(|f| f == k)
+                });
+            }
+        }
+    }
+    // Pages are cut from the path-sorted list, resuming strictly after the cursor path.
+    let mut next_cursor: Option<Option<String>> = None;
+    if paginated {
+        unique.sort_by(|a, b| {
+            let pa = a["path"].as_str().unwrap_or("");
+            let pb = b["path"].as_str().unwrap_or("");
+            pa.cmp(pb)
+        });
+        if let Some(after) = after.as_deref() {
+            unique.retain(|it| it["path"].as_str().unwrap_or("") > after);
+        }
+        let mut next = None;
+        if let Some(n) = limit
+            && unique.len() > n
+        {
+            unique.truncate(n);
+            next = unique
+                .last()
+                .and_then(|it| it["path"].as_str())
+                .map(encode_cursor);
+        }
+        next_cursor = Some(next);
+    }
     let unique_clone = unique.clone();
     {
         let mut cache = PATHS_INFO_CACHE.write().await;
@@ -460,11 +984,11 @@ pub(crate) async fn paths_info_response(
         // Evict in O(1) amortized using insertion queue
         if cache.inner.len() >= state.paths_info_cache_cap {
             while let Some((old_k, old_at)) = cache.evict_q.pop_front() {
-                if let Some(entry) = cache.inner.get(&old_k) {
-                    if entry.at == old_at {
-                        cache.inner.remove(&old_k);
-                        break;
-                    }
+                if let Some(entry) = cache.inner.get(&old_k)
+                    && entry.at == old_at
+                {
+                    cache.inner.remove(&old_k);
+                    break;
                 }
             }
         }
@@ -473,20 +997,10 @@ pub(crate) async fn paths_info_response(
             cache_key,
             PathsInfoEntry {
                 items: unique_clone,
+                next_cursor: next_cursor.clone(),
                 at: now_i,
             },
         );
     }
-    Ok(unique)
-}
-
-// ============ Helpers ============
-pub(crate) fn http_not_found(msg: &str) -> Response {
-    let body = json!({"detail": msg});
-    (StatusCode::NOT_FOUND, Json(body)).into_response()
-}
-
-pub(crate) fn http_error(status: StatusCode, msg: &str) -> Response {
-    let body = json!({"detail": msg});
-    (status, Json(body)).into_response()
+    Ok(paths_info_body(unique, next_cursor))
 }
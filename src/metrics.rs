@@ -0,0 +1,173 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use axum::http::{HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+
+// Process-wide counters rendered in Prometheus text exposition format.
+// Everything is a plain atomic except the (method, status) table, whose keys are dynamic.
+
+pub struct CacheCounters {
+    pub hits: AtomicU64,
+    pub misses: AtomicU64,
+}
+
+impl CacheCounters {
+    const fn new() -> Self {
+        Self {
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn miss(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub static SIDECAR_CACHE: CacheCounters = CacheCounters::new();
+pub static SIBLINGS_CACHE: CacheCounters = CacheCounters::new();
+pub static PATHS_INFO_CACHE: CacheCounters = CacheCounters::new();
+pub static SHA256_CACHE: CacheCounters = CacheCounters::new();
+pub static SHA1_CACHE: CacheCounters = CacheCounters::new();
+pub static BLAKE3_CACHE: CacheCounters = CacheCounters::new();
+
+pub static RESOLVE_BYTES: AtomicU64 = AtomicU64::new(0);
+
+// Upper bounds (ms) of the request duration histogram; +Inf is implicit.
+const DURATION_BUCKETS_MS: [u64; 11] = [5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10000];
+
+struct DurationHistogram {
+    buckets: [AtomicU64; DURATION_BUCKETS_MS.len()],
+    count: AtomicU64,
+    sum_ms: AtomicU64,
+}
+
+static DURATION: DurationHistogram = DurationHistogram {
+    buckets: [const { AtomicU64::new(0) }; DURATION_BUCKETS_MS.len()],
+    count: AtomicU64::new(0),
+    sum_ms: AtomicU64::new(0),
+};
+
+static REQUESTS: Mutex<BTreeMap<(String, u16), u64>> = Mutex::new(BTreeMap::new());
+
+pub fn record_request(method: &str, status: u16, dur_ms: u64) {
+    if let Ok(mut map) = REQUESTS.lock() {
+        *map.entry((method.to_string(), status)).or_insert(0) += 1;
+    }
+    // Buckets are stored non-cumulative and summed at render time.
+    if let Some(i) = DURATION_BUCKETS_MS.iter().position(|&b| dur_ms <= b) {
+        DURATION.buckets[i].fetch_add(1, Ordering::Relaxed);
+    }
+    DURATION.count.fetch_add(1, Ordering::Relaxed);
+    DURATION.sum_ms.fetch_add(dur_ms, Ordering::Relaxed);
+}
+
+pub fn add_resolve_bytes(n: u64) {
+    RESOLVE_BYTES.fetch_add(n, Ordering::Relaxed);
+}
+
+pub fn render() -> String {
+    let mut out = String::new();
+    out.push_str("# HELP fakehub_requests_total Total HTTP requests by method and status.\n");
+    out.push_str("# TYPE fakehub_requests_total counter\n");
+    if let Ok(map) = REQUESTS.lock() {
+        for ((method, status), n) in map.iter() {
+            let _ = writeln!(
+                out,
+                "fakehub_requests_total{{method=\"{method}\",status=\"{status}\"}} {n}"
+            );
+        }
+    }
+
+    out.push_str("# HELP fakehub_request_duration_seconds Request latency.\n");
+    out.push_str("# TYPE fakehub_request_duration_seconds histogram\n");
+    let mut cumulative = 0u64;
+    for (i, bound) in DURATION_BUCKETS_MS.iter().enumerate() {
+        cumulative += DURATION.buckets[i].load(Ordering::Relaxed);
+        let le = *bound as f64 / 1000.0;
+        let _ = writeln!(
+            out,
+            "fakehub_request_duration_seconds_bucket{{le=\"{le}\"}} {cumulative}"
+        );
+    }
+    let count = DURATION.count.load(Ordering::Relaxed);
+    let sum = DURATION.sum_ms.load(Ordering::Relaxed) as f64 / 1000.0;
+    let _ = writeln!(
+        out,
+        "fakehub_request_duration_seconds_bucket{{le=\"+Inf\"}} {count}"
+    );
+    let _ = writeln!(out, "fakehub_request_duration_seconds_sum {sum}");
+    let _ = writeln!(out, "fakehub_request_duration_seconds_count {count}");
+
+    out.push_str("# HELP fakehub_resolve_bytes_total Bytes served by resolve responses.\n");
+    out.push_str("# TYPE fakehub_resolve_bytes_total counter\n");
+    let _ = writeln!(
+        out,
+        "fakehub_resolve_bytes_total {}",
+        RESOLVE_BYTES.load(Ordering::Relaxed)
+    );
+
+    out.push_str("# HELP fakehub_cache_hits_total Cache hits by cache.\n");
+    out.push_str("# TYPE fakehub_cache_hits_total counter\n");
+    for (name, c) in cache_counters() {
+        let _ = writeln!(
+            out,
+            "fakehub_cache_hits_total{{cache=\"{name}\"}} {}",
+            c.hits.load(Ordering::Relaxed)
+        );
+    }
+    out.push_str("# HELP fakehub_cache_misses_total Cache misses by cache.\n");
+    out.push_str("# TYPE fakehub_cache_misses_total counter\n");
+    for (name, c) in cache_counters() {
+        let _ = writeln!(
+            out,
+            "fakehub_cache_misses_total{{cache=\"{name}\"}} {}",
+            c.misses.load(Ordering::Relaxed)
+        );
+    }
+    out
+}
+
+pub fn cache_counters() -> [(&'static str, &'static CacheCounters); 6] {
+    [
+        ("sidecar", &SIDECAR_CACHE),
+        ("siblings", &SIBLINGS_CACHE),
+        ("paths_info", &PATHS_INFO_CACHE),
+        ("sha256", &SHA256_CACHE),
+        ("sha1", &SHA1_CACHE),
+        ("blake3", &BLAKE3_CACHE),
+    ]
+}
+
+pub(crate) async fn get_metrics() -> Response {
+    let mut resp = (StatusCode::OK, render()).into_response();
+    resp.headers_mut().insert(
+        "Content-Type",
+        HeaderValue::from_static("text/plain; version=0.0.4; charset=utf-8"),
+    );
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_exposition() {
+        record_request("GET", 200, 7);
+        SHA256_CACHE.hit();
+        add_resolve_bytes(10);
+        let text = render();
+        assert!(text.contains("fakehub_requests_total{method=\"GET\",status=\"200\"}"));
+        assert!(text.contains("fakehub_request_duration_seconds_bucket{le=\"+Inf\"}"));
+        assert!(text.contains("fakehub_cache_hits_total{cache=\"sha256\"}"));
+        assert!(text.contains("# TYPE fakehub_resolve_bytes_total counter"));
+    }
+}
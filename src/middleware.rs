@@ -1,18 +1,25 @@
-use std::collections::VecDeque;
 use std::net::SocketAddr;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use axum::body::Body;
 use axum::extract::connect_info::ConnectInfo;
 use axum::extract::{Request as AxRequest, State};
-use axum::http::HeaderValue;
-use axum::response::Response;
+use axum::http::{Extensions, HeaderMap, HeaderValue, Method, StatusCode, Version};
+use axum::response::{IntoResponse, Response};
 use serde_json::json;
+use tokio::sync::RwLock;
+use tower_http::compression::CompressionLayer;
+use tower_http::compression::predicate::{DefaultPredicate, Predicate};
 use tracing::info;
 use uuid::Uuid;
 
+use crate::access_log::AccessRecord;
 use crate::app_state::AppState;
-use crate::caches::{IP_LOG, IpAccessEntry, prune_ip_bucket};
+use crate::caches::{IpAccessEntry, IpBucket, IpLog, prune_ip_bucket};
+use crate::error::AppError;
+use crate::utils::body::{declared_length, read_body_limited};
 
 // Request logging middleware with safe body handling and header redaction.
 pub(crate) async fn log_requests_mw(
@@ -21,10 +28,46 @@ pub(crate) async fn log_requests_mw(
     next: axum::middleware::Next,
 ) -> Response {
     if !state.log_requests {
-        return next.run(req).await;
+        if state.access_log.is_none() && state.rate_limit == 0 {
+            return next.run(req).await;
+        }
+        // Structured access log and/or rate limiting only; the human-readable log stays off.
+        let req_id = request_id(req.headers());
+        let trace_id = trace_id(req.headers());
+        let method = req.method().clone();
+        let uri = req.uri().clone();
+        let connect_ip = req
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ci| ci.0);
+        let client_ip = extract_client_ip(req.headers(), connect_ip);
+        let started = std::time::Instant::now();
+        let mut resp = match rate_limit_rejection(&state, client_ip.as_deref()).await {
+            Some(resp) => resp,
+            None => next.run(req).await,
+        };
+        let dur_ms = started.elapsed().as_millis() as u64;
+        if let Some(access_log) = &state.access_log {
+            let _ = resp.headers_mut().insert(
+                "X-Request-ID",
+                HeaderValue::from_str(&req_id).unwrap_or_else(|_| HeaderValue::from_static("-")),
+            );
+            let line = access_line(
+                &req_id,
+                trace_id.as_deref(),
+                &method,
+                &uri,
+                client_ip.as_deref(),
+                dur_ms,
+                &resp,
+            );
+            access_log.write(line).await;
+        }
+        return resp;
     }
 
-    let req_id = Uuid::new_v4().to_string()[..12].to_string();
+    let req_id = request_id(req.headers());
+    let trace_id = trace_id(req.headers());
     let method = req.method().clone();
     let uri = req.uri().clone();
     let connect_ip = req
@@ -32,6 +75,7 @@ pub(crate) async fn log_requests_mw(
         .get::<ConnectInfo<SocketAddr>>()
         .map(|ci| ci.0);
     let headers = req.headers().clone();
+    let client_ip = extract_client_ip(&headers, connect_ip);
     let ct = headers
         .get("content-type")
         .and_then(|v| v.to_str().ok())
@@ -73,27 +117,29 @@ pub(crate) async fn log_requests_mw(
     // Optionally log JSON body, without consuming it for downstream handlers.
     // Read the full body into memory, log a truncated snippet, and restore it.
     let mut body_snippet: Option<String> = None;
+    let mut rejected = rate_limit_rejection(&state, client_ip.as_deref()).await;
     let should_log_body = state.log_body_all
         || (state.log_json_body && ct.to_ascii_lowercase().contains("application/json"));
-    if should_log_body {
+    if should_log_body && rejected.is_none() {
         // Only read body when Content-Length exists and is within safe bounds.
-        let cl_opt = headers
-            .get("content-length")
-            .and_then(|v| v.to_str().ok())
-            .and_then(|s| s.parse::<usize>().ok());
+        let cl_opt = declared_length(&headers);
         let hard_skip_threshold = state.log_body_max.saturating_mul(4);
         match cl_opt {
             None => {
                 // Unknown length (chunked or missing): skip reading to avoid unbounded memory.
                 body_snippet = Some("<skipped unknown content-length>".to_string());
             }
+            Some(cl) if cl > state.max_json_body_bytes => {
+                body_snippet = Some(format!("<rejected body: content-length={cl}>"));
+                rejected = Some(AppError::PayloadTooLarge.into_response());
+            }
             Some(cl) if cl > hard_skip_threshold => {
                 body_snippet = Some(format!("<skipped large body: content-length={cl}>"));
             }
             Some(_) => {
                 let (parts, body) = req.into_parts();
                 // Read full body (bounded by CL) and restore; log truncated snippet only.
-                match axum::body::to_bytes(body, usize::MAX).await {
+                match read_body_limited(&parts.headers, body, state.max_json_body_bytes).await {
                     Ok(bytes) => {
                         let slice_len = std::cmp::min(bytes.len(), state.log_body_max);
                         if slice_len > 0 {
@@ -104,6 +150,10 @@ pub(crate) async fn log_requests_mw(
                         }
                         req = AxRequest::from_parts(parts, Body::from(bytes));
                     }
+                    Err(e @ AppError::PayloadTooLarge) => {
+                        rejected = Some(e.into_response());
+                        req = AxRequest::from_parts(parts, Body::empty());
+                    }
                     Err(_) => {
                         req = AxRequest::from_parts(parts, Body::empty());
                     }
@@ -114,6 +164,7 @@ pub(crate) async fn log_requests_mw(
 
     info!(
         target: "fakehub",
+        trace_id = trace_id.as_deref(),
         "[{}] HTTP {} {}",
         req_id,
         method,
@@ -125,9 +176,13 @@ pub(crate) async fn log_requests_mw(
     }
 
     let started = std::time::Instant::now();
-    let mut resp = next.run(req).await;
+    let mut resp = match rejected {
+        Some(resp) => resp,
+        None => next.run(req).await,
+    };
     let dur_ms = started.elapsed().as_millis();
     let status = resp.status();
+    crate::route_timing::record(uri.path(), dur_ms as u64);
     // attach X-Request-ID before logging to avoid borrow conflicts
     let _ = resp.headers_mut().insert(
         "X-Request-ID",
@@ -148,6 +203,7 @@ pub(crate) async fn log_requests_mw(
 
     info!(
         target: "fakehub",
+        trace_id = trace_id.as_deref(),
         "[{}] Response {} -> {} ({} ms) ct={} len={}",
         req_id,
         method,
@@ -168,37 +224,357 @@ pub(crate) async fn log_requests_mw(
         info!(target: "fakehub", "[{}] Response headers: {}", req_id, serde_json::to_string(&hdrs).unwrap_or_default());
     }
 
-    if let Some(ip_key) = extract_client_ip(&headers, connect_ip) {
-        let now_ms = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .map(|d| d.as_millis() as i64)
-            .unwrap_or(0);
-        let path = uri
-            .path_and_query()
-            .map(|pq| pq.as_str().to_string())
-            .unwrap_or_else(|| uri.path().to_string());
-        let retention_ms_u64 = state.ip_log_retention_secs.saturating_mul(1000);
-        let retention_ms = std::cmp::min(retention_ms_u64, i64::MAX as u64) as i64;
-        let per_ip_cap = state.ip_log_per_ip_cap;
-        let mut map = IP_LOG.write().await;
-        let bucket = map.entry(ip_key).or_insert_with(VecDeque::new);
-        prune_ip_bucket(bucket, now_ms, retention_ms);
-        if bucket.len() >= per_ip_cap {
-            while bucket.len() >= per_ip_cap {
-                bucket.pop_front();
+    if let Some(access_log) = &state.access_log {
+        let line = access_line(
+            &req_id,
+            trace_id.as_deref(),
+            &method,
+            &uri,
+            client_ip.as_deref(),
+            dur_ms as u64,
+            &resp,
+        );
+        access_log.write(line).await;
+    }
+
+    if let Some(ip_key) = client_ip {
+        record_ip_access(&state, ip_key, &method, &uri, status).await;
+    }
+
+    resp
+}
+
+// An incoming X-Request-ID from an upstream proxy when it is a short token of
+// [A-Za-z0-9._-]; otherwise a fresh id.
+fn request_id(headers: &HeaderMap) -> String {
+    headers
+        .get("x-request-id")
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|id| {
+            (1..=64).contains(&id.len())
+                && id
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'_' | b'-'))
+        })
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string()[..12].to_string())
+}
+
+// Trace id from a W3C `traceparent` (`{version}-{trace-id}-{parent-id}-{flags}`). Checked
+// loosely: hex fields of the right widths and a non-zero trace id; anything else is ignored.
+fn trace_id(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get("traceparent")?.to_str().ok()?.trim();
+    let parts: Vec<&str> = value.split('-').collect();
+    let hex = |s: &str, len: usize| s.len() == len && s.bytes().all(|b| b.is_ascii_hexdigit());
+    if parts.len() < 4
+        || !hex(parts[0], 2)
+        || parts[0].eq_ignore_ascii_case("ff")
+        || !hex(parts[1], 32)
+        || !hex(parts[2], 16)
+        || !hex(parts[3], 2)
+        || parts[1].bytes().all(|b| b == b'0')
+    {
+        return None;
+    }
+    Some(parts[1].to_ascii_lowercase())
+}
+
+// The bucket for `ip`, pruned to the retention window and marked as seen at `now_ms`; a
+// new IP first makes room under IP_LOG_MAX_IPS.
+fn ip_bucket<'a>(log: &'a mut IpLog, state: &AppState, ip: &str, now_ms: i64) -> &'a mut IpBucket {
+    let retention_ms_u64 = state.ip_log_retention_secs.saturating_mul(1000);
+    let retention_ms = std::cmp::min(retention_ms_u64, i64::MAX as u64) as i64;
+    if !log.inner.contains_key(ip) && log.inner.len() >= state.ip_log_max_ips {
+        evict_ip_buckets(log, state.ip_log_max_ips - 1);
+    }
+    log.evict_q.push_back((now_ms, ip.to_string()));
+    if log.evict_q.len() > 2 * state.ip_log_max_ips {
+        let inner = &log.inner;
+        log.evict_q
+            .retain(|(at, ip)| inner.get(ip).is_none_or(|b| b.last_at_ms() <= *at));
+    }
+    let bucket = log.inner.entry(ip.to_string()).or_default();
+    prune_ip_bucket(bucket, now_ms, retention_ms);
+    bucket
+}
+
+// Append one request to the per-IP log behind /admin/ip-log.
+async fn record_ip_access(
+    state: &AppState,
+    ip_key: String,
+    method: &Method,
+    uri: &axum::http::Uri,
+    status: StatusCode,
+) {
+    let now_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0);
+    let path = uri
+        .path_and_query()
+        .map(|pq| pq.as_str().to_string())
+        .unwrap_or_else(|| uri.path().to_string());
+    let per_ip_cap = state.ip_log_per_ip_cap;
+    let mut log = state.ip_log.write().await;
+    let bucket = &mut ip_bucket(&mut log, state, &ip_key, now_ms).entries;
+    while bucket.len() >= per_ip_cap {
+        bucket.pop_front();
+    }
+    bucket.push_back(IpAccessEntry {
+        at_ms: now_ms,
+        method: method.to_string(),
+        path,
+        status: status.as_u16(),
+    });
+}
+
+// RATE_LIMIT: 429 once `ip` already had `rate_limit` requests admitted within
+// RATE_WINDOW_SECS. The count and the admission happen under one IP log write lock, so
+// concurrent requests cannot all slip past the check; 429s are never admitted, so
+// Retry-After is when the oldest admitted request leaves the window and frees a slot.
+async fn rate_limit_rejection(state: &AppState, client_ip: Option<&str>) -> Option<Response> {
+    if state.rate_limit == 0 {
+        return None;
+    }
+    let ip = client_ip?;
+    let now_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0);
+    let window_ms =
+        std::cmp::min(state.rate_window_secs.saturating_mul(1000), i64::MAX as u64) as i64;
+    let cutoff = now_ms.saturating_sub(window_ms);
+    let mut log = state.ip_log.write().await;
+    let admitted = &mut ip_bucket(&mut log, state, ip, now_ms).admitted;
+    while admitted.front().is_some_and(|&at| at <= cutoff) {
+        admitted.pop_front();
+    }
+    if admitted.len() < state.rate_limit {
+        admitted.push_back(now_ms);
+        return None;
+    }
+    let frees_at_ms = admitted[admitted.len() - state.rate_limit].saturating_add(window_ms);
+    let retry_after = (frees_at_ms.saturating_sub(now_ms).max(1) as u64).div_ceil(1000);
+    Some(AppError::RateLimited { retry_after }.into_response())
+}
+
+// Serialize one ACCESS_LOG_PATH line for this request.
+fn access_line(
+    req_id: &str,
+    trace_id: Option<&str>,
+    method: &Method,
+    uri: &axum::http::Uri,
+    client_ip: Option<&str>,
+    dur_ms: u64,
+    resp: &Response,
+) -> Vec<u8> {
+    let ts_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0);
+    let uri = uri.to_string();
+    let h = resp.headers();
+    let rec = AccessRecord {
+        req_id,
+        trace_id,
+        ts_ms,
+        method: method.as_str(),
+        uri: &uri,
+        status: resp.status().as_u16(),
+        dur_ms,
+        client_ip,
+        resp_ct: h.get("content-type").and_then(|v| v.to_str().ok()),
+        resp_len: h
+            .get("content-length")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|s| s.parse::<u64>().ok())
+            .or_else(|| axum::body::HttpBody::size_hint(resp.body()).exact()),
+    };
+    rec.to_line()
+}
+
+// Record request count and latency for /metrics when METRICS_ENABLED is on.
+pub(crate) async fn metrics_mw(
+    State(state): State<AppState>,
+    req: AxRequest,
+    next: axum::middleware::Next,
+) -> Response {
+    if !state.metrics_enabled {
+        return next.run(req).await;
+    }
+    let method = req.method().clone();
+    let started = std::time::Instant::now();
+    let resp = next.run(req).await;
+    let dur_ms = started.elapsed().as_millis() as u64;
+    crate::metrics::record_request(method.as_str(), resp.status().as_u16(), dur_ms);
+    resp
+}
+
+// Shrink the IP log to at most `keep` buckets, least recently active first (expired ones
+// are the oldest of all). Pops `evict_q` until a live entry turns up, so each eviction is
+// amortized O(1); a queue entry is live while its bucket has not been seen after it.
+fn evict_ip_buckets(log: &mut IpLog, keep: usize) {
+    while log.inner.len() > keep {
+        let Some((at, ip)) = log.evict_q.pop_front() else {
+            break;
+        };
+        if log.inner.get(&ip).is_some_and(|b| b.last_at_ms() <= at) {
+            log.inner.remove(&ip);
+        }
+    }
+}
+
+// Buckets handled per write-lock hold during a sweep.
+const IP_LOG_SWEEP_BATCH: usize = 256;
+
+// Prune every bucket to the retention window and drop the ones left empty. The write lock
+// is taken per batch and released (with a yield) in between, so request logging is never
+// blocked for a whole pass. Returns how many buckets were removed.
+pub(crate) async fn sweep_ip_log(ip_log: &RwLock<IpLog>, now_ms: i64, retention_ms: i64) -> usize {
+    let ips: Vec<String> = ip_log.read().await.inner.keys().cloned().collect();
+    let mut removed = 0;
+    for batch in ips.chunks(IP_LOG_SWEEP_BATCH) {
+        {
+            let map = &mut ip_log.write().await.inner;
+            for ip in batch {
+                let Some(bucket) = map.get_mut(ip) else {
+                    continue;
+                };
+                prune_ip_bucket(bucket, now_ms, retention_ms);
+                if bucket.is_empty() {
+                    map.remove(ip);
+                    removed += 1;
+                }
             }
         }
-        bucket.push_back(IpAccessEntry {
-            at_ms: now_ms,
-            method: method.to_string(),
-            path,
-            status: status.as_u16(),
-        });
+        tokio::task::yield_now().await;
+    }
+    removed
+}
+
+// IP_LOG_SWEEP_SECS: without it a quiet IP keeps its bucket until its next request.
+pub(crate) fn spawn_ip_log_sweeper(
+    ip_log: Arc<RwLock<IpLog>>,
+    every: Duration,
+    retention_secs: u64,
+) {
+    let retention_ms = std::cmp::min(retention_secs.saturating_mul(1000), i64::MAX as u64) as i64;
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(every);
+        tick.tick().await;
+        loop {
+            tick.tick().await;
+            let now_ms = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as i64)
+                .unwrap_or(0);
+            sweep_ip_log(&ip_log, now_ms, retention_ms).await;
+        }
+    });
+}
+
+// Response headers browser JS may read cross-origin.
+const CORS_EXPOSE_HEADERS: &str = "ETag, Content-Range, Content-Length, Accept-Ranges, \
+x-repo-commit, x-revision, X-Linked-Etag, X-Linked-Size, X-Sha256, X-Request-ID, X-Error-Code, X-Error-Message, \
+Link, X-Total-Count, Retry-After";
+const CORS_ALLOW_METHODS: &str = "GET, HEAD, POST, PUT, DELETE, OPTIONS";
+
+// CORS middleware: answers preflight OPTIONS with 204 and injects
+// Access-Control-Allow-Origin / Expose-Headers on every other response.
+pub(crate) async fn cors_mw(
+    State(state): State<AppState>,
+    req: AxRequest,
+    next: axum::middleware::Next,
+) -> Response {
+    if !state.cors_enabled {
+        return next.run(req).await;
+    }
+    let origin = HeaderValue::from_str(&state.cors_allow_origin)
+        .unwrap_or_else(|_| HeaderValue::from_static("*"));
+
+    if req.method() == Method::OPTIONS {
+        let req_headers = req.headers();
+        let allow_methods = req_headers
+            .get("access-control-request-method")
+            .cloned()
+            .unwrap_or_else(|| HeaderValue::from_static(CORS_ALLOW_METHODS));
+        let allow_headers = req_headers
+            .get("access-control-request-headers")
+            .cloned()
+            .unwrap_or_else(|| HeaderValue::from_static("*"));
+        // Routes with their own OPTIONS handler (resolve) contribute Allow/Accept-Ranges;
+        // everything else gets a bare preflight answer.
+        let routed = next.run(req).await;
+        let mut resp = if routed.status().is_success() {
+            routed
+        } else {
+            StatusCode::NO_CONTENT.into_response()
+        };
+        *resp.status_mut() = StatusCode::NO_CONTENT;
+        let h = resp.headers_mut();
+        h.insert("Access-Control-Allow-Origin", origin);
+        h.insert("Access-Control-Allow-Methods", allow_methods);
+        h.insert("Access-Control-Allow-Headers", allow_headers);
+        h.insert("Access-Control-Max-Age", HeaderValue::from_static("86400"));
+        if state.cors_allow_origin.as_str() != "*" {
+            h.insert("Vary", HeaderValue::from_static("Origin"));
+        }
+        return resp;
     }
 
+    let mut resp = next.run(req).await;
+    let h = resp.headers_mut();
+    h.insert("Access-Control-Allow-Origin", origin);
+    h.insert(
+        "Access-Control-Expose-Headers",
+        HeaderValue::from_static(CORS_EXPOSE_HEADERS),
+    );
+    if state.cors_allow_origin.as_str() != "*" {
+        h.insert("Vary", HeaderValue::from_static("Origin"));
+    }
     resp
 }
 
+// Maintenance mode: every route except /admin/* and /metrics answers 503 (the health probes
+// are mounted outside the middleware stack).
+pub(crate) async fn maintenance_mw(
+    State(state): State<AppState>,
+    req: AxRequest,
+    next: axum::middleware::Next,
+) -> Response {
+    let path = req.uri().path();
+    if state.maintenance.load(Ordering::Relaxed)
+        && !path.starts_with("/admin/")
+        && path != "/metrics"
+    {
+        return AppError::Maintenance.into_response();
+    }
+    next.run(req).await
+}
+
+// `Cache-Control: no-cache` on API responses that did not choose their own policy. Mounted
+// before the resolve route, which sets RESOLVE_CACHE_MAX_AGE-based headers itself.
+pub(crate) async fn no_cache_mw(req: AxRequest, next: axum::middleware::Next) -> Response {
+    let mut resp = next.run(req).await;
+    resp.headers_mut()
+        .entry(axum::http::header::CACHE_CONTROL)
+        .or_insert(HeaderValue::from_static("no-cache"));
+    resp
+}
+
+// gzip/zstd compression (per Accept-Encoding) for JSON API responses. Mounted on the API
+// routes only, so resolve file bodies are never touched; Content-Length is dropped when it applies.
+pub(crate) fn json_compression_layer() -> CompressionLayer<impl Predicate> {
+    let json_only = |_: StatusCode, _: Version, headers: &HeaderMap, _: &Extensions| {
+        headers
+            .get(axum::http::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|ct| ct.starts_with("application/json"))
+    };
+    CompressionLayer::new().compress_when(DefaultPredicate::new().and(json_only))
+}
+
 fn redact_header(key: &str, val: &str, redact: bool) -> String {
     if !redact {
         return val.to_string();
@@ -240,3 +616,403 @@ fn extract_client_ip(
     }
     connect.map(|addr| addr.ip().to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::routing::get;
+    use std::collections::VecDeque;
+    use tower::util::ServiceExt;
+
+    fn app(state: AppState) -> Router {
+        Router::new()
+            .route("/ping", get(|| async { "pong" }))
+            .with_state(state.clone())
+            .layer(axum::middleware::from_fn_with_state(state, cors_mw))
+    }
+
+    #[tokio::test]
+    async fn cors_preflight_and_simple() {
+        let mut state = AppState::for_tests("fake_hub".into());
+        state.cors_allow_origin = std::sync::Arc::new("http://ui.local".to_string());
+        let req = axum::http::Request::builder()
+            .method("OPTIONS")
+            .uri("/ping")
+            .header("Access-Control-Request-Method", "GET")
+            .header("Access-Control-Request-Headers", "range")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app(state.clone()).oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        let h = resp.headers();
+        assert_eq!(h["Access-Control-Allow-Origin"], "http://ui.local");
+        assert_eq!(h["Access-Control-Allow-Methods"], "GET");
+        assert_eq!(h["Access-Control-Allow-Headers"], "range");
+
+        let req = axum::http::Request::builder()
+            .uri("/ping")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app(state.clone()).oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers()["Access-Control-Allow-Origin"],
+            "http://ui.local"
+        );
+        let expose = resp.headers()["Access-Control-Expose-Headers"]
+            .to_str()
+            .unwrap();
+        assert!(expose.contains("ETag") && expose.contains("x-repo-commit"));
+
+        state.cors_enabled = false;
+        let req = axum::http::Request::builder()
+            .uri("/ping")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app(state).oneshot(req).await.unwrap();
+        assert!(resp.headers().get("Access-Control-Allow-Origin").is_none());
+    }
+
+    #[tokio::test]
+    async fn rate_limit_answers_429_with_retry_after() {
+        let mut state = AppState::for_tests("fake_hub".into());
+        state.rate_limit = 2;
+        state.rate_window_secs = 30;
+        let ip_log = state.ip_log.clone();
+        let app = Router::new()
+            .route("/ping", get(|| async { "pong" }))
+            .with_state(state.clone())
+            .layer(axum::middleware::from_fn_with_state(state, log_requests_mw));
+        let ip = "198.51.100.77";
+        let ping = |ip: &'static str| {
+            let req = axum::http::Request::builder()
+                .uri("/ping")
+                .header("x-forwarded-for", ip)
+                .body(Body::empty())
+                .unwrap();
+            app.clone().oneshot(req)
+        };
+
+        for _ in 0..2 {
+            assert_eq!(ping(ip).await.unwrap().status(), StatusCode::OK);
+        }
+        for _ in 0..2 {
+            let resp = ping(ip).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+            let retry: u64 = resp.headers()["Retry-After"]
+                .to_str()
+                .unwrap()
+                .parse()
+                .unwrap();
+            assert!((29..=30).contains(&retry), "{retry}");
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(v["error"], "rate_limited");
+        }
+        assert_eq!(
+            ping("198.51.100.78").await.unwrap().status(),
+            StatusCode::OK
+        );
+
+        // Once the counted requests age out of the window the IP is let through again.
+        for at in ip_log
+            .write()
+            .await
+            .inner
+            .get_mut(ip)
+            .unwrap()
+            .admitted
+            .iter_mut()
+        {
+            *at -= 31_000;
+        }
+        assert_eq!(ping(ip).await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn rate_limit_holds_under_concurrent_hammering() {
+        let mut state = AppState::for_tests("fake_hub".into());
+        state.rate_limit = 3;
+        state.rate_window_secs = 30;
+        // Logged 429s overflow this cap quickly; they must not evict the admitted requests
+        state.log_requests = true;
+        state.ip_log_per_ip_cap = 3;
+        let app = Router::new()
+            .route("/ping", get(|| async { "pong" }))
+            .with_state(state.clone())
+            .layer(axum::middleware::from_fn_with_state(state, log_requests_mw));
+        let ip = "198.51.100.79";
+        let mut tasks = tokio::task::JoinSet::new();
+        for _ in 0..40 {
+            let req = axum::http::Request::builder()
+                .uri("/ping")
+                .header("x-forwarded-for", ip)
+                .body(Body::empty())
+                .unwrap();
+            tasks.spawn(app.clone().oneshot(req));
+        }
+        let mut admitted = 0;
+        while let Some(res) = tasks.join_next().await {
+            if res.unwrap().unwrap().status() == StatusCode::OK {
+                admitted += 1;
+            }
+        }
+        assert_eq!(admitted, 3);
+    }
+
+    #[tokio::test]
+    async fn background_sweep_empties_quiet_buckets() {
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or(0);
+        let entry = |at_ms: i64| IpAccessEntry {
+            at_ms,
+            method: "GET".to_string(),
+            path: "/".to_string(),
+            status: 200,
+        };
+        let (quiet, active) = ("198.51.100.91", "198.51.100.92");
+        let ip_log = Arc::new(RwLock::new(IpLog::default()));
+        {
+            let map = &mut ip_log.write().await.inner;
+            map.insert(
+                quiet.to_string(),
+                IpBucket {
+                    entries: VecDeque::from([entry(now_ms - 7_200_000)]),
+                    ..Default::default()
+                },
+            );
+            map.insert(
+                active.to_string(),
+                IpBucket {
+                    entries: VecDeque::from([entry(now_ms - 7_200_000), entry(now_ms)]),
+                    ..Default::default()
+                },
+            );
+        }
+        spawn_ip_log_sweeper(ip_log.clone(), Duration::from_millis(20), 3_600);
+        let mut emptied = false;
+        for _ in 0..100 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            if !ip_log.read().await.inner.contains_key(quiet) {
+                emptied = true;
+                break;
+            }
+        }
+        assert!(emptied, "quiet bucket was never swept");
+        assert_eq!(ip_log.read().await.inner[active].entries.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn access_log_writes_json_lines() {
+        let dir = std::path::PathBuf::from("fake_hub").join("tests_access_log");
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("access.jsonl");
+        let _ = tokio::fs::remove_file(&path).await;
+        let log = crate::access_log::AccessLog::open(&path, std::time::Duration::from_secs(60))
+            .await
+            .unwrap();
+        let mut state = AppState::for_tests("fake_hub".into());
+        state.access_log = Some(log.clone());
+        let app = Router::new()
+            .route("/ping", get(|| async { "pong" }))
+            .with_state(state.clone())
+            .layer(axum::middleware::from_fn_with_state(state, log_requests_mw));
+
+        let req = axum::http::Request::builder()
+            .uri("/ping?x=1")
+            .header("x-forwarded-for", "192.0.2.9")
+            .header(
+                "traceparent",
+                "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            )
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        log.flush().await;
+
+        let text = tokio::fs::read_to_string(&path).await.unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 1);
+        let v: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(v["method"], "GET");
+        assert_eq!(v["uri"], "/ping?x=1");
+        assert_eq!(v["status"], 200);
+        assert_eq!(v["client_ip"], "192.0.2.9");
+        assert_eq!(v["resp_len"], 4);
+        assert_eq!(v["trace_id"], "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(
+            v["req_id"].as_str().unwrap(),
+            resp.headers()["X-Request-ID"].to_str().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn incoming_request_id_is_echoed_and_logged() {
+        let dir = std::path::PathBuf::from("fake_hub").join("tests_access_log_req_id");
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("access.jsonl");
+        let _ = tokio::fs::remove_file(&path).await;
+        let log = crate::access_log::AccessLog::open(&path, std::time::Duration::from_secs(60))
+            .await
+            .unwrap();
+        let mut state = AppState::for_tests("fake_hub".into());
+        state.access_log = Some(log.clone());
+        for log_requests in [false, true] {
+            state.log_requests = log_requests;
+            let app = Router::new()
+                .route("/ping", get(|| async { "pong" }))
+                .with_state(state.clone())
+                .layer(axum::middleware::from_fn_with_state(
+                    state.clone(),
+                    log_requests_mw,
+                ));
+            for sent in ["edge-7f3a.01_b", "bad id;with spaces"] {
+                let req = axum::http::Request::builder()
+                    .uri("/ping")
+                    .header("X-Request-ID", sent)
+                    .body(Body::empty())
+                    .unwrap();
+                let resp = app.clone().oneshot(req).await.unwrap();
+                let echoed = resp.headers()["X-Request-ID"].to_str().unwrap();
+                if sent.contains(' ') {
+                    assert_eq!(echoed.len(), 12);
+                } else {
+                    assert_eq!(echoed, sent);
+                }
+            }
+        }
+        log.flush().await;
+
+        let text = tokio::fs::read_to_string(&path).await.unwrap();
+        let ids: Vec<String> = text
+            .lines()
+            .map(|l| serde_json::from_str::<serde_json::Value>(l).unwrap()["req_id"].to_string())
+            .collect();
+        assert_eq!(ids.len(), 4);
+        assert_eq!(ids[0], "\"edge-7f3a.01_b\"");
+        assert_eq!(ids[2], "\"edge-7f3a.01_b\"");
+        assert!(!ids[1].contains("bad"));
+        // No traceparent: the field is left out rather than logged empty
+        assert!(!text.contains("trace_id"));
+    }
+
+    #[test]
+    fn malformed_traceparent_is_ignored() {
+        let with = |v: &str| {
+            let mut h = HeaderMap::new();
+            h.insert("traceparent", HeaderValue::from_str(v).unwrap());
+            trace_id(&h)
+        };
+        let good = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+        assert_eq!(with(good).unwrap(), "0af7651916cd43dd8448eb211c80319c");
+        for bad in [
+            "garbage",
+            "00-0af7651916cd43dd8448eb211c80319-b7ad6b7169203331-01",
+            "00-00000000000000000000000000000000-b7ad6b7169203331-01",
+            "ff-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+            "00-0af7651916cd43dd8448eb211c80319z-b7ad6b7169203331-01",
+        ] {
+            assert!(with(bad).is_none(), "{bad}");
+        }
+        assert!(trace_id(&HeaderMap::new()).is_none());
+    }
+
+    #[tokio::test]
+    async fn ip_log_stays_bounded() {
+        let mut state = AppState::for_tests("fake_hub".into());
+        state.log_requests = true;
+        state.ip_log_max_ips = 5;
+        let ip_log = state.ip_log.clone();
+        let app = Router::new()
+            .route("/ping", get(|| async { "pong" }))
+            .with_state(state.clone())
+            .layer(axum::middleware::from_fn_with_state(state, log_requests_mw));
+        let ping = |ip: String| {
+            let req = axum::http::Request::builder()
+                .uri("/ping")
+                .header("x-forwarded-for", ip)
+                .body(Body::empty())
+                .unwrap();
+            app.clone().oneshot(req)
+        };
+        let has = |ip: &str| {
+            let ip_log = ip_log.clone();
+            let ip = ip.to_string();
+            async move { ip_log.read().await.inner.contains_key(&ip) }
+        };
+
+        for i in 0..50 {
+            ping(format!("10.0.0.{i}")).await.unwrap();
+            assert!(ip_log.read().await.inner.len() <= 5);
+            // Stale queue entries are compacted away at twice the cap
+            assert!(ip_log.read().await.evict_q.len() <= 10);
+            tokio::time::sleep(Duration::from_millis(2)).await;
+        }
+        // the most recently active IPs survive
+        assert!(has("10.0.0.49").await && has("10.0.0.45").await);
+        assert!(!has("10.0.0.44").await);
+
+        // Seeing an IP again moves it to the back of the line
+        ping("10.0.0.45".to_string()).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(2)).await;
+        ping("10.0.0.50".to_string()).await.unwrap();
+        assert!(has("10.0.0.45").await);
+        assert!(!has("10.0.0.46").await);
+        assert_eq!(ip_log.read().await.inner.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn compresses_json_only() {
+        let big = "x".repeat(4096);
+        let json_body = serde_json::json!({ "data": big.clone() });
+        let app = Router::new()
+            .route(
+                "/json",
+                get(move || {
+                    let v = json_body.clone();
+                    async move { axum::Json(v) }
+                }),
+            )
+            .route("/text", get(move || async move { big.clone() }))
+            .layer(json_compression_layer());
+
+        let req = axum::http::Request::builder()
+            .uri("/json")
+            .header("accept-encoding", "zstd, gzip")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        let enc = resp.headers()["content-encoding"].to_str().unwrap();
+        assert!(enc == "zstd" || enc == "gzip", "{enc}");
+        assert!(resp.headers().get("content-length").is_none());
+
+        let req = axum::http::Request::builder()
+            .uri("/json")
+            .header("accept-encoding", "gzip")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(resp.headers()["content-encoding"], "gzip");
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let mut plain = String::new();
+        std::io::Read::read_to_string(&mut flate2::read::GzDecoder::new(&bytes[..]), &mut plain)
+            .unwrap();
+        assert!(plain.starts_with("{\"data\":\"xxx"));
+
+        let req = axum::http::Request::builder()
+            .uri("/text")
+            .header("accept-encoding", "gzip")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert!(resp.headers().get("content-encoding").is_none());
+    }
+}
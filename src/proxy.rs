@@ -0,0 +1,219 @@
+use std::path::Path;
+use std::time::Duration;
+
+use async_stream::stream;
+use axum::body::Body;
+use reqwest::StatusCode;
+use tracing::{error, info};
+
+use crate::app_state::AppState;
+use crate::caches::evict_repo;
+use crate::error::AppError;
+use crate::upload::{path_lock, store_upload};
+use crate::utils::paths::{JoinError, try_secure_join};
+use crate::utils::sidecar::upsert_sidecar_entry;
+
+// Bounded connect and per-read waits: a stalled upstream must not hold a path's fetch lock
+// (and every request queued on it) forever.
+static CLIENT: once_cell::sync::Lazy<reqwest::Client> = once_cell::sync::Lazy::new(|| {
+    reqwest::Client::builder()
+        .connect_timeout(Duration::from_secs(10))
+        .read_timeout(Duration::from_secs(60))
+        .build()
+        .unwrap_or_default()
+});
+
+// Only the default branch is mirrored: files stored on disk are served for every revision,
+// so fetching a tag or commit would pin its content under `main` too.
+const PROXY_REVISION: &str = "main";
+
+// `{endpoint}/{repo}/resolve/{revision}/{filename}` with every segment percent-encoded.
+fn upstream_url(
+    endpoint: &str,
+    left: &str,
+    revision: &str,
+    filename: &str,
+) -> Option<reqwest::Url> {
+    let mut url = reqwest::Url::parse(endpoint).ok()?;
+    {
+        let mut segs = url.path_segments_mut().ok()?;
+        segs.pop_if_empty()
+            .extend(left.split('/'))
+            .push("resolve")
+            .push(revision)
+            .extend(filename.split('/'));
+    }
+    Some(url)
+}
+
+// PROXY_MODE: download a file missing under FAKE_HUB_ROOT from HF_REMOTE_ENDPOINT, store it at
+// `filepath` and record it in the repo sidecar, so the caller serves it (ranges included) like
+// any local file. Upstream 401/403/404 and non-default revisions become a plain 404; other
+// failures a 502.
+pub(crate) async fn fetch_missing(
+    state: &AppState,
+    endpoint: &str,
+    left: &str,
+    revision: &str,
+    filename: &str,
+    filepath: &Path,
+) -> Result<(), AppError> {
+    if revision != PROXY_REVISION {
+        return Err(AppError::FileNotFound);
+    }
+    let repo_dir = match try_secure_join(&state.root, left, state.allow_symlink_escape) {
+        Ok(d) => d,
+        Err(JoinError::Escapes) => return Err(AppError::PathForbidden),
+        Err(JoinError::BaseMissing) => return Err(AppError::FileNotFound),
+    };
+    let Ok(rel) = filepath.strip_prefix(&repo_dir) else {
+        return Err(AppError::PathForbidden);
+    };
+    let rel = rel.to_string_lossy().replace('\\', "/");
+    // One download per target path; later requests wait and then find the file on disk
+    let lock = path_lock(filepath);
+    let _guard = lock.lock().await;
+    if filepath.is_file() {
+        return Ok(());
+    }
+    let Some(url) = upstream_url(endpoint, left, revision, filename) else {
+        return Err(AppError::UpstreamFailed("Invalid HF_REMOTE_ENDPOINT"));
+    };
+    let mut req = CLIENT.get(url.clone());
+    if let Some(token) = &state.proxy_token {
+        req = req.bearer_auth(token.as_str());
+    }
+    let mut resp = match req.send().await {
+        Ok(r) => r,
+        Err(e) => {
+            error!(target: "fakehub", "[proxy] GET {} failed: {}", url, e);
+            return Err(AppError::UpstreamFailed("Upstream request failed"));
+        }
+    };
+    match resp.status() {
+        s if s.is_success() => {}
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN | StatusCode::NOT_FOUND => {
+            return Err(AppError::FileNotFound);
+        }
+        s => {
+            error!(target: "fakehub", "[proxy] GET {} returned {}", url, s);
+            return Err(AppError::UpstreamFailed("Upstream returned an error"));
+        }
+    }
+    let body = Body::from_stream(stream! {
+        while let Some(chunk) = resp.chunk().await.transpose() {
+            yield chunk;
+        }
+    });
+    let entry = match store_upload(state, filepath, &rel, body).await {
+        Ok(entry) => entry,
+        Err(e) => {
+            error!(target: "fakehub", "[proxy] storing {}/{} failed: {}", left, rel, e);
+            return Err(AppError::UpstreamFailed("Could not mirror upstream file"));
+        }
+    };
+    if let Err(e) = upsert_sidecar_entry(state, &repo_dir, entry.clone()).await {
+        error!(target: "fakehub", "[proxy] sidecar update for {}/{} failed: {}", left, rel, e);
+        return Err(AppError::WriteFailed("Could not update sidecar"));
+    }
+    evict_repo(&repo_dir).await;
+    info!(target: "fakehub", "[proxy] mirrored {}/{} ({} bytes)", left, rel,
+        entry.get("size").and_then(|v| v.as_u64()).unwrap_or(0));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::http::{Method, StatusCode};
+    use axum::routing::get;
+    use serde_json::Value;
+    use std::sync::Arc;
+    use tower::util::ServiceExt;
+
+    #[tokio::test]
+    async fn missing_file_is_fetched_stored_and_served_locally() {
+        let upstream = Router::new()
+            .route(
+                "/org/mirrored/resolve/main/sub/w.bin",
+                get(|| async { "0123456789" }),
+            )
+            .route(
+                "/org/mirrored/resolve/v1/tagged.bin",
+                get(|| async { "tagged" }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move { axum::serve(listener, upstream).await });
+
+        let root = dunce::canonicalize("fake_hub").unwrap().join("tests_proxy");
+        let _ = tokio::fs::remove_dir_all(&root).await;
+        tokio::fs::create_dir_all(&root).await.unwrap();
+        let mut state = AppState::for_tests(root.clone());
+        let app = |state: AppState| {
+            Router::new()
+                .route("/{*rest}", get(crate::resolve::resolve_catchall))
+                .with_state(state)
+        };
+        let send = |app: Router, method: Method, uri: &str, range: Option<&str>| {
+            let mut b = axum::http::Request::builder().method(method).uri(uri);
+            if let Some(r) = range {
+                b = b.header("Range", r);
+            }
+            app.oneshot(b.body(Body::empty()).unwrap())
+        };
+        let uri = "/org/mirrored/resolve/main/sub/w.bin";
+
+        // Off by default: a missing file stays a 404
+        let resp = send(app(state.clone()), Method::GET, uri, None)
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        state.proxy_endpoint = Some(Arc::new(format!("http://{addr}/")));
+        let resp = send(app(state.clone()), Method::GET, uri, Some("bytes=2-4"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"234");
+        let on_disk = root.join("org/mirrored/sub/w.bin");
+        assert_eq!(std::fs::read(&on_disk).unwrap(), b"0123456789");
+        let sc: Value = serde_json::from_slice(
+            &std::fs::read(root.join("org/mirrored/.paths-info.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(sc["entries"][0]["path"], "sub/w.bin");
+        assert_eq!(sc["entries"][0]["size"], 10);
+
+        // Upstream misses stay 404; once mirrored the file no longer needs the upstream
+        let resp = send(
+            app(state.clone()),
+            Method::HEAD,
+            "/org/mirrored/resolve/main/absent.bin",
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        // Only the default branch is mirrored, even when upstream has the file
+        let resp = send(
+            app(state.clone()),
+            Method::GET,
+            "/org/mirrored/resolve/v1/tagged.bin",
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert!(!root.join("org/mirrored/tagged.bin").exists());
+        server.abort();
+        let _ = server.await;
+        let resp = send(app(state), Method::GET, uri, None).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["content-length"], "10");
+    }
+}
@@ -1,40 +1,57 @@
 use std::io;
-use std::path::Path;
-use std::time::UNIX_EPOCH;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use async_stream::stream;
 use axum::Json;
 use axum::body::{Body, Bytes};
 use axum::extract::{Path as AxPath, Request as AxRequest, State};
-use axum::http::{HeaderMap, HeaderValue, Method, StatusCode};
-use axum::response::{IntoResponse, Response};
-use serde_json::json;
+use axum::http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode};
+use axum::response::{Html, IntoResponse, Response};
+use http_body_util::BodyExt;
+use serde_json::{Value, json};
 use sha2::Digest;
 use tokio::fs;
 use tokio::io::{AsyncReadExt, AsyncSeekExt};
-use tracing::error;
+use tracing::{debug, error};
+use uuid::Uuid;
 
 use crate::app_state::AppState;
-use crate::caches::{SHA256_CACHE, Sha256Entry};
-use crate::utils::headers::{file_headers_common, set_content_range};
-use crate::utils::paths::{is_sidecar_path, secure_join};
-use crate::utils::sidecar::{etag_from_sidecar, get_sidecar_map};
-use crate::{CHUNK_SIZE, http_error, http_not_found};
+use crate::caches::{DigestCacheLock, SHA1_CACHE, SHA256_CACHE, Sha256Entry};
+use crate::error::AppError;
+use crate::routes_auth::{accessible_repo_path, request_auth};
+use crate::sidecar_dir_records;
+use crate::utils::headers::{
+    content_type_for_encoded, file_headers_common, set_cache_headers, set_content_disposition,
+    set_content_range,
+};
+use crate::utils::listing::{prefers_html, render_html_listing};
+use crate::utils::paths::{
+    JoinError, decode_path_segments, has_invalid_path_chars, is_sidecar_path, secure_join,
+    try_secure_join,
+};
+use crate::utils::prng::splitmix64_next;
+use crate::utils::refs::ref_target_commit;
+use crate::utils::repo_json::RepoKind;
+use crate::utils::sidecar::{etag_from_sidecar, load_sidecar_map};
 
 // ============ Resolve (GET/HEAD) ============
 pub(crate) async fn resolve_catchall(
     State(state): State<AppState>,
-    AxPath(rest): AxPath<String>,
     req: AxRequest,
 ) -> impl IntoResponse {
     // Two patterns supported:
     // - /{repo_id}/resolve/{revision}/{filename...} (GET|HEAD)
-    // - /{repo_id}/sha256/{revision}/{filename...} (GET only)
-    let path = if rest.starts_with('/') {
-        rest.clone()
-    } else {
-        format!("/{rest}")
+    // - /{repo_id}/sha256/{revision}/{filename...} (GET|HEAD)
+    // Decoded from the raw URI per segment, so `my%20file.bin` finds `my file.bin` but `%2F`
+    // cannot split a name into directories.
+    let Some(path) = decode_path_segments(req.uri().path()) else {
+        return AppError::InvalidPath.into_response();
     };
+    if has_invalid_path_chars(&path) {
+        return AppError::InvalidPath.into_response();
+    }
 
     // First, handle /sha256/
     if let Some(idx) = path.rfind("/sha256/") {
@@ -44,27 +61,35 @@ pub(crate) async fn resolve_catchall(
         let _revision = right_parts.next().unwrap_or("");
         let filename = right_parts.next().unwrap_or("");
         if left.is_empty() || filename.is_empty() {
-            return http_not_found("Not Found");
-        }
-        if req.method() == Method::HEAD {
-            return http_error(StatusCode::METHOD_NOT_ALLOWED, "Use GET for sha256");
+            return AppError::NotFound.into_response();
         }
         if is_sidecar_path(filename) {
-            return http_not_found("File not found");
+            return AppError::FileNotFound.into_response();
+        }
+        if let Err(e) = check_resolve_access(&state, left, req.headers()).await {
+            return e.into_response();
         }
         let rel = format!("{}/{}", left.trim_start_matches('/'), filename);
-        let Some(filepath) = secure_join(&state.root, &rel) else {
-            return http_not_found("File not found");
+        let filepath = match try_secure_join(&state.root, &rel, state.allow_symlink_escape) {
+            Ok(p) => p,
+            Err(e) => return join_rejection(e),
         };
         if !filepath.is_file() {
-            return http_not_found("File not found");
+            return AppError::FileNotFound.into_response();
         }
         match sha256_file_cached(&state, &filepath).await {
             Ok(sum) => {
+                // HEAD: same hash (and cache) as GET, reported as a header without a body
+                if req.method() == Method::HEAD {
+                    return match HeaderValue::from_str(&sum) {
+                        Ok(hv) => (StatusCode::OK, [("X-Sha256", hv)]).into_response(),
+                        Err(_) => AppError::HashFailed("Hash compute failed").into_response(),
+                    };
+                }
                 let body = json!({ "sha256": sum });
                 return (StatusCode::OK, Json(body)).into_response();
             }
-            Err(_) => return http_error(StatusCode::INTERNAL_SERVER_ERROR, "Hash compute failed"),
+            Err(_) => return AppError::HashFailed("Hash compute failed").into_response(),
         }
     }
 
@@ -73,7 +98,7 @@ pub(crate) async fn resolve_catchall(
     // We'll find the last occurrence of "/resolve/" and split.
     let needle = "/resolve/";
     let Some(idx) = path.rfind(needle) else {
-        return http_not_found("Not Found");
+        return AppError::NotFound.into_response();
     };
     let left = &path[1..idx]; // skip leading '/'
     let right = &path[(idx + needle.len())..];
@@ -81,25 +106,144 @@ pub(crate) async fn resolve_catchall(
     let mut right_parts = right.splitn(2, '/');
     let revision = right_parts.next().unwrap_or("");
     let filename = right_parts.next().unwrap_or("");
-    if left.is_empty() || revision.is_empty() || filename.is_empty() {
-        return http_not_found("Not Found");
+    if left.is_empty() || revision.is_empty() {
+        return AppError::NotFound.into_response();
+    }
+
+    // FAULT_INJECT_RATE: decided before anything is read or sent
+    if state.fault_inject_rate > 0.0 && fault_draw() < state.fault_inject_rate {
+        debug!(target: "fakehub", "injected {} for {}", state.fault_inject_status.as_u16(), path);
+        return AppError::InjectedFault(state.fault_inject_status).into_response();
     }
 
     // .paths-info.json cannot be served as file
     if is_sidecar_path(filename) {
-        return http_not_found("File not found");
+        return AppError::FileNotFound.into_response();
+    }
+    // Gated repos refuse before anything about the file is revealed
+    if let Err(e) = check_resolve_access(&state, left, req.headers()).await {
+        return e.into_response();
     }
 
     let rel = format!("{}/{}", left.trim_start_matches('/'), filename);
-    let Some(filepath) = secure_join(&state.root, &rel) else {
-        return http_not_found("File not found");
+    let filepath = match try_secure_join(&state.root, &rel, state.allow_symlink_escape) {
+        Ok(p) => p,
+        Err(e) => return join_rejection(e),
     };
     if !filepath.is_file() {
-        return http_not_found("File not found");
+        // Directory URLs get an index (HTML for browsers, paths-info JSON otherwise)
+        if req.method() == Method::GET && filepath.is_dir() {
+            return directory_listing(&state, left, revision, filename, req.headers()).await;
+        }
+        // PROXY_MODE: mirror the whole file from upstream, then serve it as a local one
+        let Some(endpoint) = state.proxy_endpoint.as_deref() else {
+            return AppError::FileNotFound.into_response();
+        };
+        if filename.is_empty() || filepath.exists() {
+            return AppError::FileNotFound.into_response();
+        }
+        if let Err(e) =
+            crate::proxy::fetch_missing(&state, endpoint, left, revision, filename, &filepath).await
+        {
+            return e.into_response();
+        }
+    }
+
+    // A ref listed in .refs.json reports its target sha as x-repo-commit;
+    // x-revision keeps the requested name.
+    let commit = match secure_join(&state.root, left, state.allow_symlink_escape) {
+        Some(repo_dir) => ref_target_commit(&repo_dir, revision).await,
+        None => None,
+    };
+    // RESOLVE_DELAY_MS: hold back the first byte
+    if !state.resolve_delay.is_zero() {
+        tokio::time::sleep(state.resolve_delay).await;
+    }
+    let mut resp = serve_file(&state, left, revision, filename, filepath, req).await;
+    if state.resolve_throttle_bps > 0
+        && matches!(resp.status(), StatusCode::OK | StatusCode::PARTIAL_CONTENT)
+    {
+        resp = resp.map(|body| throttle_body(body, state.resolve_throttle_bps, state.chunk_size));
+    }
+    if matches!(resp.status(), StatusCode::OK | StatusCode::PARTIAL_CONTENT) {
+        resp = resp.map(count_resolve_bytes);
+    }
+    if let Some(sha) = commit
+        && resp.headers().contains_key("x-repo-commit")
+        && let Ok(hv) = HeaderValue::from_str(&sha)
+    {
+        resp.headers_mut().insert("x-repo-commit", hv);
+    }
+    resp
+}
+
+// Uniform draw in [0, 1) for FAULT_INJECT_RATE: splitmix64 seeded per
+// request from the clock and a process-wide counter.
+fn fault_draw() -> f64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+    let mut seed = nanos ^ COUNTER.fetch_add(1, Ordering::Relaxed).rotate_left(32);
+    (splitmix64_next(&mut seed) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+// Conditional, HEAD, range and full-body handling for an existing file.
+async fn serve_file(
+    state: &AppState,
+    left: &str,
+    revision: &str,
+    filename: &str,
+    filepath: PathBuf,
+    req: AxRequest,
+) -> Response {
+    // Conditional request: a matching If-None-Match (or, absent that, a satisfied
+    // If-Modified-Since) short-circuits to 304 before any body is opened.
+    let if_none_match = req
+        .headers()
+        .get("if-none-match")
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string());
+    let if_modified_since = req
+        .headers()
+        .get("if-modified-since")
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string());
+    if if_none_match.is_some() || if_modified_since.is_some() {
+        let md = fs::metadata(&filepath).await.ok();
+        let size = md.as_ref().map(|m| m.len()).unwrap_or(0);
+        let mut etag_headers = HeaderMap::new();
+        if let Err(e) = ensure_and_insert_etag(
+            state,
+            &mut etag_headers,
+            &filepath,
+            filename,
+            left,
+            revision,
+            size,
+        )
+        .await
+        {
+            return e.into_response();
+        }
+        let current = etag_headers
+            .get("ETag")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("");
+        // RFC 7232 §6: If-None-Match takes precedence; If-Modified-Since is then ignored.
+        let not_modified = match (&if_none_match, &if_modified_since) {
+            (Some(inm), _) => etag_list_matches(inm, current),
+            (None, Some(ims)) => not_modified_since(ims, md.and_then(|m| m.modified().ok())),
+            (None, None) => false,
+        };
+        if not_modified {
+            return not_modified_response(state, revision, etag_headers);
+        }
     }
 
     if req.method() == Method::HEAD {
-        return head_file(&state, left, revision, filename, &filepath).await;
+        return head_file(state, left, revision, filename, &filepath).await;
     }
     // GET with Range
     let range_header = req
@@ -109,15 +253,48 @@ pub(crate) async fn resolve_catchall(
         .and_then(|v| v.to_str().ok())
         .map(|s| s.to_string());
 
+    let if_range = req
+        .headers()
+        .get("if-range")
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string());
+
     if let Some(rh) = range_header {
-        let total = match fs::metadata(&filepath).await {
-            Ok(m) => m.len(),
-            Err(_) => 0,
-        };
+        let md = fs::metadata(&filepath).await.ok();
+        let total = md.as_ref().map(|m| m.len()).unwrap_or(0);
+        // Empty files advertise `Accept-Ranges: none`; any Range gets the plain empty 200
+        if total == 0 {
+            return full_file_response(state, left, revision, filename, &filepath).await;
+        }
+        // If-Range: resume only when the validator still matches; otherwise send the full body.
+        if let Some(ir) = if_range {
+            let mut etag_headers = HeaderMap::new();
+            if let Err(e) = ensure_and_insert_etag(
+                state,
+                &mut etag_headers,
+                &filepath,
+                filename,
+                left,
+                revision,
+                total,
+            )
+            .await
+            {
+                return e.into_response();
+            }
+            let current = etag_headers
+                .get("ETag")
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("");
+            let mtime = md.and_then(|m| m.modified().ok());
+            if !if_range_matches(&ir, current, mtime) {
+                return full_file_response(state, left, revision, filename, &filepath).await;
+            }
+        }
         match parse_range(&rh, total) {
             RangeParse::Invalid => {
                 // ignore range, return full file
-                return full_file_response(&state, left, revision, filename, &filepath).await;
+                return full_file_response(state, left, revision, filename, &filepath).await;
             }
             RangeParse::Unsatisfiable => {
                 let mut headers = HeaderMap::new();
@@ -126,12 +303,18 @@ pub(crate) async fn resolve_catchall(
                     HeaderValue::from_str(&format!("bytes */{total}")).unwrap(),
                 );
                 headers.insert("Accept-Ranges", HeaderValue::from_static("bytes"));
-                headers.insert("Content-Length", HeaderValue::from_static("0"));
-                return (StatusCode::RANGE_NOT_SATISFIABLE, headers).into_response();
+                return (headers, AppError::RangeNotSatisfiable).into_response();
+            }
+            RangeParse::Multi(ranges) => {
+                return multi_range_response(
+                    state, &filepath, filename, left, revision, ranges, total,
+                )
+                .await;
             }
             RangeParse::Ok(start, end) => {
                 let length = end - start + 1;
                 let fp_for_stream = filepath.clone();
+                let chunk_size = state.chunk_size;
                 let stream = stream! {
                     let mut f =
                         match tokio::fs::File::open(fp_for_stream).await { Ok(f) => f, Err(e) => { let _ = e; return; } };
@@ -139,7 +322,7 @@ pub(crate) async fn resolve_catchall(
                         let _ = e; return;
                     }
                     let mut remaining = length as usize;
-                    let mut buf = vec![0u8; CHUNK_SIZE];
+                    let mut buf = vec![0u8; chunk_size];
                     while remaining > 0 {
                         let cap = std::cmp::min(buf.len(), remaining);
                         match f.read(&mut buf[..cap]).await {
@@ -152,12 +335,23 @@ pub(crate) async fn resolve_catchall(
                         }
                     }
                 };
-                let mut headers = file_headers_common(revision, length);
-                if let Err(resp) =
-                    ensure_and_insert_etag(&mut headers, &filepath, filename, left, revision, total)
-                        .await
+                let mut headers =
+                    file_headers_common(filename, revision, length, state.resolve_cache_max_age);
+                if state.resolve_content_disposition {
+                    set_content_disposition(&mut headers, filename);
+                }
+                if let Err(e) = ensure_and_insert_etag(
+                    state,
+                    &mut headers,
+                    &filepath,
+                    filename,
+                    left,
+                    revision,
+                    total,
+                )
+                .await
                 {
-                    return resp;
+                    return e.into_response();
                 }
                 set_content_range(&mut headers, start, end, total);
                 let body = Body::from_stream(stream);
@@ -174,31 +368,31 @@ pub(crate) async fn resolve_catchall(
         }
     }
 
-    full_file_response(&state, left, revision, filename, &filepath).await
+    full_file_response(state, left, revision, filename, &filepath).await
 }
 
 async fn full_file_response(
-    _state: &AppState,
+    state: &AppState,
     repo_id: &str,
     revision: &str,
     filename: &str,
     path: &Path,
 ) -> Response {
-    // Read entire file into body stream using tokio_util::io::ReaderStream if desired.
-    // For simplicity and parity, we use a streaming reader.
     let file = match fs::File::open(path).await {
         Ok(f) => f,
-        Err(_) => return http_not_found("File not found"),
+        Err(_) => return AppError::FileNotFound.into_response(),
     };
     let size = file.metadata().await.ok().map(|m| m.len()).unwrap_or(0);
-    let stream = tokio_util::io::ReaderStream::with_capacity(file, CHUNK_SIZE);
-    let mut headers = file_headers_common(revision, size);
-    if let Err(resp) =
-        ensure_and_insert_etag(&mut headers, path, filename, repo_id, revision, size).await
+    let mut headers = file_headers_common(filename, revision, size, state.resolve_cache_max_age);
+    if state.resolve_content_disposition {
+        set_content_disposition(&mut headers, filename);
+    }
+    if let Err(e) =
+        ensure_and_insert_etag(state, &mut headers, path, filename, repo_id, revision, size).await
     {
-        return resp;
+        return e.into_response();
     }
-    let body = Body::from_stream(stream);
+    let body = file_body(file, size, state.chunk_size).await;
     Response::builder()
         .status(StatusCode::OK)
         .body(body)
@@ -211,8 +405,88 @@ async fn full_file_response(
         .unwrap()
 }
 
+// RESOLVE_THROTTLE_BPS: re-slice `body` into pieces of about a tenth of a second and
+// release each one only once the running average allows it. Bytes and order are untouched.
+fn throttle_body(mut body: Body, bps: u64, chunk_size: usize) -> Body {
+    let piece_len = std::cmp::min(chunk_size as u64, (bps / 10).max(1)) as usize;
+    let stream = stream! {
+        let started = tokio::time::Instant::now();
+        let mut sent: u64 = 0;
+        while let Some(frame) = body.frame().await {
+            let mut data = match frame {
+                Ok(f) => match f.into_data() {
+                    Ok(d) => d,
+                    Err(_) => continue,
+                },
+                Err(e) => { yield Err(io::Error::other(e)); return; }
+            };
+            while !data.is_empty() {
+                let piece = data.split_to(std::cmp::min(piece_len, data.len()));
+                sent += piece.len() as u64;
+                let due = started + Duration::from_secs_f64(sent as f64 / bps as f64);
+                tokio::time::sleep_until(due).await;
+                yield Ok::<Bytes, io::Error>(piece);
+            }
+        }
+    };
+    Body::from_stream(stream)
+}
+
+// fakehub_resolve_bytes_total grows as the body is handed to the connection, so aborted
+// downloads only count what actually went out.
+fn count_resolve_bytes(mut body: Body) -> Body {
+    let stream = stream! {
+        while let Some(frame) = body.frame().await {
+            match frame {
+                Ok(f) => {
+                    if let Ok(data) = f.into_data() {
+                        crate::metrics::add_resolve_bytes(data.len() as u64);
+                        yield Ok::<Bytes, io::Error>(data);
+                    }
+                }
+                Err(e) => { yield Err(io::Error::other(e)); return; }
+            }
+        }
+    };
+    Body::from_stream(stream)
+}
+
+fn stream_body(file: fs::File, chunk_size: usize) -> Body {
+    Body::from_stream(tokio_util::io::ReaderStream::with_capacity(
+        file, chunk_size,
+    ))
+}
+
+// Files at least this large are memory-mapped by the `mmap` feature.
+#[cfg(feature = "mmap")]
+const MMAP_MIN_BYTES: u64 = 1 << 20;
+
+// Full-file body: with the `mmap` feature, large files go out as a single buffer backed by
+// the page cache, skipping the per-chunk copy into user space. Falls back to chunked reads.
+#[cfg(feature = "mmap")]
+async fn file_body(file: fs::File, size: u64, chunk_size: usize) -> Body {
+    if size < MMAP_MIN_BYTES {
+        return stream_body(file, chunk_size);
+    }
+    let std_file = file.into_std().await;
+    // SAFETY: the map is read-only. Truncating the file while it is being served faults the
+    // process; that is the trade-off of enabling `mmap` on a hub whose files are not rewritten.
+    match unsafe { memmap2::Mmap::map(&std_file) } {
+        Ok(map) => Body::from(Bytes::from_owner(map)),
+        Err(e) => {
+            error!("mmap: {}", e);
+            stream_body(fs::File::from_std(std_file), chunk_size)
+        }
+    }
+}
+
+#[cfg(not(feature = "mmap"))]
+async fn file_body(file: fs::File, _size: u64, chunk_size: usize) -> Body {
+    stream_body(file, chunk_size)
+}
+
 async fn head_file(
-    _state: &AppState,
+    state: &AppState,
     repo_id: &str,
     revision: &str,
     filename: &str,
@@ -222,19 +496,85 @@ async fn head_file(
         Ok(m) => m.len(),
         Err(_) => 0,
     };
-    let mut headers = file_headers_common(revision, size);
-    if let Err(resp) =
-        ensure_and_insert_etag(&mut headers, filepath, filename, repo_id, revision, size).await
+    let mut headers = file_headers_common(filename, revision, size, state.resolve_cache_max_age);
+    if state.resolve_content_disposition {
+        set_content_disposition(&mut headers, filename);
+    }
+    if let Err(e) = ensure_and_insert_etag(
+        state,
+        &mut headers,
+        filepath,
+        filename,
+        repo_id,
+        revision,
+        size,
+    )
+    .await
     {
-        return resp;
+        return e.into_response();
     }
     (StatusCode::OK, headers).into_response()
 }
 
+// If-None-Match semantics: "*" matches any existing file; otherwise the header
+// is a comma-separated list of quoted tags compared against the current ETag using
+// weak comparison (RFC 7232 §2.3.2: `W/` prefixes are ignored on both sides).
+fn etag_list_matches(header: &str, etag: &str) -> bool {
+    let h = header.trim();
+    if h == "*" {
+        return true;
+    }
+    let current = etag.trim_start_matches("W/");
+    h.split(',')
+        .map(|t| t.trim())
+        .any(|t| !t.is_empty() && t.trim_start_matches("W/") == current)
+}
+
+// If-Range holds either an entity tag (strong comparison only, so a weak tag on either
+// side never matches) or an HTTP-date that must equal the file mtime at second precision.
+fn if_range_matches(value: &str, etag: &str, mtime: Option<SystemTime>) -> bool {
+    let v = value.trim();
+    if v.starts_with('"') || v.starts_with("W/") {
+        return !etag.starts_with("W/") && v == etag;
+    }
+    let secs = |t: SystemTime| t.duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs());
+    match (httpdate::parse_http_date(v), mtime) {
+        (Ok(date), Some(mt)) => secs(date).is_some() && secs(date) == secs(mt),
+        _ => false,
+    }
+}
+
+// If-Modified-Since: unmodified when the file mtime is not newer than the given
+// date (second precision). Unparseable dates are ignored per RFC 7232.
+fn not_modified_since(value: &str, mtime: Option<SystemTime>) -> bool {
+    let secs = |t: SystemTime| t.duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs());
+    match (httpdate::parse_http_date(value.trim()), mtime) {
+        (Ok(date), Some(mt)) => match (secs(mt), secs(date)) {
+            (Some(m), Some(d)) => m <= d,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+// 304 carries the validator and commit headers but never a body.
+fn not_modified_response(state: &AppState, revision: &str, mut headers: HeaderMap) -> Response {
+    set_cache_headers(&mut headers, state.resolve_cache_max_age);
+    headers.insert(
+        "x-repo-commit",
+        HeaderValue::from_str(revision).unwrap_or(HeaderValue::from_static("-")),
+    );
+    (StatusCode::NOT_MODIFIED, headers).into_response()
+}
+
+// Upper bound on parts in a multi-range request; larger sets collapse to the full body.
+const MAX_RANGE_PARTS: usize = 100;
+
 enum RangeParse {
     Invalid,
     Unsatisfiable,
     Ok(u64, u64),
+    Multi(Vec<(u64, u64)>),
 }
 
 fn parse_range(h: &str, total: u64) -> RangeParse {
@@ -245,13 +585,46 @@ fn parse_range(h: &str, total: u64) -> RangeParse {
     if !unit.eq_ignore_ascii_case("bytes") {
         return RangeParse::Invalid;
     }
-    let first = rest.split(',').next().unwrap_or("").trim();
-    if !first.contains('-') {
+    let specs: Vec<&str> = rest
+        .split(',')
+        .map(|p| p.trim())
+        .filter(|p| !p.is_empty())
+        .collect();
+    if specs.is_empty() || specs.len() > MAX_RANGE_PARTS {
+        return RangeParse::Invalid;
+    }
+    let mut ranges: Vec<(u64, u64)> = Vec::with_capacity(specs.len());
+    for spec in specs {
+        match parse_range_spec(spec, total) {
+            RangeParse::Ok(start, end) => ranges.push((start, end)),
+            RangeParse::Unsatisfiable => {}
+            _ => return RangeParse::Invalid,
+        }
+    }
+    // Coalesce overlapping or adjacent ranges so each byte is sent at most once.
+    ranges.sort_unstable();
+    let mut merged: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    match merged.len() {
+        0 => RangeParse::Unsatisfiable,
+        1 => RangeParse::Ok(merged[0].0, merged[0].1),
+        _ => RangeParse::Multi(merged),
+    }
+}
+
+// Parse a single "a-b" / "a-" / "-n" spec; never yields Multi.
+fn parse_range_spec(spec: &str, total: u64) -> RangeParse {
+    if !spec.contains('-') {
         return RangeParse::Invalid;
     }
-    let mut ab = first.splitn(2, '-');
-    let a = ab.next().unwrap_or("");
-    let b = ab.next().unwrap_or("");
+    let mut ab = spec.splitn(2, '-');
+    let a = ab.next().unwrap_or("").trim();
+    let b = ab.next().unwrap_or("").trim();
     if a.is_empty() {
         // suffix: bytes=-N
         let Ok(n) = b.parse::<u64>() else {
@@ -288,8 +661,198 @@ fn parse_range(h: &str, total: u64) -> RangeParse {
     }
 }
 
+// Build a 206 multipart/byteranges response streaming each part from one file handle.
+// Content-Length is exact: part headers and the closing boundary are known up front.
+// Content-encoded files get the full 200 instead: Content-Encoding would claim the
+// multipart envelope itself is compressed.
+async fn multi_range_response(
+    state: &AppState,
+    filepath: &Path,
+    filename: &str,
+    repo_id: &str,
+    revision: &str,
+    ranges: Vec<(u64, u64)>,
+    total: u64,
+) -> Response {
+    // Content-Length is fixed up below, once the part headers (which carry the file's
+    // Content-Type, possibly from the sidecar) are known
+    let mut headers = file_headers_common(filename, revision, total, state.resolve_cache_max_age);
+    if state.resolve_content_disposition {
+        set_content_disposition(&mut headers, filename);
+    }
+    if let Err(e) = ensure_and_insert_etag(
+        state,
+        &mut headers,
+        filepath,
+        filename,
+        repo_id,
+        revision,
+        total,
+    )
+    .await
+    {
+        return e.into_response();
+    }
+    if headers.contains_key("Content-Encoding") {
+        return full_file_response(state, repo_id, revision, filename, filepath).await;
+    }
+    let part_type = headers
+        .get("Content-Type")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("application/octet-stream")
+        .to_string();
+    let boundary = Uuid::new_v4().simple().to_string();
+    let part_heads: Vec<String> = ranges
+        .iter()
+        .enumerate()
+        .map(|(i, (start, end))| {
+            let sep = if i == 0 { "" } else { "\r\n" };
+            format!(
+                "{sep}--{boundary}\r\nContent-Type: {part_type}\r\nContent-Range: bytes {start}-{end}/{total}\r\n\r\n"
+            )
+        })
+        .collect();
+    let tail = format!("\r\n--{boundary}--\r\n");
+    let body_len = part_heads.iter().map(|h| h.len() as u64).sum::<u64>()
+        + ranges.iter().map(|(s, e)| e - s + 1).sum::<u64>()
+        + tail.len() as u64;
+    headers.insert("Content-Length", HeaderValue::from(body_len));
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_str(&format!("multipart/byteranges; boundary={boundary}")).unwrap(),
+    );
+
+    let fp_for_stream = filepath.to_path_buf();
+    let chunk_size = state.chunk_size;
+    let stream = stream! {
+        let mut f = match tokio::fs::File::open(fp_for_stream).await {
+            Ok(f) => f,
+            Err(e) => { error!("open: {}", e); return; }
+        };
+        let mut buf = vec![0u8; chunk_size];
+        for (head, (start, end)) in part_heads.into_iter().zip(ranges) {
+            yield Ok::<Bytes, io::Error>(Bytes::from(head));
+            if let Err(e) = f.seek(std::io::SeekFrom::Start(start)).await {
+                error!("seek: {}", e); return;
+            }
+            let mut remaining = (end - start + 1) as usize;
+            while remaining > 0 {
+                let cap = std::cmp::min(buf.len(), remaining);
+                match f.read(&mut buf[..cap]).await {
+                    Ok(0) => return,
+                    Ok(n) => {
+                        yield Ok::<Bytes, io::Error>(Bytes::copy_from_slice(&buf[..n]));
+                        remaining -= n;
+                    }
+                    Err(e) => { error!("read: {}", e); return; }
+                }
+            }
+        }
+        yield Ok::<Bytes, io::Error>(Bytes::from(tail));
+    };
+    Response::builder()
+        .status(StatusCode::PARTIAL_CONTENT)
+        .body(Body::from_stream(stream))
+        .map(|mut r| {
+            *r.headers_mut() = headers;
+            r
+        })
+        .unwrap()
+        .into_response()
+}
+
+// OPTIONS on resolve/sha256 URLs: 204 with the allowed methods, plus the range advertisement
+// for resolve. Answered from the URL shape alone so a preflight never reveals whether a file exists.
+pub(crate) async fn resolve_options(AxPath(rest): AxPath<String>) -> Response {
+    let path = format!("/{}", rest.trim_start_matches('/'));
+    let is_resolve = path.contains("/resolve/");
+    if !is_resolve && !path.contains("/sha256/") {
+        return AppError::NotFound.into_response();
+    }
+    let mut resp = StatusCode::NO_CONTENT.into_response();
+    let h = resp.headers_mut();
+    h.insert("Allow", HeaderValue::from_static("GET, HEAD, OPTIONS"));
+    if is_resolve {
+        h.insert("Accept-Ranges", HeaderValue::from_static("bytes"));
+    }
+    resp
+}
+
+// `.repo-meta.json` access rules for the repo a resolve URL points into.
+async fn check_resolve_access(
+    state: &AppState,
+    left: &str,
+    headers: &HeaderMap,
+) -> Result<(), AppError> {
+    // A missing repo is left to the file lookup (and PROXY_MODE) that follows
+    if !secure_join(&state.root, left, state.allow_symlink_escape).is_some_and(|p| p.is_dir()) {
+        return Ok(());
+    }
+    let (kind, repo_id) = if let Some(id) = left.strip_prefix("datasets/") {
+        (RepoKind::Dataset, id)
+    } else if let Some(id) = left.strip_prefix("spaces/") {
+        (RepoKind::Space, id)
+    } else {
+        (RepoKind::Model, left)
+    };
+    accessible_repo_path(state, kind, repo_id, request_auth(state, headers))
+        .await
+        .map(|_| ())
+}
+
+// Traversal attempts are reported as 403 so they stand apart from genuine misses.
+fn join_rejection(err: JoinError) -> Response {
+    match err {
+        JoinError::Escapes => AppError::PathForbidden.into_response(),
+        JoinError::BaseMissing => AppError::FileNotFound.into_response(),
+    }
+}
+
+// Index for a directory URL, built from the repo sidecar.
+async fn directory_listing(
+    state: &AppState,
+    repo_id: &str,
+    revision: &str,
+    dir: &str,
+    headers: &HeaderMap,
+) -> Response {
+    let Some(repo_dir) = secure_join(&state.root, repo_id, state.allow_symlink_escape) else {
+        return AppError::RepoNotFound(RepoKind::Model).into_response();
+    };
+    let sc_map = match load_sidecar_map(state, &repo_dir).await {
+        Ok(m) => m,
+        Err(e) => return e.into_response(),
+    };
+    let dir = dir.trim_matches('/');
+    let Some(records) = sidecar_dir_records(&sc_map, dir) else {
+        return AppError::SidecarMissing("Sidecar missing size").into_response();
+    };
+    if prefers_html(headers) {
+        let base_href = format!("/{repo_id}/resolve/{revision}");
+        Html(render_html_listing(&base_href, dir, &records)).into_response()
+    } else {
+        Json(records).into_response()
+    }
+}
+
 // Compute sha256 with TTL cache keyed by (path, mtime, size)
 async fn sha256_file_cached(state: &AppState, p: &Path) -> io::Result<String> {
+    digest_file_cached::<sha2::Sha256>(state, p, &SHA256_CACHE, &crate::metrics::SHA256_CACHE).await
+}
+
+// Plain SHA-1 of the file bytes, i.e. the `oid` fetch_repo and PUT uploads write for
+// non-LFS files.
+async fn sha1_file_cached(state: &AppState, p: &Path) -> io::Result<String> {
+    digest_file_cached::<sha1::Sha1>(state, p, &SHA1_CACHE, &crate::metrics::SHA1_CACHE).await
+}
+
+// Hex digest of a file, cached per (path, mtime, size) with the sha256 TTL and capacity.
+async fn digest_file_cached<D: Digest>(
+    state: &AppState,
+    p: &Path,
+    cache_lock: &DigestCacheLock,
+    counters: &crate::metrics::CacheCounters,
+) -> io::Result<String> {
     let md = tokio::fs::metadata(p).await?;
     let size = md.len();
     let mtime = md
@@ -301,46 +864,46 @@ async fn sha256_file_cached(state: &AppState, p: &Path) -> io::Result<String> {
     // p is canonical at call sites; avoid redundant canonicalize for cache key
     let key = (p.to_path_buf(), mtime, size);
     if let Some(hit) = {
-        let cache = SHA256_CACHE.read().await;
+        let cache = cache_lock.read().await;
         cache.inner.get(&key).cloned()
-    } {
-        if std::time::Instant::now().duration_since(hit.at) < state.cache_ttl {
-            let fresh = std::time::Instant::now();
-            let mut cachew = SHA256_CACHE.write().await;
-            let cloned = if let Some(entry) = cachew.inner.get_mut(&key) {
-                entry.at = fresh;
-                Some(entry.sum.clone())
-            } else {
-                None
-            };
-            cachew.evict_q.push_back((key.clone(), fresh));
-            if let Some(sum) = cloned {
-                return Ok(sum);
-            }
-            return Ok(hit.sum);
+    } && std::time::Instant::now().duration_since(hit.at) < state.sha256_cache_ttl
+    {
+        counters.hit();
+        let fresh = std::time::Instant::now();
+        let mut cachew = cache_lock.write().await;
+        let cloned = if let Some(entry) = cachew.inner.get_mut(&key) {
+            entry.at = fresh;
+            Some(entry.sum.clone())
+        } else {
+            None
+        };
+        cachew.evict_q.push_back((key.clone(), fresh));
+        if let Some(sum) = cloned {
+            return Ok(sum);
         }
+        return Ok(hit.sum);
     }
+    counters.miss();
     let mut file = tokio::fs::File::open(p).await?;
-    let mut hasher = sha2::Sha256::new();
-    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut hasher = D::new();
+    let mut buf = vec![0u8; state.chunk_size];
     loop {
         let n = file.read(&mut buf).await?;
         if n == 0 {
             break;
         }
-        use sha2::Digest;
         hasher.update(&buf[..n]);
     }
     let sum = hex::encode(hasher.finalize());
     {
-        let mut cache = SHA256_CACHE.write().await;
+        let mut cache = cache_lock.write().await;
         if cache.inner.len() >= state.sha256_cache_cap {
             while let Some((old_k, old_at)) = cache.evict_q.pop_front() {
-                if let Some(entry) = cache.inner.get(&old_k) {
-                    if entry.at == old_at {
-                        cache.inner.remove(&old_k);
-                        break;
-                    }
+                if let Some(entry) = cache.inner.get(&old_k)
+                    && entry.at == old_at
+                {
+                    cache.inner.remove(&old_k);
+                    break;
                 }
             }
         }
@@ -357,16 +920,47 @@ async fn sha256_file_cached(state: &AppState, p: &Path) -> io::Result<String> {
     Ok(sum)
 }
 
-// Strictly load ETag from sidecar and inject into headers.
-// No fallback permitted: on failure returns an HTTP 500 Response.
+// Framing and validator headers a sidecar `headers` object may not replace.
+const RESERVED_SIDECAR_HEADERS: [&str; 4] = [
+    "content-length",
+    "content-range",
+    "etag",
+    "transfer-encoding",
+];
+
+// Sidecar `"headers": {"X-Custom-Meta": "v"}`: extra response headers for one file.
+// Non-string values, invalid names/values and reserved headers are skipped.
+fn insert_sidecar_headers(headers: &mut HeaderMap, extra: &serde_json::Map<String, Value>) {
+    for (name, value) in extra {
+        let Some(value) = value.as_str() else {
+            continue;
+        };
+        let Ok(name) = HeaderName::from_bytes(name.as_bytes()) else {
+            continue;
+        };
+        if RESERVED_SIDECAR_HEADERS.contains(&name.as_str()) {
+            continue;
+        }
+        if let Ok(hv) = HeaderValue::from_str(value) {
+            headers.insert(name, hv);
+        }
+    }
+}
+
+// Load the ETag from the sidecar and inject it into headers, along with the
+// Last-Modified validator derived from the file mtime. By default the sidecar is the
+// only source and a missing ETag is a 500 (`etag_unavailable`). Two opt-in modes hash
+// from disk instead: ETAG_RECOMPUTE replaces the sidecar value of every non-LFS file,
+// and ETAG_FALLBACK_COMPUTE fills in the same SHA-1 for files with no sidecar entry.
 async fn ensure_and_insert_etag(
+    state: &AppState,
     headers: &mut HeaderMap,
     filepath: &Path,
     filename: &str,
     repo_id: &str,
     revision: &str,
     total_size: u64,
-) -> Result<(), Response> {
+) -> Result<(), AppError> {
     // Derive repo root by walking up path components of filename.
     let mut repo_root = filepath.to_path_buf();
     let depth = filename.split('/').count();
@@ -375,28 +969,102 @@ async fn ensure_and_insert_etag(
             repo_root = parent.to_path_buf();
         }
     }
-    let sc_map = get_sidecar_map(&repo_root).await.unwrap_or_default();
+    if let Some(mtime) = fs::metadata(filepath)
+        .await
+        .ok()
+        .and_then(|m| m.modified().ok())
+        && let Ok(hv) = HeaderValue::from_str(&httpdate::fmt_http_date(mtime))
+    {
+        headers.insert("Last-Modified", hv);
+    }
+    let sc_map = load_sidecar_map(state, &repo_root).await?;
     let rel_path = filename.replace('\\', "/");
-    let etag_pair = etag_from_sidecar(&sc_map, &rel_path, total_size);
+    let mut etag_pair = etag_from_sidecar(&sc_map, &rel_path, total_size);
+    let entry = sc_map.get(&rel_path);
+    // `"etag_weak": true` on the sidecar entry emits `W/"..."` instead of a strong tag
+    let weak = entry
+        .and_then(|e| e.get("etag_weak"))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    // `"content_encoding"`: the stored bytes are already compressed; they are served
+    // verbatim (ranges included) and the client decodes them, so Content-Type describes the
+    // decoded body
+    match entry
+        .and_then(|e| e.get("content_encoding"))
+        .and_then(|v| v.as_str())
+    {
+        Some(enc @ ("gzip" | "br" | "zstd" | "deflate")) => {
+            headers.insert("Content-Encoding", HeaderValue::from_str(enc).unwrap());
+            headers.insert(
+                "Content-Type",
+                HeaderValue::from_static(content_type_for_encoded(&rel_path, enc)),
+            );
+        }
+        Some("identity") | None => {}
+        Some(other) => {
+            debug!(target: "fakehub", "ignoring content_encoding {:?} for {}", other, rel_path)
+        }
+    }
+    // `"content_type"` replaces the extension-derived Content-Type
+    if let Some(hv) = entry
+        .and_then(|e| e.get("content_type"))
+        .and_then(|v| v.as_str())
+        .and_then(|s| HeaderValue::from_str(s).ok())
+    {
+        headers.insert("Content-Type", hv);
+    }
+    if let Some(extra) = entry
+        .and_then(|e| e.get("headers"))
+        .and_then(|v| v.as_object())
+    {
+        insert_sidecar_headers(headers, extra);
+    }
+    // ETAG_RECOMPUTE: hash non-LFS files from disk so hand-edited fixtures stay truthful
+    if state.etag_recompute && !matches!(etag_pair, Some((_, true))) {
+        match sha1_file_cached(state, filepath).await {
+            Ok(sum) => etag_pair = Some((sum, false)),
+            Err(e) => {
+                error!("ETag recompute failed for {}: {}", filepath.display(), e);
+                return Err(AppError::EtagUnavailable);
+            }
+        }
+    }
+    // ETAG_FALLBACK_COMPUTE: files dropped in without a sidecar entry get their content
+    // SHA-1, the same value ETAG_RECOMPUTE and PUT uploads produce
+    if etag_pair.is_none() && state.etag_fallback_compute && !sc_map.contains_key(&rel_path) {
+        match sha1_file_cached(state, filepath).await {
+            Ok(sum) => etag_pair = Some((sum, false)),
+            Err(e) => {
+                error!("ETag fallback failed for {}: {}", filepath.display(), e);
+                return Err(AppError::EtagUnavailable);
+            }
+        }
+    }
     match etag_pair {
         None => {
             error!("ETag missing for {}@{}:{}", repo_id, revision, rel_path);
-            Err(http_error(
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "ETag not available",
-            ))
+            Err(AppError::EtagUnavailable)
         }
         Some((etag, is_lfs)) => {
             let quoted = format!("\"{etag}\"");
+            let tag = if weak {
+                format!("W/{quoted}")
+            } else {
+                quoted.clone()
+            };
             headers.insert(
                 "ETag",
-                HeaderValue::from_str(&quoted).unwrap_or(HeaderValue::from_static("\"-\"")),
+                HeaderValue::from_str(&tag).unwrap_or(HeaderValue::from_static("\"-\"")),
             );
             if is_lfs {
+                let size_hv = HeaderValue::from_str(&total_size.to_string()).unwrap();
+                headers.insert("x-lfs-size", size_hv.clone());
+                // Linked object validators: the sha256 of the blob behind the pointer.
                 headers.insert(
-                    "x-lfs-size",
-                    HeaderValue::from_str(&total_size.to_string()).unwrap(),
+                    "X-Linked-Etag",
+                    HeaderValue::from_str(&quoted).unwrap_or(HeaderValue::from_static("\"-\"")),
                 );
+                headers.insert("X-Linked-Size", size_hv);
             }
             Ok(())
         }
@@ -409,7 +1077,6 @@ mod tests {
     use super::*;
     use axum::Router;
     use axum::routing::get;
-    use std::sync::Arc;
     use tower::util::ServiceExt;
 
     #[test]
@@ -435,48 +1102,35 @@ mod tests {
         ));
     }
 
-    #[tokio::test]
-    async fn router_head_get_with_etag() {
-        // Arrange a tiny repo under fake_hub/tests_repo_etag
+    // Arrange a tiny repo under fake_hub/{repo_id} holding x.bin with an LFS sidecar entry.
+    async fn setup_repo(repo_id: &str, content: &[u8], lfs_oid: &str) -> Router {
         let root = dunce::canonicalize("fake_hub")
             .unwrap_or_else(|_| std::path::PathBuf::from("fake_hub"));
-        let repo_id = "tests_repo_etag";
         let repo_dir = root.join(repo_id);
         tokio::fs::create_dir_all(&repo_dir).await.unwrap();
         let file_path = repo_dir.join("x.bin");
-        tokio::fs::write(&file_path, b"hello").await.unwrap();
+        tokio::fs::write(&file_path, content).await.unwrap();
         let size = file_path.metadata().unwrap().len();
         let sidecar = repo_dir.join(".paths-info.json");
         let sc = serde_json::json!({
             "entries": [{
                 "path": "x.bin", "type": "file", "size": size as i64,
-                "lfs": {"oid": "sha256:1234", "size": size as i64}
+                "lfs": {"oid": lfs_oid, "size": size as i64}
             }]
         });
         tokio::fs::write(&sidecar, serde_json::to_vec(&sc).unwrap())
             .await
             .unwrap();
-
         // Build router with only resolve route
-        let state = AppState {
-            root: Arc::new(root.clone()),
-            log_requests: false,
-            log_body_max: 1024,
-            log_headers_mode_all: false,
-            log_resp_headers: false,
-            log_redact: true,
-            log_body_all: false,
-            log_json_body: false,
-            ip_log_retention_secs: 1_800,
-            ip_log_per_ip_cap: 200,
-            cache_ttl: std::time::Duration::from_millis(2000),
-            paths_info_cache_cap: 64,
-            siblings_cache_cap: 64,
-            sha256_cache_cap: 64,
-        };
-        let app = Router::new()
+        Router::new()
             .route("/{*rest}", get(resolve_catchall).head(resolve_catchall))
-            .with_state(state);
+            .with_state(AppState::for_tests(root))
+    }
+
+    #[tokio::test]
+    async fn router_head_get_with_etag() {
+        let repo_id = "tests_repo_etag";
+        let app = setup_repo(repo_id, b"hello", "sha256:1234").await;
 
         // HEAD should return ETag from sidecar (1234)
         let uri = format!("/{repo_id}/resolve/main/x.bin");
@@ -495,6 +1149,8 @@ mod tests {
         let etag = resp.headers().get("ETag").unwrap().to_str().unwrap();
         assert_eq!(etag, "\"1234\"");
         assert!(resp.headers().get("Accept-Ranges").is_some());
+        assert_eq!(resp.headers().get("X-Linked-Etag").unwrap(), "\"1234\"");
+        assert_eq!(resp.headers().get("X-Linked-Size").unwrap(), "5");
 
         // GET with range
         let req = axum::http::Request::builder()
@@ -513,5 +1169,1171 @@ mod tests {
             .unwrap();
         assert!(cr.starts_with("bytes 0-1/"));
         assert!(resp.headers().get("Accept-Ranges").is_some());
+        assert_eq!(
+            resp.headers().get("Content-Disposition").unwrap(),
+            "attachment; filename=\"x.bin\""
+        );
+    }
+
+    #[test]
+    fn etag_list_matching() {
+        assert!(etag_list_matches("*", "\"abc\""));
+        assert!(etag_list_matches("\"abc\"", "\"abc\""));
+        assert!(etag_list_matches("\"x\", \"abc\"", "\"abc\""));
+        assert!(!etag_list_matches("\"x\", \"y\"", "\"abc\""));
+        assert!(!etag_list_matches("", "\"abc\""));
+        // Weak comparison: W/ is ignored on either side
+        assert!(etag_list_matches("W/\"abc\"", "\"abc\""));
+        assert!(etag_list_matches("\"abc\"", "W/\"abc\""));
+        assert!(etag_list_matches("\"x\", W/\"abc\"", "W/\"abc\""));
+        assert!(!etag_list_matches("W/\"x\"", "W/\"abc\""));
+    }
+
+    #[tokio::test]
+    async fn if_none_match_returns_304() {
+        let repo_id = "tests_repo_inm";
+        let app = setup_repo(repo_id, b"hello", "sha256:5678").await;
+        let uri = format!("/{repo_id}/resolve/main/x.bin");
+
+        for (method, range) in [("GET", None), ("GET", Some("bytes=0-1")), ("HEAD", None)] {
+            let mut builder = axum::http::Request::builder()
+                .method(method)
+                .uri(&uri)
+                .header("If-None-Match", "\"other\", \"5678\"");
+            if let Some(r) = range {
+                builder = builder.header("Range", r);
+            }
+            let resp = app
+                .clone()
+                .oneshot(builder.body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+            assert_eq!(resp.headers().get("ETag").unwrap(), "\"5678\"");
+            assert_eq!(resp.headers().get("x-repo-commit").unwrap(), "main");
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert!(body.is_empty());
+        }
+
+        // Non-matching tag still streams the file
+        let req = axum::http::Request::builder()
+            .method("GET")
+            .uri(&uri)
+            .header("If-None-Match", "\"stale\"")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn if_range_validators() {
+        let mtime = UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+        assert!(if_range_matches("\"abc\"", "\"abc\"", Some(mtime)));
+        assert!(!if_range_matches("\"old\"", "\"abc\"", Some(mtime)));
+        assert!(!if_range_matches("W/\"abc\"", "\"abc\"", Some(mtime)));
+        // Strong comparison: a weak current tag never validates a range
+        assert!(!if_range_matches("\"abc\"", "W/\"abc\"", Some(mtime)));
+        assert!(!if_range_matches("W/\"abc\"", "W/\"abc\"", Some(mtime)));
+        let date = httpdate::fmt_http_date(mtime);
+        assert!(if_range_matches(&date, "\"abc\"", Some(mtime)));
+        let later = httpdate::fmt_http_date(mtime + std::time::Duration::from_secs(60));
+        assert!(!if_range_matches(&later, "\"abc\"", Some(mtime)));
+        assert!(!if_range_matches("not a date", "\"abc\"", Some(mtime)));
+    }
+
+    #[tokio::test]
+    async fn if_range_etag_match_and_mismatch() {
+        let repo_id = "tests_repo_if_range";
+        let app = setup_repo(repo_id, b"hello world", "sha256:9abc").await;
+        let uri = format!("/{repo_id}/resolve/main/x.bin");
+
+        let req = axum::http::Request::builder()
+            .method("GET")
+            .uri(&uri)
+            .header("Range", "bytes=0-4")
+            .header("If-Range", "\"9abc\"")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"hello");
+
+        // Changed ETag: ignore the range and return the whole file
+        let req = axum::http::Request::builder()
+            .method("GET")
+            .uri(&uri)
+            .header("Range", "bytes=0-4")
+            .header("If-Range", "\"changed\"")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.headers().get("Content-Range").is_none());
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"hello world");
+    }
+
+    #[tokio::test]
+    async fn external_sidecar_dir_serves_etags() {
+        let root = dunce::canonicalize("fake_hub").unwrap();
+        let repo_dir = root.join("tests_org_ext").join("ro-model");
+        tokio::fs::create_dir_all(&repo_dir).await.unwrap();
+        tokio::fs::write(repo_dir.join("x.bin"), b"hello")
+            .await
+            .unwrap();
+        let _ = tokio::fs::remove_file(repo_dir.join(".paths-info.json")).await;
+        // Outside FAKE_HUB_ROOT, so other tests never pick it up as a repo
+        let ext = std::env::temp_dir().join(format!("fakehub-sidecars-{}", std::process::id()));
+        tokio::fs::create_dir_all(ext.join("tests_org_ext"))
+            .await
+            .unwrap();
+        let sc = serde_json::json!({"entries": [
+            {"path": "x.bin", "type": "file", "size": 5, "oid": "ext0"},
+        ]});
+        tokio::fs::write(
+            ext.join("tests_org_ext").join("ro-model.paths-info.json"),
+            sc.to_string(),
+        )
+        .await
+        .unwrap();
+        let mut state = AppState::for_tests(root);
+        state.sidecar_dir = Some(std::sync::Arc::new(ext.clone()));
+
+        let app = Router::new()
+            .route("/{*rest}", get(resolve_catchall))
+            .with_state(state);
+        let req = axum::http::Request::builder()
+            .uri("/tests_org_ext/ro-model/resolve/main/x.bin")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["ETag"], "\"ext0\"");
+        assert!(!repo_dir.join(".paths-info.json").exists());
+        let _ = tokio::fs::remove_dir_all(&ext).await;
+    }
+
+    #[tokio::test]
+    async fn resolve_is_immutable_cacheable_and_api_is_not() {
+        let repo_id = "tests_repo_cache_headers";
+        let _ = setup_repo(repo_id, b"hello", "sha256:c0de").await;
+        let root = dunce::canonicalize("fake_hub").unwrap();
+        let mut state = AppState::for_tests(root);
+        state.resolve_cache_max_age = 600;
+        // Mounted like main: the no-cache layer covers only routes added before it
+        let app = Router::new()
+            .route("/api/ping", get(|| async { "pong" }))
+            .layer(axum::middleware::from_fn(crate::middleware::no_cache_mw))
+            .route("/{*rest}", get(resolve_catchall))
+            .with_state(state);
+        let send = |uri: String, inm: Option<&'static str>| {
+            let mut b = axum::http::Request::builder().uri(uri);
+            if let Some(v) = inm {
+                b = b.header("If-None-Match", v);
+            }
+            app.clone().oneshot(b.body(Body::empty()).unwrap())
+        };
+        let uri = format!("/{repo_id}/resolve/main/x.bin");
+
+        for inm in [None, Some("\"c0de\"")] {
+            let resp = send(uri.clone(), inm).await.unwrap();
+            assert_eq!(
+                resp.headers()["Cache-Control"],
+                "public, max-age=600, immutable"
+            );
+            assert_eq!(resp.headers()["Age"], "0");
+        }
+        let resp = send("/api/ping".to_string(), None).await.unwrap();
+        assert_eq!(resp.headers()["Cache-Control"], "no-cache");
+        assert!(resp.headers().get("Age").is_none());
+    }
+
+    #[tokio::test]
+    async fn content_type_from_extension_or_sidecar() {
+        let root = dunce::canonicalize("fake_hub").unwrap();
+        let repo_dir = root.join("tests_repo_content_type");
+        tokio::fs::create_dir_all(&repo_dir).await.unwrap();
+        for name in ["config.json", "notes.txt", "model.safetensors"] {
+            tokio::fs::write(repo_dir.join(name), b"{}").await.unwrap();
+        }
+        let sc = json!({"entries": [
+            {"path": "config.json", "type": "file", "size": 2, "oid": "a"},
+            {"path": "notes.txt", "type": "file", "size": 2, "oid": "b",
+             "content_type": "text/plain; charset=utf-8"},
+            {"path": "model.safetensors", "type": "file", "size": 2, "oid": "c"},
+        ]});
+        tokio::fs::write(repo_dir.join(".paths-info.json"), sc.to_string())
+            .await
+            .unwrap();
+        let app = Router::new()
+            .route("/{*rest}", get(resolve_catchall))
+            .with_state(AppState::for_tests(root));
+        for (name, expected) in [
+            ("config.json", "application/json"),
+            ("notes.txt", "text/plain; charset=utf-8"),
+            ("model.safetensors", "application/octet-stream"),
+        ] {
+            let req = axum::http::Request::builder()
+                .uri(format!("/tests_repo_content_type/resolve/main/{name}"))
+                .body(Body::empty())
+                .unwrap();
+            let resp = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK, "{name}");
+            assert_eq!(resp.headers()["Content-Type"], expected, "{name}");
+        }
+    }
+
+    #[tokio::test]
+    async fn sidecar_headers_are_added_except_reserved() {
+        let root = dunce::canonicalize("fake_hub").unwrap();
+        let repo_dir = root.join("tests_repo_custom_headers");
+        tokio::fs::create_dir_all(&repo_dir).await.unwrap();
+        tokio::fs::write(repo_dir.join("x.bin"), b"hello")
+            .await
+            .unwrap();
+        let sc = json!({"entries": [{
+            "path": "x.bin", "type": "file", "size": 5, "oid": "abc",
+            "headers": {
+                "X-Custom-Meta": "fixture-7",
+                "Content-Length": "1",
+                "etag": "\"forged\"",
+                "X-Not-A-String": 3,
+            },
+        }]});
+        tokio::fs::write(repo_dir.join(".paths-info.json"), sc.to_string())
+            .await
+            .unwrap();
+        let app = Router::new()
+            .route("/{*rest}", get(resolve_catchall))
+            .with_state(AppState::for_tests(root));
+        for range in [None, Some("bytes=1-2")] {
+            let mut b =
+                axum::http::Request::builder().uri("/tests_repo_custom_headers/resolve/main/x.bin");
+            if let Some(r) = range {
+                b = b.header("Range", r);
+            }
+            let resp = app
+                .clone()
+                .oneshot(b.body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(resp.headers()["X-Custom-Meta"], "fixture-7");
+            assert_eq!(resp.headers()["ETag"], "\"abc\"");
+            assert!(resp.headers().get("X-Not-A-String").is_none());
+            let expected_len = if range.is_some() { "2" } else { "5" };
+            assert_eq!(resp.headers()["Content-Length"], expected_len);
+        }
+    }
+
+    #[tokio::test]
+    async fn precompressed_file_declares_content_encoding() {
+        let root = dunce::canonicalize("fake_hub").unwrap();
+        let repo_dir = root.join("tests_repo_content_encoding");
+        tokio::fs::create_dir_all(&repo_dir).await.unwrap();
+        let stored = b"\x1f\x8b\x08\x00stored-gzip-bytes";
+        for name in ["data.json.gz", "plain.txt"] {
+            tokio::fs::write(repo_dir.join(name), stored).await.unwrap();
+        }
+        let sc = json!({"entries": [
+            {"path": "data.json.gz", "type": "file", "size": stored.len(), "oid": "a",
+             "content_encoding": "gzip"},
+            {"path": "plain.txt", "type": "file", "size": stored.len(), "oid": "b"},
+        ]});
+        tokio::fs::write(repo_dir.join(".paths-info.json"), sc.to_string())
+            .await
+            .unwrap();
+        let app = Router::new()
+            .route("/{*rest}", get(resolve_catchall))
+            .with_state(AppState::for_tests(root));
+        let send = |name: &str, range: Option<&'static str>| {
+            let mut b = axum::http::Request::builder()
+                .uri(format!("/tests_repo_content_encoding/resolve/main/{name}"));
+            if let Some(r) = range {
+                b = b.header("Range", r);
+            }
+            app.clone().oneshot(b.body(Body::empty()).unwrap())
+        };
+
+        let resp = send("data.json.gz", None).await.unwrap();
+        assert_eq!(resp.headers()["Content-Encoding"], "gzip");
+        // The type is that of the decoded body, not application/gzip
+        assert_eq!(resp.headers()["Content-Type"], "application/json");
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], stored);
+        // Ranges address the stored (encoded) bytes
+        let resp = send("data.json.gz", Some("bytes=0-1")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(resp.headers()["Content-Encoding"], "gzip");
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"\x1f\x8b");
+        // Several ranges fall back to the whole encoded file rather than an encoded envelope
+        let resp = send("data.json.gz", Some("bytes=0-1,4-5")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["Content-Encoding"], "gzip");
+        assert_eq!(resp.headers()["Content-Type"], "application/json");
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], stored);
+
+        let resp = send("plain.txt", None).await.unwrap();
+        assert!(resp.headers().get("Content-Encoding").is_none());
+    }
+
+    #[tokio::test]
+    async fn resolve_bytes_are_counted_as_the_body_streams() {
+        use std::sync::atomic::Ordering;
+        let repo_id = "tests_repo_resolve_bytes";
+        let content = vec![7u8; 64 * 1024];
+        let app = setup_repo(repo_id, &content, "sha256:5e7a").await;
+        let req = axum::http::Request::builder()
+            .uri(format!("/{repo_id}/resolve/main/x.bin"))
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let before = crate::metrics::RESOLVE_BYTES.load(Ordering::Relaxed);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body.len(), content.len());
+        // Other tests may add to the counter too, but never take away
+        let after = crate::metrics::RESOLVE_BYTES.load(Ordering::Relaxed);
+        assert!(after - before >= content.len() as u64);
+    }
+
+    #[tokio::test]
+    async fn empty_file_ignores_range() {
+        let repo_id = "tests_repo_empty_range";
+        let app = setup_repo(repo_id, b"", "sha256:e3b0").await;
+        let uri = format!("/{repo_id}/resolve/main/x.bin");
+        let req = axum::http::Request::builder()
+            .uri(&uri)
+            .header("Range", "bytes=0-0")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["Accept-Ranges"], "none");
+        assert_eq!(resp.headers()["Content-Length"], "0");
+        assert!(resp.headers().get("Content-Range").is_none());
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn weak_etag_from_sidecar_flag() {
+        let repo_id = "tests_repo_weak_etag";
+        let app = setup_repo(repo_id, b"hello world", "sha256:7def").await;
+        let sidecar = dunce::canonicalize("fake_hub")
+            .unwrap()
+            .join(repo_id)
+            .join(".paths-info.json");
+        let sc = serde_json::json!({"entries": [{
+            "path": "x.bin", "type": "file", "size": 11, "etag_weak": true,
+            "lfs": {"oid": "sha256:7def", "size": 11}
+        }]});
+        tokio::fs::write(&sidecar, sc.to_string()).await.unwrap();
+        let uri = format!("/{repo_id}/resolve/main/x.bin");
+        let send = |headers: &[(&str, &str)]| {
+            let mut b = axum::http::Request::builder().uri(&uri);
+            for (k, v) in headers {
+                b = b.header(*k, *v);
+            }
+            app.clone().oneshot(b.body(Body::empty()).unwrap())
+        };
+
+        let resp = send(&[]).await.unwrap();
+        assert_eq!(resp.headers()["ETag"], "W/\"7def\"");
+        // If-None-Match compares weakly, with or without the prefix
+        for inm in ["\"7def\"", "W/\"7def\""] {
+            let resp = send(&[("If-None-Match", inm)]).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::NOT_MODIFIED, "{inm}");
+        }
+        // If-Range never validates against a weak tag: full body instead of a range
+        let resp = send(&[("Range", "bytes=0-4"), ("If-Range", "W/\"7def\"")])
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn parse_range_multi() {
+        use super::RangeParse;
+        match parse_range("bytes=0-1, 4-5", 10) {
+            RangeParse::Multi(r) => assert_eq!(r, vec![(0, 1), (4, 5)]),
+            _ => panic!("expected multi"),
+        }
+        // Overlapping and adjacent specs coalesce back into a single range
+        assert!(matches!(
+            parse_range("bytes=0-5,3-8", 10),
+            RangeParse::Ok(0, 8)
+        ));
+        assert!(matches!(
+            parse_range("bytes=0-1,2-3", 10),
+            RangeParse::Ok(0, 3)
+        ));
+        // Unsatisfiable parts are dropped; all-unsatisfiable is 416
+        assert!(matches!(
+            parse_range("bytes=2-3,50-60", 10),
+            RangeParse::Ok(2, 3)
+        ));
+        assert!(matches!(
+            parse_range("bytes=20-30,40-50", 10),
+            RangeParse::Unsatisfiable
+        ));
+        // Too many parts collapses to a full body
+        let many = (0..=MAX_RANGE_PARTS)
+            .map(|i| format!("{i}-{i}"))
+            .collect::<Vec<_>>()
+            .join(",");
+        assert!(matches!(
+            parse_range(&format!("bytes={many}"), 1000),
+            RangeParse::Invalid
+        ));
+    }
+
+    #[tokio::test]
+    async fn multi_range_returns_multipart() {
+        let repo_id = "tests_repo_multirange";
+        let app = setup_repo(repo_id, b"0123456789", "sha256:77aa").await;
+        let uri = format!("/{repo_id}/resolve/main/x.bin");
+        let req = axum::http::Request::builder()
+            .method("GET")
+            .uri(&uri)
+            .header("Range", "bytes=0-1,7-8")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        let ct = resp
+            .headers()
+            .get("Content-Type")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        let boundary = ct
+            .strip_prefix("multipart/byteranges; boundary=")
+            .unwrap()
+            .to_string();
+        let cl: usize = resp.headers()["Content-Length"]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body.len(), cl);
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        assert!(text.starts_with(&format!("--{boundary}\r\n")));
+        assert!(text.contains("Content-Range: bytes 0-1/10\r\n\r\n01\r\n"));
+        assert!(text.contains("Content-Range: bytes 7-8/10\r\n\r\n78\r\n"));
+        assert!(text.ends_with(&format!("--{boundary}--\r\n")));
+    }
+
+    #[tokio::test]
+    async fn non_lfs_has_no_linked_headers() {
+        let root = dunce::canonicalize("fake_hub")
+            .unwrap_or_else(|_| std::path::PathBuf::from("fake_hub"));
+        let repo_dir = root.join("tests_repo_non_lfs");
+        tokio::fs::create_dir_all(&repo_dir).await.unwrap();
+        tokio::fs::write(repo_dir.join("x.txt"), b"plain")
+            .await
+            .unwrap();
+        let sc = serde_json::json!({
+            "entries": [{"path": "x.txt", "type": "file", "size": 5, "oid": "abcd"}]
+        });
+        tokio::fs::write(repo_dir.join(".paths-info.json"), sc.to_string())
+            .await
+            .unwrap();
+        let app = Router::new()
+            .route("/{*rest}", get(resolve_catchall).head(resolve_catchall))
+            .with_state(AppState::for_tests(root));
+        let req = axum::http::Request::builder()
+            .method("HEAD")
+            .uri("/tests_repo_non_lfs/resolve/main/x.txt")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get("ETag").unwrap(), "\"abcd\"");
+        assert!(resp.headers().get("X-Linked-Etag").is_none());
+        assert!(resp.headers().get("X-Linked-Size").is_none());
+    }
+
+    #[test]
+    fn if_modified_since_formats() {
+        let mtime = UNIX_EPOCH + std::time::Duration::from_secs(784_111_777);
+        // IMF-fixdate, RFC 850 and asctime forms of the same instant
+        assert!(not_modified_since(
+            "Sun, 06 Nov 1994 08:49:37 GMT",
+            Some(mtime)
+        ));
+        assert!(not_modified_since(
+            "Sunday, 06-Nov-94 08:49:37 GMT",
+            Some(mtime)
+        ));
+        assert!(not_modified_since("Sun Nov  6 08:49:37 1994", Some(mtime)));
+        assert!(!not_modified_since(
+            "Sun, 06 Nov 1994 08:49:36 GMT",
+            Some(mtime)
+        ));
+        assert!(!not_modified_since("garbage", Some(mtime)));
+    }
+
+    #[tokio::test]
+    async fn if_modified_since_304_and_200() {
+        let repo_id = "tests_repo_ims";
+        let app = setup_repo(repo_id, b"hello", "sha256:4242").await;
+        let uri = format!("/{repo_id}/resolve/main/x.bin");
+
+        let req = axum::http::Request::builder()
+            .method("HEAD")
+            .uri(&uri)
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        let last_modified = resp.headers()["Last-Modified"]
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        // Unchanged since the advertised date
+        let req = axum::http::Request::builder()
+            .method("GET")
+            .uri(&uri)
+            .header("If-Modified-Since", &last_modified)
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+
+        // File is newer than the client's copy
+        let req = axum::http::Request::builder()
+            .method("GET")
+            .uri(&uri)
+            .header("If-Modified-Since", "Thu, 01 Jan 1970 00:00:00 GMT")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.headers().get("Last-Modified").is_some());
+
+        // A non-matching If-None-Match wins over a satisfied If-Modified-Since
+        let req = axum::http::Request::builder()
+            .method("GET")
+            .uri(&uri)
+            .header("If-None-Match", "\"stale\"")
+            .header("If-Modified-Since", &last_modified)
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn directory_url_lists_entries() {
+        let repo_id = "tests_repo_dirlist";
+        let app = setup_repo(repo_id, b"hello", "sha256:1234").await;
+        // The repo root is a directory; HTML for browsers, paths-info JSON otherwise
+        let uri = format!("/{repo_id}/resolve/main/");
+        let resp = app
+            .clone()
+            .oneshot(
+                axum::http::Request::builder()
+                    .uri(&uri)
+                    .header("Accept", "text/html,*/*;q=0.8")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(
+            resp.headers()["content-type"]
+                .to_str()
+                .unwrap()
+                .starts_with("text/html")
+        );
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let html = String::from_utf8(body.to_vec()).unwrap();
+        assert!(html.contains(&format!("href=\"/{repo_id}/resolve/main/x.bin\"")));
+
+        let resp = app
+            .oneshot(
+                axum::http::Request::builder()
+                    .uri(&uri)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(v[0]["type"], "directory");
+        assert_eq!(v[1]["path"], "x.bin");
+        assert_eq!(v[1]["size"], 5);
+    }
+
+    #[tokio::test]
+    async fn percent_encoded_names_resolve_per_segment() {
+        let repo_id = "tests_repo_encoded_names";
+        let app = setup_repo(repo_id, b"hello", "sha256:1234").await;
+        let repo_dir = dunce::canonicalize("fake_hub").unwrap().join(repo_id);
+        tokio::fs::create_dir_all(repo_dir.join("sub"))
+            .await
+            .unwrap();
+        tokio::fs::write(repo_dir.join("my file+1.bin"), b"spaced")
+            .await
+            .unwrap();
+        tokio::fs::write(repo_dir.join("sub/x.bin"), b"nested")
+            .await
+            .unwrap();
+        let sc = json!({"entries": [
+            {"path": "my file+1.bin", "type": "file", "size": 6, "oid": "a1"},
+            {"path": "sub/x.bin", "type": "file", "size": 6, "oid": "b2"},
+        ]});
+        tokio::fs::write(repo_dir.join(".paths-info.json"), sc.to_string())
+            .await
+            .unwrap();
+        let get = |uri: String| {
+            let req = axum::http::Request::builder()
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap();
+            app.clone().oneshot(req)
+        };
+
+        let resp = get(format!("/{repo_id}/resolve/main/my%20file%2B1.bin"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"spaced");
+        // A literal `+` stays a plus, and an encoded `/` is not a separator
+        let resp = get(format!("/{repo_id}/resolve/main/my%20file+1.bin"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = get(format!("/{repo_id}/resolve/main/sub%2Fx.bin"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let resp = get(format!("/{repo_id}/resolve/main/sub/x.bin"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn control_characters_in_path_are_rejected() {
+        let repo_id = "tests_repo_invalid_path";
+        let app = setup_repo(repo_id, b"hello", "sha256:1234").await;
+        let mut uris = vec![
+            format!("/{repo_id}/resolve/main/x%00.bin"),
+            format!("/{repo_id}/resolve/main/x%1F.bin"),
+            format!("/{repo_id}/sha256/main/x%0A.bin"),
+            format!("/{repo_id}/resolve/ma%07in/x.bin"),
+        ];
+        if cfg!(unix) {
+            uris.push(format!("/{repo_id}/resolve/main/..%5C..%5Cx.bin"));
+        }
+        for uri in uris {
+            let req = axum::http::Request::builder()
+                .uri(&uri)
+                .body(Body::empty())
+                .unwrap();
+            let resp = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{uri}");
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(v["error"], "invalid_path");
+        }
+        let req = axum::http::Request::builder()
+            .uri(format!("/{repo_id}/resolve/main/x.bin"))
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(app.oneshot(req).await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn traversal_is_forbidden_but_missing_is_not_found() {
+        let repo_id = "tests_repo_traversal";
+        let app = setup_repo(repo_id, b"hello", "sha256:1234").await;
+        for uri in [
+            format!("/{repo_id}/resolve/main/../../../../etc/passwd"),
+            format!("/{repo_id}/sha256/main/../../../../etc/passwd"),
+        ] {
+            let req = axum::http::Request::builder()
+                .uri(&uri)
+                .body(Body::empty())
+                .unwrap();
+            let resp = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::FORBIDDEN, "{uri}");
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(v["error"], "path_forbidden");
+            assert_eq!(v["detail"], "Path not allowed");
+        }
+        for uri in [
+            format!("/{repo_id}/resolve/main/missing.bin"),
+            format!("/{repo_id}/sha256/main/missing.bin"),
+        ] {
+            let req = axum::http::Request::builder()
+                .uri(&uri)
+                .body(Body::empty())
+                .unwrap();
+            let resp = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND, "{uri}");
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(v["error"], "file_not_found");
+        }
+
+        // unsatisfiable range keeps its Content-Range and gains a coded body
+        let req = axum::http::Request::builder()
+            .uri(format!("/{repo_id}/resolve/main/x.bin"))
+            .header("Range", "bytes=100-200")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(resp.headers()["Content-Range"], "bytes */5");
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(v["error"], "range_not_satisfiable");
+    }
+
+    #[tokio::test]
+    async fn etag_recompute_ignores_stale_sidecar_oid() {
+        let root = dunce::canonicalize("fake_hub").unwrap();
+        let repo_dir = root.join("tests_repo_etag_recompute");
+        tokio::fs::create_dir_all(&repo_dir).await.unwrap();
+        tokio::fs::write(repo_dir.join("a.txt"), b"edited by hand")
+            .await
+            .unwrap();
+        let sc = serde_json::json!({"entries": [
+            {"path": "a.txt", "type": "file", "size": 14, "oid": "stale"},
+        ]});
+        tokio::fs::write(repo_dir.join(".paths-info.json"), sc.to_string())
+            .await
+            .unwrap();
+        let mut state = AppState::for_tests(root);
+        let head = |state: AppState| async move {
+            let app = Router::new()
+                .route("/{*rest}", get(resolve_catchall).head(resolve_catchall))
+                .with_state(state);
+            let req = axum::http::Request::builder()
+                .method("HEAD")
+                .uri("/tests_repo_etag_recompute/resolve/main/a.txt")
+                .body(Body::empty())
+                .unwrap();
+            let resp = app.oneshot(req).await.unwrap();
+            resp.headers()["ETag"].to_str().unwrap().to_string()
+        };
+        assert_eq!(head(state.clone()).await, "\"stale\"");
+
+        state.etag_recompute = true;
+        let expected = hex::encode(sha1::Sha1::digest(b"edited by hand"));
+        assert_eq!(head(state).await, format!("\"{expected}\""));
+    }
+
+    #[tokio::test]
+    async fn etag_fallback_hashes_file_missing_from_sidecar() {
+        let root = dunce::canonicalize("fake_hub").unwrap();
+        let repo_dir = root.join("tests_repo_etag_fallback");
+        tokio::fs::create_dir_all(&repo_dir).await.unwrap();
+        tokio::fs::write(repo_dir.join("listed.txt"), b"listed")
+            .await
+            .unwrap();
+        tokio::fs::write(repo_dir.join("dropped.txt"), b"dropped in")
+            .await
+            .unwrap();
+        let sc = serde_json::json!({"entries": [
+            {"path": "listed.txt", "type": "file", "size": 6, "oid": "abcd"},
+        ]});
+        tokio::fs::write(repo_dir.join(".paths-info.json"), sc.to_string())
+            .await
+            .unwrap();
+        let mut state = AppState::for_tests(root);
+        let head = |state: AppState| async move {
+            let app = Router::new()
+                .route("/{*rest}", get(resolve_catchall).head(resolve_catchall))
+                .with_state(state);
+            let req = axum::http::Request::builder()
+                .method("HEAD")
+                .uri("/tests_repo_etag_fallback/resolve/main/dropped.txt")
+                .body(Body::empty())
+                .unwrap();
+            app.oneshot(req).await.unwrap()
+        };
+        let resp = head(state.clone()).await;
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        state.etag_fallback_compute = true;
+        let resp = head(state).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        // Plain SHA-1 of the bytes, as ETAG_RECOMPUTE and uploads compute it
+        let expected = hex::encode(sha1::Sha1::digest(b"dropped in"));
+        assert_eq!(resp.headers()["ETag"], format!("\"{expected}\"").as_str());
+    }
+
+    #[tokio::test]
+    async fn refs_json_maps_revision_to_commit() {
+        let root = dunce::canonicalize("fake_hub").unwrap();
+        let repo_dir = root.join("tests_repo_ref_alias");
+        tokio::fs::create_dir_all(&repo_dir).await.unwrap();
+        tokio::fs::write(repo_dir.join("x.bin"), b"x")
+            .await
+            .unwrap();
+        let sc = serde_json::json!({
+            "entries": [{"path": "x.bin", "type": "file", "size": 1, "oid": "abcd"}]
+        });
+        tokio::fs::write(repo_dir.join(".paths-info.json"), sc.to_string())
+            .await
+            .unwrap();
+        let sha = "0123456789abcdef0123456789abcdef01234567";
+        let refs = serde_json::json!({
+            "branches": [],
+            "tags": [{"name": "v1.0", "ref": "refs/tags/v1.0", "targetCommit": sha}],
+        });
+        tokio::fs::write(repo_dir.join(".refs.json"), refs.to_string())
+            .await
+            .unwrap();
+        let app = Router::new()
+            .route("/{*rest}", get(resolve_catchall).head(resolve_catchall))
+            .with_state(AppState::for_tests(root));
+        let head = |rev: &str| {
+            axum::http::Request::builder()
+                .method("HEAD")
+                .uri(format!("/tests_repo_ref_alias/resolve/{rev}/x.bin"))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let resp = app.clone().oneshot(head("v1.0")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["x-repo-commit"], sha);
+        assert_eq!(resp.headers()["x-revision"], "v1.0");
+
+        let resp = app.oneshot(head("main")).await.unwrap();
+        assert_eq!(resp.headers()["x-repo-commit"], "main");
+        assert_eq!(resp.headers()["x-revision"], "main");
+    }
+
+    #[tokio::test]
+    async fn small_chunk_size_streams_whole_range() {
+        let root = dunce::canonicalize("fake_hub").unwrap();
+        let repo_dir = root.join("tests_repo_chunk_size");
+        tokio::fs::create_dir_all(&repo_dir).await.unwrap();
+        let content: Vec<u8> = (0..20_000u32).map(|i| (i % 251) as u8).collect();
+        tokio::fs::write(repo_dir.join("x.bin"), &content)
+            .await
+            .unwrap();
+        let sc = serde_json::json!({
+            "entries": [{"path": "x.bin", "type": "file", "size": content.len(), "oid": "abcd"}]
+        });
+        tokio::fs::write(repo_dir.join(".paths-info.json"), sc.to_string())
+            .await
+            .unwrap();
+        let mut state = AppState::for_tests(root);
+        state.chunk_size = 4_096;
+        let app = Router::new()
+            .route("/{*rest}", get(resolve_catchall).head(resolve_catchall))
+            .with_state(state);
+        let get = |range: Option<&str>| {
+            let mut b =
+                axum::http::Request::builder().uri("/tests_repo_chunk_size/resolve/main/x.bin");
+            if let Some(r) = range {
+                b = b.header("Range", r);
+            }
+            b.body(Body::empty()).unwrap()
+        };
+
+        let resp = app
+            .clone()
+            .oneshot(get(Some("bytes=100-15099")))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], &content[100..15_100]);
+
+        let resp = app.oneshot(get(None)).await.unwrap();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], &content[..]);
+    }
+
+    #[tokio::test]
+    async fn large_full_file_body_is_intact() {
+        let content: Vec<u8> = (0..(3u32 << 20)).map(|i| (i % 251) as u8).collect();
+        let app = setup_repo("tests_repo_large_body", &content, "sha256:large").await;
+        let req = axum::http::Request::builder()
+            .uri("/tests_repo_large_body/resolve/main/x.bin")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["ETag"], "\"large\"");
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(body[..] == content[..]);
+    }
+
+    #[tokio::test]
+    async fn sha256_head_reports_header() {
+        let app = setup_repo("tests_repo_sha256_head", b"hash me", "sha256:unused").await;
+        let req = |method: &str| {
+            axum::http::Request::builder()
+                .method(method)
+                .uri("/tests_repo_sha256_head/sha256/main/x.bin")
+                .body(Body::empty())
+                .unwrap()
+        };
+        let resp = app.clone().oneshot(req("HEAD")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let header = resp.headers()["X-Sha256"].to_str().unwrap().to_string();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(body.is_empty());
+
+        let resp = app.oneshot(req("GET")).await.unwrap();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(v["sha256"], header.as_str());
+        assert_eq!(header, hex::encode(sha2::Sha256::digest(b"hash me")));
+    }
+
+    #[tokio::test]
+    async fn options_preflight_on_resolve() {
+        let state = AppState::for_tests(PathBuf::from("fake_hub"));
+        let app = Router::new()
+            .route(
+                "/{*rest}",
+                get(resolve_catchall)
+                    .head(resolve_catchall)
+                    .options(resolve_options),
+            )
+            .layer(axum::middleware::from_fn_with_state(
+                state.clone(),
+                crate::middleware::cors_mw,
+            ))
+            .with_state(state.clone());
+        let req = |uri: &str| {
+            axum::http::Request::builder()
+                .method("OPTIONS")
+                .uri(uri)
+                .header("Access-Control-Request-Method", "GET")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        // missing file: same answer as an existing one
+        let resp = app
+            .clone()
+            .oneshot(req("/no_such_repo/resolve/main/missing.bin"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        assert_eq!(resp.headers()["Allow"], "GET, HEAD, OPTIONS");
+        assert_eq!(resp.headers()["Accept-Ranges"], "bytes");
+        assert_eq!(resp.headers()["Access-Control-Allow-Origin"], "*");
+
+        // without the CORS layer the route still answers on its own
+        let mut state = state;
+        state.cors_enabled = false;
+        let app = Router::new()
+            .route("/{*rest}", get(resolve_catchall).options(resolve_options))
+            .with_state(state);
+        let resp = app
+            .oneshot(req("/no_such_repo/resolve/main/missing.bin"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        assert_eq!(resp.headers()["Accept-Ranges"], "bytes");
+    }
+
+    #[tokio::test]
+    async fn delay_and_throttle_only_change_pacing() {
+        let root = dunce::canonicalize("fake_hub").unwrap();
+        let repo_dir = root.join("tests_repo_throttle");
+        tokio::fs::create_dir_all(&repo_dir).await.unwrap();
+        let content: Vec<u8> = (0..2_000u32).map(|i| (i % 251) as u8).collect();
+        tokio::fs::write(repo_dir.join("x.bin"), &content)
+            .await
+            .unwrap();
+        let sc = serde_json::json!({
+            "entries": [{"path": "x.bin", "type": "file", "size": 2_000, "oid": "abcd"}]
+        });
+        tokio::fs::write(repo_dir.join(".paths-info.json"), sc.to_string())
+            .await
+            .unwrap();
+        let mut state = AppState::for_tests(root);
+        state.resolve_delay = Duration::from_millis(100);
+        state.resolve_throttle_bps = 10_000;
+        let app = Router::new()
+            .route("/{*rest}", get(resolve_catchall))
+            .with_state(state);
+
+        for (range, expected) in [
+            (None, &content[..]),
+            (Some("bytes=500-1499"), &content[500..1500]),
+        ] {
+            let mut b =
+                axum::http::Request::builder().uri("/tests_repo_throttle/resolve/main/x.bin");
+            if let Some(r) = range {
+                b = b.header("Range", r);
+            }
+            let started = std::time::Instant::now();
+            let resp = app
+                .clone()
+                .oneshot(b.body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let elapsed = started.elapsed();
+            assert_eq!(&body[..], expected);
+            // delay plus len / bps
+            let floor = Duration::from_millis(100 + expected.len() as u64 * 1000 / 10_000);
+            assert!(
+                elapsed >= floor - Duration::from_millis(5),
+                "{elapsed:?} < {floor:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn fault_injection_fails_before_any_body() {
+        let root = dunce::canonicalize("fake_hub").unwrap();
+        let repo_dir = root.join("tests_repo_fault");
+        tokio::fs::create_dir_all(&repo_dir).await.unwrap();
+        tokio::fs::write(repo_dir.join("x.bin"), b"abc")
+            .await
+            .unwrap();
+        let sc = json!({
+            "entries": [{"path": "x.bin", "type": "file", "size": 3, "oid": "abcd"}]
+        });
+        tokio::fs::write(repo_dir.join(".paths-info.json"), sc.to_string())
+            .await
+            .unwrap();
+        let get_x = |state: AppState| async move {
+            let app = Router::new()
+                .route("/{*rest}", get(resolve_catchall))
+                .with_state(state);
+            let req = axum::http::Request::builder()
+                .uri("/tests_repo_fault/resolve/main/x.bin")
+                .header("Range", "bytes=1-")
+                .body(Body::empty())
+                .unwrap();
+            app.oneshot(req).await.unwrap()
+        };
+
+        let mut state = AppState::for_tests(root);
+        state.fault_inject_rate = 1.0;
+        state.fault_inject_status = StatusCode::INTERNAL_SERVER_ERROR;
+        let resp = get_x(state.clone()).await;
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(resp.headers()["Retry-After"], "1");
+        assert!(resp.headers().get("Content-Range").is_none());
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(v["error"], "injected_fault");
+
+        state.fault_inject_rate = 0.0;
+        let resp = get_x(state).await;
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+
+        let draws: Vec<f64> = (0..1_000).map(|_| fault_draw()).collect();
+        assert!(draws.iter().all(|d| (0.0..1.0).contains(d)));
+        let hits = draws.iter().filter(|d| **d < 0.25).count();
+        assert!((150..350).contains(&hits), "{hits}");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn symlinked_repo_is_served_only_with_allow_symlink_escape() {
+        let root = dunce::canonicalize("fake_hub")
+            .unwrap()
+            .join("tests_resolve_symlink_root");
+        let outside = dunce::canonicalize("fake_hub")
+            .unwrap()
+            .join("tests_resolve_symlink_shared");
+        tokio::fs::create_dir_all(&root).await.unwrap();
+        tokio::fs::create_dir_all(&outside).await.unwrap();
+        tokio::fs::write(outside.join("x.bin"), b"abc")
+            .await
+            .unwrap();
+        let sc = serde_json::json!({
+            "entries": [{"path": "x.bin", "type": "file", "size": 3, "oid": "abcd"}]
+        });
+        tokio::fs::write(outside.join(".paths-info.json"), sc.to_string())
+            .await
+            .unwrap();
+        let link = root.join("linked");
+        let _ = std::fs::remove_file(&link);
+        std::os::unix::fs::symlink(&outside, &link).unwrap();
+        let get_x = |state: AppState| async move {
+            let app = Router::new()
+                .route("/{*rest}", get(resolve_catchall))
+                .with_state(state);
+            let req = axum::http::Request::builder()
+                .uri("/linked/resolve/main/x.bin")
+                .body(Body::empty())
+                .unwrap();
+            app.oneshot(req).await.unwrap()
+        };
+
+        let mut state = AppState::for_tests(root);
+        assert_eq!(get_x(state.clone()).await.status(), StatusCode::FORBIDDEN);
+        state.allow_symlink_escape = true;
+        let resp = get_x(state).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"abc");
     }
 }
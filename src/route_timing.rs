@@ -0,0 +1,133 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tracing::info;
+
+// Per-route latency samples for the periodic p50/p95/max log line.
+// Windows are tumbling: each report drains the samples collected since the previous one.
+
+// Samples kept per bucket and window; older ones are overwritten ring-style past this.
+const MAX_SAMPLES: usize = 4096;
+
+#[derive(Default)]
+struct Window {
+    samples: Vec<u64>,
+    seen: u64,
+}
+
+static WINDOWS: Mutex<BTreeMap<&'static str, Window>> = Mutex::new(BTreeMap::new());
+
+// Map a request path onto a coarse route label.
+pub fn route_bucket(path: &str) -> &'static str {
+    if path.starts_with("/admin/") {
+        "admin"
+    } else if path.starts_with("/api/blake3/") {
+        "blake3"
+    } else if path.contains("/paths-info/") {
+        "paths-info"
+    } else if path.starts_with("/api/models/") {
+        "models"
+    } else if path.starts_with("/api/datasets/") {
+        "datasets"
+    } else if path.starts_with("/api/spaces/") {
+        "spaces"
+    } else if path.contains("/resolve/") || path.contains("/sha256/") {
+        "resolve"
+    } else {
+        "other"
+    }
+}
+
+pub fn record(path: &str, dur_ms: u64) {
+    let Ok(mut map) = WINDOWS.lock() else {
+        return;
+    };
+    let w = map.entry(route_bucket(path)).or_default();
+    if w.samples.len() < MAX_SAMPLES {
+        w.samples.push(dur_ms);
+    } else {
+        let slot = (w.seen % MAX_SAMPLES as u64) as usize;
+        w.samples[slot] = dur_ms;
+    }
+    w.seen += 1;
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Summary {
+    count: u64,
+    p50: u64,
+    p95: u64,
+    max: u64,
+}
+
+// Nearest-rank percentiles over a non-empty window; `samples` is sorted in place.
+fn summarize(samples: &mut [u64], count: u64) -> Summary {
+    samples.sort_unstable();
+    let pct = |p: usize| samples[(samples.len() - 1) * p / 100];
+    Summary {
+        count,
+        p50: pct(50),
+        p95: pct(95),
+        max: samples[samples.len() - 1],
+    }
+}
+
+// Drain the current window into one summary per bucket that saw traffic.
+fn take_summaries() -> Vec<(&'static str, Summary)> {
+    let drained = match WINDOWS.lock() {
+        Ok(mut map) => std::mem::take(&mut *map),
+        Err(_) => return Vec::new(),
+    };
+    drained
+        .into_iter()
+        .filter(|(_, w)| !w.samples.is_empty())
+        .map(|(name, mut w)| (name, summarize(&mut w.samples, w.seen)))
+        .collect()
+}
+
+// Log per-route latency through the `fakehub` target every `every`.
+pub fn spawn_reporter(every: Duration) {
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(every);
+        tick.tick().await;
+        loop {
+            tick.tick().await;
+            for (name, st) in take_summaries() {
+                info!(
+                    target: "fakehub",
+                    "[route-timing] {} n={} p50={}ms p95={}ms max={}ms",
+                    name, st.count, st.p50, st.p95, st.max
+                );
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_by_prefix() {
+        assert_eq!(route_bucket("/org/m/resolve/main/config.json"), "resolve");
+        assert_eq!(
+            route_bucket("/api/models/org/m/paths-info/main"),
+            "paths-info"
+        );
+        assert_eq!(route_bucket("/api/models/org/m"), "models");
+        assert_eq!(route_bucket("/api/datasets/org/d/tree/main"), "datasets");
+        assert_eq!(route_bucket("/api/blake3/org/m"), "blake3");
+        assert_eq!(route_bucket("/admin/ip-log"), "admin");
+        assert_eq!(route_bucket("/api/whoami-v2"), "other");
+    }
+
+    #[test]
+    fn percentiles() {
+        let mut s: Vec<u64> = (1..=100).rev().collect();
+        let st = summarize(&mut s, 100);
+        assert_eq!((st.count, st.p50, st.p95, st.max), (100, 50, 95, 100));
+        let st = summarize(&mut [7], 1);
+        assert_eq!((st.p50, st.p95, st.max), (7, 7, 7));
+    }
+}
@@ -1,15 +1,23 @@
 use std::cmp;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use axum::Json;
 use axum::extract::{Query, State};
-use axum::http::StatusCode;
-use axum::response::IntoResponse;
+use axum::http::HeaderMap;
+use axum::response::{IntoResponse, Response};
 use serde::Deserialize;
 use serde_json::json;
+use sha2::{Digest, Sha256};
+use tracing::warn;
 
 use crate::app_state::AppState;
-use crate::caches::{IP_LOG, IpAccessEntry, prune_ip_bucket};
+use crate::caches::{
+    BLAKE3_CACHE, HUB_STATS_CACHE, HubStatsEntry, IpAccessEntry, PATHS_INFO_CACHE, SHA1_CACHE,
+    SHA256_CACHE, SIBLINGS_CACHE, SIDECAR_CACHE, prune_ip_bucket,
+};
+use crate::error::AppError;
+use crate::utils::sidecar::{find_repo_dirs, sidecar_totals};
 
 #[derive(Deserialize)]
 pub struct IpLogQuery {
@@ -18,14 +26,46 @@ pub struct IpLogQuery {
     pub limit: Option<usize>,
 }
 
+#[derive(Deserialize)]
+pub struct IpLogSummaryQuery {
+    pub limit: Option<usize>,
+    pub active_mins: Option<u64>,
+}
+
+// When ADMIN_TOKEN is configured, admin routes require a matching X-Admin-Token.
+pub(crate) fn admin_token_rejection(state: &AppState, headers: &HeaderMap) -> Option<Response> {
+    let expected = state.admin_token.as_deref()?;
+    let provided = headers.get("x-admin-token").map(|v| v.as_bytes());
+    if provided.is_some_and(|p| tokens_match(p, expected.as_bytes())) {
+        None
+    } else {
+        Some(AppError::Unauthorized("Invalid or missing X-Admin-Token").into_response())
+    }
+}
+
+// Compares SHA-256 digests byte by byte without an early exit, so the time taken says
+// nothing about how much of the token was right (nor, through the digest, its length).
+fn tokens_match(provided: &[u8], expected: &[u8]) -> bool {
+    let a = Sha256::digest(provided);
+    let b = Sha256::digest(expected);
+    a.iter()
+        .zip(b.iter())
+        .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+        == 0
+}
+
 pub async fn get_ip_log(
     State(state): State<AppState>,
+    headers: HeaderMap,
     Query(params): Query<IpLogQuery>,
 ) -> impl IntoResponse {
+    if let Some(resp) = admin_token_rejection(&state, &headers) {
+        return resp;
+    }
     let IpLogQuery { ip, mins, limit } = params;
     let ip = ip.trim().to_string();
     if ip.is_empty() {
-        return crate::http_error(StatusCode::BAD_REQUEST, "ip required");
+        return AppError::BadRequest("ip required").into_response();
     }
 
     let req_window_secs = mins
@@ -50,13 +90,14 @@ pub async fn get_ip_log(
     let mut total = 0usize;
 
     {
-        let mut map = IP_LOG.write().await;
+        let map = &mut state.ip_log.write().await.inner;
         if let Some(bucket) = map.get_mut(ip.as_str()) {
             let retention_ms_u64 = state.ip_log_retention_secs.saturating_mul(1000);
             let retention_ms = cmp::min(retention_ms_u64, i64::MAX as u64) as i64;
             prune_ip_bucket(bucket, now_ms, retention_ms);
-            total = bucket.len();
+            total = bucket.entries.len();
             let mut filtered: Vec<IpAccessEntry> = bucket
+                .entries
                 .iter()
                 .filter(|entry| entry.at_ms >= cutoff)
                 .cloned()
@@ -93,3 +134,530 @@ pub async fn get_ip_log(
     }))
     .into_response()
 }
+
+// Every tracked IP with its in-retention request count, latest at_ms and last path,
+// most recently active first.
+pub async fn get_ip_log_summary(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<IpLogSummaryQuery>,
+) -> impl IntoResponse {
+    if let Some(resp) = admin_token_rejection(&state, &headers) {
+        return resp;
+    }
+    let now_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0);
+    let retention_ms_u64 = state.ip_log_retention_secs.saturating_mul(1000);
+    let retention_ms = cmp::min(retention_ms_u64, i64::MAX as u64) as i64;
+    let active_cutoff = params
+        .active_mins
+        .map(|m| now_ms.saturating_sub(m.saturating_mul(60_000).min(i64::MAX as u64) as i64));
+
+    let mut rows: Vec<(String, usize, i64, String)> = Vec::new();
+    let total_ips;
+    {
+        let map = &mut state.ip_log.write().await.inner;
+        map.retain(|_, bucket| {
+            prune_ip_bucket(bucket, now_ms, retention_ms);
+            !bucket.is_empty()
+        });
+        total_ips = map.len();
+        for (ip, bucket) in map.iter() {
+            let Some(last) = bucket.entries.back() else {
+                continue;
+            };
+            if active_cutoff.is_some_and(|c| last.at_ms < c) {
+                continue;
+            }
+            rows.push((
+                ip.clone(),
+                bucket.entries.len(),
+                last.at_ms,
+                last.path.clone(),
+            ));
+        }
+    }
+    rows.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.0.cmp(&b.0)));
+    if let Some(limit) = params.limit {
+        rows.truncate(limit.max(1));
+    }
+
+    let ips_json: Vec<_> = rows
+        .into_iter()
+        .map(|(ip, count, last_at_ms, last_path)| {
+            json!({
+                "ip": ip,
+                "count": count,
+                "last_at_ms": last_at_ms,
+                "last_path": last_path,
+            })
+        })
+        .collect();
+
+    Json(json!({
+        "window_secs": state.ip_log_retention_secs,
+        "total_ips": total_ips,
+        "returned": ips_json.len(),
+        "ips": ips_json,
+    }))
+    .into_response()
+}
+
+pub async fn get_cache_stats(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if let Some(resp) = admin_token_rejection(&state, &headers) {
+        return resp;
+    }
+    let sidecar_len = SIDECAR_CACHE.read().await.inner.len();
+    let siblings_len = SIBLINGS_CACHE.read().await.inner.len();
+    let paths_info_len = PATHS_INFO_CACHE.read().await.inner.len();
+    let sha256_len = SHA256_CACHE.read().await.inner.len();
+    let sha1_len = SHA1_CACHE.read().await.inner.len();
+    let blake3_len = BLAKE3_CACHE.read().await.inner.len();
+
+    let counters = |name: &str| {
+        crate::metrics::cache_counters()
+            .into_iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, c)| {
+                (
+                    c.hits.load(std::sync::atomic::Ordering::Relaxed),
+                    c.misses.load(std::sync::atomic::Ordering::Relaxed),
+                )
+            })
+            .unwrap_or((0, 0))
+    };
+    let entry = |name: &str, len: usize, cap: Option<usize>, ttl: Duration| {
+        let (hits, misses) = counters(name);
+        json!({
+            "entries": len,
+            "capacity": cap,
+            "ttl_ms": ttl.as_millis() as u64,
+            "hits": hits,
+            "misses": misses,
+        })
+    };
+
+    Json(json!({
+        "cache_ttl_ms": state.cache_ttl.as_millis() as u64,
+        "caches": {
+            "sidecar": entry("sidecar", sidecar_len, Some(state.sidecar_cache_cap), state.sidecar_cache_ttl),
+            "siblings": entry("siblings", siblings_len, Some(state.siblings_cache_cap), state.siblings_cache_ttl),
+            "paths_info": entry("paths_info", paths_info_len, Some(state.paths_info_cache_cap), state.paths_info_cache_ttl),
+            "sha256": entry("sha256", sha256_len, Some(state.sha256_cache_cap), state.sha256_cache_ttl),
+            "sha1": entry("sha1", sha1_len, Some(state.sha256_cache_cap), state.sha256_cache_ttl),
+            "blake3": entry("blake3", blake3_len, Some(state.blake3_cache_cap), state.cache_ttl),
+        },
+    }))
+    .into_response()
+}
+
+#[derive(Deserialize)]
+pub struct MaintenanceBody {
+    pub enabled: bool,
+}
+
+// POST /admin/maintenance: `{"enabled": bool}` sets maintenance mode, an empty body flips it.
+pub async fn post_maintenance(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: axum::body::Bytes,
+) -> Response {
+    if let Some(resp) = admin_token_rejection(&state, &headers) {
+        return resp;
+    }
+    let enabled = if body.is_empty() {
+        !state.maintenance.load(Ordering::Relaxed)
+    } else {
+        match serde_json::from_slice::<MaintenanceBody>(&body) {
+            Ok(b) => b.enabled,
+            Err(_) => return AppError::BadRequest("Expected {\"enabled\": bool}").into_response(),
+        }
+    };
+    state.maintenance.store(enabled, Ordering::Relaxed);
+    warn!(target: "fakehub", "[fake-hub] maintenance mode {}", if enabled { "on" } else { "off" });
+    Json(json!({"maintenance": enabled})).into_response()
+}
+
+// Effective env-derived settings. Tokens are reported only as set/unset, and the root path
+// is hidden under LOG_REDACT.
+pub async fn get_config(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if let Some(resp) = admin_token_rejection(&state, &headers) {
+        return resp;
+    }
+    let ms = |d: Duration| d.as_millis() as u64;
+    let root = if state.log_redact {
+        "<redacted>".to_string()
+    } else {
+        state.root.display().to_string()
+    };
+    Json(json!({
+        "root": root,
+        "logging": {
+            "log_requests": state.log_requests,
+            "log_body_max": state.log_body_max,
+            "log_headers_mode_all": state.log_headers_mode_all,
+            "log_resp_headers": state.log_resp_headers,
+            "log_redact": state.log_redact,
+            "log_body_all": state.log_body_all,
+            "log_json_body": state.log_json_body,
+            "access_log": state.access_log.is_some(),
+        },
+        "ip_log": {
+            "retention_secs": state.ip_log_retention_secs,
+            "per_ip_cap": state.ip_log_per_ip_cap,
+            "max_ips": state.ip_log_max_ips,
+        },
+        "rate_limit": state.rate_limit,
+        "rate_window_secs": state.rate_window_secs,
+        "max_json_body_bytes": state.max_json_body_bytes,
+        "caches": {
+            "cache_ttl_ms": ms(state.cache_ttl),
+            "sidecar_ttl_ms": ms(state.sidecar_cache_ttl),
+            "siblings_ttl_ms": ms(state.siblings_cache_ttl),
+            "paths_info_ttl_ms": ms(state.paths_info_cache_ttl),
+            "sha256_ttl_ms": ms(state.sha256_cache_ttl),
+            "sidecar_cap": state.sidecar_cache_cap,
+            "paths_info_cap": state.paths_info_cache_cap,
+            "siblings_cap": state.siblings_cache_cap,
+            "sha256_cap": state.sha256_cache_cap,
+            "blake3_cap": state.blake3_cache_cap,
+        },
+        "chunk_size": state.chunk_size,
+        "resolve": {
+            "content_disposition": state.resolve_content_disposition,
+            "delay_ms": ms(state.resolve_delay),
+            "throttle_bps": state.resolve_throttle_bps,
+            "cache_max_age": state.resolve_cache_max_age,
+            "fault_inject_rate": state.fault_inject_rate,
+            "fault_inject_status": state.fault_inject_status.as_u16(),
+            "etag_recompute": state.etag_recompute,
+            "etag_fallback_compute": state.etag_fallback_compute,
+        },
+        "allow_writes": state.allow_writes,
+        "lfs_threshold": state.lfs_threshold,
+        "proxy_endpoint": state.proxy_endpoint.as_deref(),
+        "proxy_token_set": state.proxy_token.is_some(),
+        "xet_stub": state.xet_stub,
+        "repo_defaults": {
+            "author": state.repo_defaults.author,
+            "pipeline_tag": state.repo_defaults.pipeline_tag,
+            "library_name": state.repo_defaults.library_name,
+        },
+        "hub_user": state.hub_user.as_str(),
+        "hub_token_set": state.hub_token.is_some(),
+        "cors_enabled": state.cors_enabled,
+        "cors_allow_origin": state.cors_allow_origin.as_str(),
+        "metrics_enabled": state.metrics_enabled,
+        "compression_enabled": state.compression_enabled,
+        "admin_token_set": state.admin_token.is_some(),
+        "maintenance": state.maintenance.load(Ordering::Relaxed),
+    }))
+    .into_response()
+}
+
+// Repo inventory from one walk over the root, reused for `cache_ttl`. Repos whose sidecar
+// cannot be parsed are counted under `errors` and left out of the totals.
+pub async fn get_hub_stats(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if let Some(resp) = admin_token_rejection(&state, &headers) {
+        return resp;
+    }
+    let root = state.root.as_path();
+    if let Some(hit) = HUB_STATS_CACHE.read().await.as_ref()
+        && hit.root == root
+        && hit.at.elapsed() < state.cache_ttl
+    {
+        return Json(hit.stats.clone()).into_response();
+    }
+
+    let (mut models, mut datasets, mut spaces, mut errors) = (0u64, 0u64, 0u64, 0u64);
+    let (mut files, mut used_storage) = (0u64, 0u64);
+    for dir in find_repo_dirs(&state, root).await {
+        match sidecar_totals(&state, &dir).await {
+            Ok((n, bytes)) => {
+                files += n;
+                used_storage += bytes;
+            }
+            Err(e) => {
+                warn!(target: "fakehub", "[admin-stats] {}: unreadable sidecar: {}", dir.display(), e);
+                errors += 1;
+                continue;
+            }
+        }
+        let rel = dir.strip_prefix(root).unwrap_or(&dir);
+        if rel.starts_with("datasets") {
+            datasets += 1;
+        } else if rel.starts_with("spaces") {
+            spaces += 1;
+        } else {
+            models += 1;
+        }
+    }
+    let stats = json!({
+        "models": models,
+        "datasets": datasets,
+        "spaces": spaces,
+        "files": files,
+        "usedStorage": used_storage,
+        "errors": errors,
+    });
+    *HUB_STATS_CACHE.write().await = Some(HubStatsEntry {
+        root: root.to_path_buf(),
+        stats: stats.clone(),
+        at: Instant::now(),
+    });
+    Json(stats).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use tower::util::ServiceExt;
+
+    #[tokio::test]
+    async fn cache_stats_requires_token_when_configured() {
+        let mut state = AppState::for_tests("fake_hub".into());
+        state.admin_token = Some(Arc::new("s3cret".to_string()));
+        state.sidecar_cache_ttl = Duration::from_secs(60);
+        let app = Router::new()
+            .route("/admin/caches/stats", get(get_cache_stats))
+            .with_state(state);
+
+        let req = axum::http::Request::builder()
+            .uri("/admin/caches/stats")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        // A prefix of the token is still wrong
+        let req = axum::http::Request::builder()
+            .uri("/admin/caches/stats")
+            .header("X-Admin-Token", "s3cre")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        let req = axum::http::Request::builder()
+            .uri("/admin/caches/stats")
+            .header("X-Admin-Token", "s3cret")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(v["cache_ttl_ms"], 2000);
+        assert_eq!(v["caches"]["sha256"]["capacity"], 64);
+        assert_eq!(v["caches"]["sidecar"]["capacity"], 64);
+        assert!(v["caches"]["paths_info"]["entries"].is_u64());
+        // Per-cache TTLs fall back to CACHE_TTL_MS unless set individually
+        assert_eq!(v["caches"]["sidecar"]["ttl_ms"], 60_000);
+        assert_eq!(v["caches"]["siblings"]["ttl_ms"], 2000);
+        assert_eq!(v["caches"]["sha1"]["ttl_ms"], 2000);
+    }
+
+    #[tokio::test]
+    async fn config_hides_root_under_redaction_and_never_shows_tokens() {
+        let mut state = AppState::for_tests("fake_hub".into());
+        state.admin_token = Some(Arc::new("s3cret".to_string()));
+        state.hub_token = Some(Arc::new("hf_secret".to_string()));
+        let get_config_json = |state: AppState| async move {
+            let app = Router::new()
+                .route("/admin/config", get(get_config))
+                .with_state(state);
+            let req = axum::http::Request::builder()
+                .uri("/admin/config")
+                .header("X-Admin-Token", "s3cret")
+                .body(Body::empty())
+                .unwrap();
+            let resp = app.oneshot(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            String::from_utf8(body.to_vec()).unwrap()
+        };
+
+        let text = get_config_json(state.clone()).await;
+        let v: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(v["root"], "<redacted>");
+        assert_eq!(v["caches"]["sidecar_cap"], 64);
+        assert_eq!(v["caches"]["sha256_ttl_ms"], 2000);
+        assert_eq!(v["ip_log"]["retention_secs"], 1_800);
+        assert_eq!(v["admin_token_set"], true);
+        assert!(!text.contains("s3cret") && !text.contains("hf_secret"));
+
+        state.log_redact = false;
+        let v: serde_json::Value = serde_json::from_str(&get_config_json(state).await).unwrap();
+        assert_eq!(v["root"], "fake_hub");
+    }
+
+    #[tokio::test]
+    async fn maintenance_toggle_blocks_repo_routes_but_not_admin_or_health() {
+        let state = AppState::for_tests("fake_hub".into());
+        let app = Router::new()
+            .route("/api/models/{*rest}", get(|| async { "model" }))
+            .route("/{*rest}", get(|| async { "file" }))
+            .route("/admin/maintenance", axum::routing::post(post_maintenance))
+            .with_state(state.clone())
+            .layer(axum::middleware::from_fn_with_state(
+                state.clone(),
+                crate::middleware::maintenance_mw,
+            ))
+            .merge(Router::new().route("/healthz", get(|| async { "ok" })));
+        let send = |method: &str, uri: &str, body: &'static str| {
+            let req = axum::http::Request::builder()
+                .method(method)
+                .uri(uri)
+                .body(Body::from(body))
+                .unwrap();
+            app.clone().oneshot(req)
+        };
+
+        // Off: everything is served
+        let resp = send("GET", "/api/models/org/m", "").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let resp = send("POST", "/admin/maintenance", r#"{"enabled": true}"#)
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(state.maintenance.load(Ordering::Relaxed));
+        for uri in ["/api/models/org/m", "/org/m/resolve/main/a.bin"] {
+            let resp = send("GET", uri, "").await.unwrap();
+            assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE, "{uri}");
+            assert_eq!(resp.headers()["Retry-After"], "60");
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(v["error"], "maintenance");
+        }
+        let resp = send("GET", "/healthz", "").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // An empty body flips it back off
+        let resp = send("POST", "/admin/maintenance", "").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = send("GET", "/org/m/resolve/main/a.bin", "").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn hub_stats_counts_repos_and_malformed_sidecars() {
+        let root = PathBuf::from("fake_hub").join("tests_admin_stats_root");
+        let _ = std::fs::remove_dir_all(&root);
+        let sidecar = |files: &[(&str, u64)]| {
+            let entries: Vec<_> = files
+                .iter()
+                .map(|(p, s)| json!({"path": p, "type": "file", "size": s}))
+                .collect();
+            json!({"entries": entries}).to_string()
+        };
+        for (dir, body) in [
+            ("org/model-a", sidecar(&[("a.bin", 10), ("b.txt", 5)])),
+            ("model-b", sidecar(&[("c.bin", 100)])),
+            ("datasets/org/ds", sidecar(&[("d.csv", 7)])),
+            ("datasets/broken", "{not json".to_string()),
+            (
+                "datasets/future",
+                r#"{"version": 99, "entries": []}"#.to_string(),
+            ),
+        ] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+            std::fs::write(root.join(dir).join(".paths-info.json"), body).unwrap();
+        }
+        let mut state = AppState::for_tests(root);
+        state.admin_token = Some(Arc::new("s3cret".to_string()));
+        let app = Router::new()
+            .route("/admin/stats", get(get_hub_stats))
+            .with_state(state);
+
+        let req = axum::http::Request::builder()
+            .uri("/admin/stats")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        let req = axum::http::Request::builder()
+            .uri("/admin/stats")
+            .header("X-Admin-Token", "s3cret")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(v["models"], 2);
+        assert_eq!(v["datasets"], 1);
+        assert_eq!(v["files"], 4);
+        assert_eq!(v["usedStorage"], 122);
+        assert_eq!(v["errors"], 2);
+    }
+
+    #[tokio::test]
+    async fn ip_log_summary_sorted_by_recency() {
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64;
+        let state = AppState::for_tests("fake_hub".into());
+        {
+            let map = &mut state.ip_log.write().await.inner;
+            for (ip, ago_ms, path) in [
+                ("198.51.100.201", 5_000, "/old"),
+                ("198.51.100.202", 1_000, "/new"),
+                ("198.51.100.203", 10 * 60_000, "/stale"),
+            ] {
+                let bucket = &mut map.entry(ip.to_string()).or_default().entries;
+                bucket.clear();
+                bucket.push_back(IpAccessEntry {
+                    at_ms: now_ms - ago_ms - 1_000,
+                    method: "GET".to_string(),
+                    path: "/first".to_string(),
+                    status: 200,
+                });
+                bucket.push_back(IpAccessEntry {
+                    at_ms: now_ms - ago_ms,
+                    method: "GET".to_string(),
+                    path: path.to_string(),
+                    status: 200,
+                });
+            }
+        }
+        let app = Router::new()
+            .route("/admin/ip-log/summary", get(get_ip_log_summary))
+            .with_state(state);
+
+        let req = axum::http::Request::builder()
+            .uri("/admin/ip-log/summary?active_mins=2")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let ips = v["ips"].as_array().unwrap();
+        assert_eq!(ips.len(), 2);
+        assert_eq!(ips[0]["ip"], "198.51.100.202");
+        assert_eq!(ips[0]["last_path"], "/new");
+        assert_eq!(ips[0]["count"], 2);
+        assert_eq!(ips[1]["ip"], "198.51.100.201");
+    }
+}
@@ -0,0 +1,389 @@
+use std::path::PathBuf;
+
+use axum::Json;
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::response::IntoResponse;
+use serde_json::json;
+
+use crate::app_state::AppState;
+use crate::error::AppError;
+use crate::utils::paths::secure_join;
+use crate::utils::repo_json::{RepoKind, RepoMeta, read_repo_meta};
+use crate::utils::sidecar::is_repo_dir;
+
+// GET /api/whoami-v2: a valid Bearer token (see `request_auth`) is mapped to the
+// configured hub user; the body mirrors the real Hub shape.
+pub(crate) async fn get_whoami_v2(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if request_auth(&state, &headers) != Auth::Valid {
+        return AppError::Unauthorized("Invalid credentials in Authorization header")
+            .into_response();
+    }
+    let name = state.hub_user.as_str();
+    Json(json!({
+        "type": "user",
+        "id": format!("local/{}", name),
+        "name": name,
+        "fullname": name,
+        "email": format!("{}@localhost", name),
+        "emailVerified": true,
+        "canPay": false,
+        "isPro": false,
+        "periodEnd": null,
+        "avatarUrl": "",
+        "orgs": [],
+        "auth": {
+            "type": "access_token",
+            "accessToken": {
+                "displayName": "local-token",
+                "role": "write",
+                "createdAt": "1970-01-01T00:00:00.000Z",
+            },
+        },
+    }))
+    .into_response()
+}
+
+// Extract the token from `Authorization: Bearer <token>`; None when absent or empty.
+pub(crate) fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    let val = headers.get("authorization")?.to_str().ok()?.trim();
+    let (scheme, token) = val.split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("bearer") {
+        return None;
+    }
+    let token = token.trim();
+    if token.is_empty() {
+        None
+    } else {
+        Some(token.to_string())
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Auth {
+    Anonymous,
+    Invalid,
+    Valid,
+}
+
+// With FAKE_HUB_TOKEN set only that token is valid; otherwise any non-empty Bearer token is.
+pub(crate) fn request_auth(state: &AppState, headers: &HeaderMap) -> Auth {
+    match (bearer_token(headers), state.hub_token.as_deref()) {
+        (None, _) => Auth::Anonymous,
+        (Some(tok), Some(expected)) if tok != *expected => Auth::Invalid,
+        (Some(_), _) => Auth::Valid,
+    }
+}
+
+// Per-repo access from `.repo-meta.json`: private repos are a plain 404 without a valid
+// token; gated repos answer 401 to anonymous requests and 403 to a wrong token, like the
+// Hub's GatedRepo errors.
+pub(crate) fn check_repo_access(
+    meta: &RepoMeta,
+    kind: RepoKind,
+    repo_id: &str,
+    auth: Auth,
+) -> Result<(), AppError> {
+    if meta.is_private() && auth != Auth::Valid {
+        return Err(AppError::RepoNotFound(kind));
+    }
+    if meta.is_gated() && auth != Auth::Valid {
+        return Err(AppError::GatedRepo {
+            kind,
+            repo_id: repo_id.to_string(),
+            authenticated: auth == Auth::Invalid,
+        });
+    }
+    Ok(())
+}
+
+// Directory the repos of a kind live under.
+pub(crate) fn repo_base(state: &AppState, kind: RepoKind) -> PathBuf {
+    match kind {
+        RepoKind::Model => state.root.to_path_buf(),
+        RepoKind::Dataset => state.root.join("datasets"),
+        RepoKind::Space => state.root.join("spaces"),
+    }
+}
+
+// Whether `repo_id` names an actual repo of `kind`; used to anchor the route markers
+// (`tree`, `file-metadata`, ...) so a repo whose own name contains one is not split on it.
+pub(crate) fn repo_exists(state: &AppState, kind: RepoKind, repo_id: &str) -> bool {
+    secure_join(&repo_base(state, kind), repo_id, state.allow_symlink_escape)
+        .is_some_and(|d| is_repo_dir(state, &d))
+}
+
+// Directory of a repo under its kind's base, handed out only once `.repo-meta.json` allows
+// the request; every per-repo route goes through here so none of them reveals a private or
+// gated repo that the info endpoint would refuse.
+pub(crate) async fn accessible_repo_path(
+    state: &AppState,
+    kind: RepoKind,
+    repo_id: &str,
+    auth: Auth,
+) -> Result<PathBuf, AppError> {
+    accessible_repo(state, kind, repo_id, auth)
+        .await
+        .map(|(repo_path, _)| repo_path)
+}
+
+// `accessible_repo_path` plus the meta it checked, for the info endpoints that render it.
+pub(crate) async fn accessible_repo(
+    state: &AppState,
+    kind: RepoKind,
+    repo_id: &str,
+    auth: Auth,
+) -> Result<(PathBuf, RepoMeta), AppError> {
+    let Some(repo_path) = secure_join(&repo_base(state, kind), repo_id, state.allow_symlink_escape)
+        .filter(|p| p.is_dir())
+    else {
+        return Err(AppError::RepoNotFound(kind));
+    };
+    let Some(meta) = read_repo_meta(&repo_path).await else {
+        return Err(AppError::SidecarMalformed("Repo meta sidecar malformed"));
+    };
+    check_repo_access(&meta, kind, repo_id, auth)?;
+    Ok((repo_path, meta))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use tower::util::ServiceExt;
+
+    fn app() -> Router {
+        Router::new()
+            .route("/api/whoami-v2", get(get_whoami_v2))
+            .with_state(AppState::for_tests("fake_hub".into()))
+    }
+
+    #[tokio::test]
+    async fn whoami_with_token() {
+        let req = axum::http::Request::builder()
+            .uri("/api/whoami-v2")
+            .header("Authorization", "Bearer hf_testtoken123")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(v["name"], "local-user");
+        assert_eq!(v["type"], "user");
+        assert_eq!(v["auth"]["accessToken"]["role"], "write");
+        assert!(v["email"].is_string());
+    }
+
+    #[tokio::test]
+    async fn whoami_without_token() {
+        let req = axum::http::Request::builder()
+            .uri("/api/whoami-v2")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(v["detail"].is_string());
+
+        let req = axum::http::Request::builder()
+            .uri("/api/whoami-v2")
+            .header("Authorization", "Basic abc")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn gated_repo_requires_matching_token() {
+        let root = dunce::canonicalize("fake_hub").unwrap();
+        let repo_dir = root.join("tests_repo_gated");
+        tokio::fs::create_dir_all(&repo_dir).await.unwrap();
+        tokio::fs::write(repo_dir.join("x.bin"), b"x")
+            .await
+            .unwrap();
+        let sc = json!({"entries": [{"path": "x.bin", "type": "file", "size": 1, "oid": "abcd"}]});
+        tokio::fs::write(repo_dir.join(".paths-info.json"), sc.to_string())
+            .await
+            .unwrap();
+        tokio::fs::write(repo_dir.join(".repo-meta.json"), r#"{"gated": true}"#)
+            .await
+            .unwrap();
+        let mut state = AppState::for_tests(root);
+        state.hub_token = Some(std::sync::Arc::new("hf_secret".to_string()));
+        let app = Router::new()
+            .route(
+                "/api/models/{*rest}",
+                get(crate::routes_models::get_model_catchall_get),
+            )
+            .route(
+                "/{*rest}",
+                get(crate::resolve::resolve_catchall).head(crate::resolve::resolve_catchall),
+            )
+            .with_state(state);
+        let send = |method: &str, uri: &str, token: Option<&str>| {
+            let mut b = axum::http::Request::builder().method(method).uri(uri);
+            if let Some(t) = token {
+                b = b.header("Authorization", format!("Bearer {t}"));
+            }
+            app.clone().oneshot(b.body(Body::empty()).unwrap())
+        };
+
+        for (method, uri) in [
+            ("GET", "/api/models/tests_repo_gated"),
+            ("HEAD", "/tests_repo_gated/resolve/main/x.bin"),
+        ] {
+            let resp = send(method, uri, None).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::UNAUTHORIZED, "{uri}");
+            assert_eq!(resp.headers()["X-Error-Code"], "GatedRepo");
+            let resp = send(method, uri, Some("hf_wrong")).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::FORBIDDEN, "{uri}");
+            assert_eq!(resp.headers()["X-Error-Code"], "GatedRepo");
+            let resp = send(method, uri, Some("hf_secret")).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK, "{uri}");
+        }
+
+        let resp = send("GET", "/api/models/tests_repo_gated", Some("hf_secret"))
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(v["gated"], "auto");
+
+        let resp = send("GET", "/api/models/tests_repo_gated", None)
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(v["error"], "gated_repo");
+    }
+
+    #[tokio::test]
+    async fn private_repo_is_hidden_without_token() {
+        let root = dunce::canonicalize("fake_hub").unwrap();
+        let ds_dir = root.join("datasets").join("tests_ds_private");
+        tokio::fs::create_dir_all(&ds_dir).await.unwrap();
+        tokio::fs::write(ds_dir.join("x.csv"), b"x").await.unwrap();
+        let sc = json!({"entries": [{"path": "x.csv", "type": "file", "size": 1, "oid": "abcd"}]});
+        tokio::fs::write(ds_dir.join(".paths-info.json"), sc.to_string())
+            .await
+            .unwrap();
+        tokio::fs::write(ds_dir.join(".repo-meta.json"), r#"{"private": true}"#)
+            .await
+            .unwrap();
+        let mut state = AppState::for_tests(root);
+        state.hub_token = Some(std::sync::Arc::new("hf_secret".to_string()));
+        let app = Router::new()
+            .route(
+                "/api/datasets/{*rest}",
+                get(crate::routes_datasets::get_dataset_catchall_get),
+            )
+            .route("/{*rest}", get(crate::resolve::resolve_catchall))
+            .with_state(state);
+        let send = |uri: &str, token: Option<&str>| {
+            let mut b = axum::http::Request::builder().uri(uri);
+            if let Some(t) = token {
+                b = b.header("Authorization", format!("Bearer {t}"));
+            }
+            app.clone().oneshot(b.body(Body::empty()).unwrap())
+        };
+
+        for uri in [
+            "/api/datasets/tests_ds_private",
+            "/datasets/tests_ds_private/resolve/main/x.csv",
+        ] {
+            for token in [None, Some("hf_wrong")] {
+                let resp = send(uri, token).await.unwrap();
+                assert_eq!(resp.status(), StatusCode::NOT_FOUND, "{uri}");
+                let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                assert_eq!(v["error"], "repo_not_found");
+            }
+            let resp = send(uri, Some("hf_secret")).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK, "{uri}");
+        }
+        let resp = send("/api/datasets/tests_ds_private", Some("hf_secret"))
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(v["private"], true);
+    }
+
+    #[tokio::test]
+    async fn private_repo_hidden_from_every_per_repo_route() {
+        let root = dunce::canonicalize("fake_hub").unwrap();
+        let repo_dir = root.join("tests_repo_private_routes");
+        tokio::fs::create_dir_all(&repo_dir).await.unwrap();
+        tokio::fs::write(repo_dir.join("x.bin"), b"x")
+            .await
+            .unwrap();
+        let sc = json!({"entries": [{"path": "x.bin", "type": "file", "size": 1, "oid": "abcd"}]});
+        tokio::fs::write(repo_dir.join(".paths-info.json"), sc.to_string())
+            .await
+            .unwrap();
+        tokio::fs::write(repo_dir.join(".repo-meta.json"), r#"{"private": true}"#)
+            .await
+            .unwrap();
+        let app = Router::new()
+            .route(
+                "/api/models/{*rest}",
+                get(crate::routes_models::get_model_catchall_get)
+                    .post(crate::routes_models::get_model_paths_info_post),
+            )
+            .route(
+                "/api/blake3/{*repo}",
+                get(crate::routes_blake3::get_repo_blake3),
+            )
+            .route("/{*rest}", get(crate::resolve::resolve_catchall))
+            .with_state(AppState::for_tests(root));
+        let send = |method: &str, uri: &str, token: Option<&str>| {
+            let mut b = axum::http::Request::builder().method(method).uri(uri);
+            if let Some(t) = token {
+                b = b.header("Authorization", format!("Bearer {t}"));
+            }
+            app.clone().oneshot(b.body(Body::empty()).unwrap())
+        };
+
+        for (method, uri) in [
+            ("GET", "/api/models/tests_repo_private_routes/tree/main"),
+            ("GET", "/api/models/tests_repo_private_routes/refs"),
+            (
+                "POST",
+                "/api/models/tests_repo_private_routes/paths-info/main",
+            ),
+            ("GET", "/api/blake3/tests_repo_private_routes"),
+            ("GET", "/tests_repo_private_routes/sha256/main/x.bin"),
+        ] {
+            let resp = send(method, uri, None).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND, "{uri}");
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(v["error"], "repo_not_found", "{uri}");
+            let resp = send(method, uri, Some("hf_any")).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK, "{uri}");
+        }
+    }
+}
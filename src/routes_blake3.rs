@@ -1,66 +1,99 @@
 use std::collections::BTreeMap;
-use std::io;
-use std::path::{Path, PathBuf};
+use std::io::{self, Read};
+use std::path::Path;
+use std::time::{Instant, UNIX_EPOCH};
 
 use axum::Json;
 use axum::extract::{Path as AxPath, State};
-use axum::http::StatusCode;
+use axum::http::HeaderMap;
 use axum::response::IntoResponse;
-use tokio::io::AsyncReadExt;
+use rayon::prelude::*;
 use tracing::warn;
 
-use crate::CHUNK_SIZE;
 use crate::app_state::AppState;
-use crate::http_error;
-use crate::http_not_found;
-use crate::utils::paths::{normalize_rel, secure_join};
-use crate::utils::sidecar::get_sidecar_map;
+use crate::caches::{BLAKE3_CACHE, Blake3Entry};
+use crate::error::AppError;
+use crate::routes_auth::{accessible_repo_path, request_auth};
+use crate::utils::paths::{JoinError, normalize_rel, secure_join, try_secure_join};
+use crate::utils::repo_json::RepoKind;
+use crate::utils::sidecar::{find_sidecar, get_sidecar_map};
 
 pub(crate) async fn get_repo_blake3(
     State(state): State<AppState>,
     AxPath(repo): AxPath<String>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
     let repo_id = repo.trim_matches('/');
     if repo_id.is_empty() {
-        return http_not_found("Repository not found");
+        return AppError::RepoNotFound(RepoKind::Model).into_response();
     }
 
-    let Some(repo_path) = resolve_repo_path(&state, repo_id).await else {
-        return http_not_found("Repository not found");
+    let kind = match resolve_repo_kind(&state, repo_id).await {
+        Ok(Some(k)) => k,
+        Ok(None) => return AppError::RepoNotFound(RepoKind::Model).into_response(),
+        Err(JoinError::Escapes) => return AppError::PathForbidden.into_response(),
+        Err(JoinError::BaseMissing) => {
+            return AppError::RepoNotFound(RepoKind::Model).into_response();
+        }
+    };
+    let auth = request_auth(&state, &headers);
+    let repo_path = match accessible_repo_path(&state, kind, repo_id, auth).await {
+        Ok(p) => p,
+        Err(e) => return e.into_response(),
     };
 
-    let sc_path = repo_path.join(".paths-info.json");
-    if !sc_path.is_file() {
-        return http_error(
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Sidecar missing or incomplete",
-        );
+    if find_sidecar(&state, &repo_path).is_none() {
+        return AppError::SidecarMissing("Sidecar missing or incomplete").into_response();
     }
 
-    let sc_map = match get_sidecar_map(&repo_path).await {
+    let sc_map = match get_sidecar_map(&state, &repo_path).await {
         Ok(map) => map,
         Err(err) => {
             warn!(target: "fakehub", "load sidecar failed: {}", err);
-            return http_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to read sidecar");
+            return AppError::SidecarMalformed("Failed to read sidecar").into_response();
         }
     };
 
     let mut out: BTreeMap<String, String> = BTreeMap::new();
+    let mut pending: Vec<String> = Vec::new();
     for (rel, entry) in sc_map.iter() {
         if let Some(hash) = entry.get("blake3").and_then(|v| v.as_str()) {
             out.insert(rel.clone(), hash.to_string());
-            continue;
+        } else {
+            pending.push(rel.clone());
         }
-        match compute_blake3(&repo_path, rel).await {
-            Ok(hash) => {
-                out.insert(rel.clone(), hash);
-            }
+    }
+
+    // Hash the remaining files in parallel on rayon, off the async runtime.
+    if !pending.is_empty() {
+        let base = repo_path.clone();
+        let st = state.clone();
+        let results = tokio::task::spawn_blocking(move || {
+            pending
+                .into_par_iter()
+                .map(|rel| {
+                    let res = compute_blake3(&st, &base, &rel);
+                    (rel, res)
+                })
+                .collect::<Vec<_>>()
+        })
+        .await;
+        let results = match results {
+            Ok(r) => r,
             Err(err) => {
-                warn!(target: "fakehub", "compute blake3 failed for {}: {}", rel, err);
-                return http_error(
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "Failed to compute BLAKE3",
-                );
+                warn!(target: "fakehub", "blake3 hashing task failed: {}", err);
+                return AppError::HashFailed("Failed to compute BLAKE3").into_response();
+            }
+        };
+        for (rel, res) in results {
+            match res {
+                Ok(hash) => {
+                    out.insert(rel, hash);
+                }
+                Err(err) => {
+                    warn!(target: "fakehub", "compute blake3 failed for {}: {}", rel, err);
+                    return AppError::HashFailed("Failed to compute BLAKE3").into_response();
+                }
             }
         }
     }
@@ -68,21 +101,22 @@ pub(crate) async fn get_repo_blake3(
     Json(out).into_response()
 }
 
-async fn resolve_repo_path(state: &AppState, repo_id: &str) -> Option<PathBuf> {
+// Whether the id names a model or, failing that, a dataset directory; Ok(None) when neither
+// exists, Err when it escapes root.
+async fn resolve_repo_kind(state: &AppState, repo_id: &str) -> Result<Option<RepoKind>, JoinError> {
     let base = state.root.as_ref();
-    if let Some(candidate) = secure_join(base, repo_id) {
-        if dir_exists(&candidate).await {
-            return Some(candidate);
-        }
+    let candidate = try_secure_join(base, repo_id, state.allow_symlink_escape)?;
+    if dir_exists(&candidate).await {
+        return Ok(Some(RepoKind::Model));
     }
 
     let dataset_base = base.join("datasets");
-    if let Some(candidate) = secure_join(&dataset_base, repo_id) {
-        if dir_exists(&candidate).await {
-            return Some(candidate);
-        }
+    if let Some(candidate) = secure_join(&dataset_base, repo_id, state.allow_symlink_escape)
+        && dir_exists(&candidate).await
+    {
+        return Ok(Some(RepoKind::Dataset));
     }
-    None
+    Ok(None)
 }
 
 async fn dir_exists(p: &Path) -> bool {
@@ -92,7 +126,8 @@ async fn dir_exists(p: &Path) -> bool {
         .unwrap_or(false)
 }
 
-async fn compute_blake3(base: &Path, rel: &str) -> Result<String, io::Error> {
+// Runs on rayon worker threads, so the cache is accessed with blocking locks.
+fn compute_blake3(state: &AppState, base: &Path, rel: &str) -> Result<String, io::Error> {
     let rel_norm = normalize_rel(rel)
         .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid path"))?;
     let full = base.join(&rel_norm);
@@ -102,15 +137,163 @@ async fn compute_blake3(base: &Path, rel: &str) -> Result<String, io::Error> {
             "path escapes repository",
         ));
     }
-    let mut file = tokio::fs::File::open(full).await?;
+    let md = std::fs::metadata(&full)?;
+    let size = md.len();
+    let mtime = md
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let key = (full.clone(), mtime, size);
+    if let Some(hit) = {
+        let cache = BLAKE3_CACHE.blocking_read();
+        cache.inner.get(&key).cloned()
+    } && Instant::now().duration_since(hit.at) < state.cache_ttl
+    {
+        crate::metrics::BLAKE3_CACHE.hit();
+        let fresh = Instant::now();
+        let mut cachew = BLAKE3_CACHE.blocking_write();
+        if let Some(entry) = cachew.inner.get_mut(&key) {
+            entry.at = fresh;
+        }
+        cachew.evict_q.push_back((key, fresh));
+        return Ok(hit.sum);
+    }
+    crate::metrics::BLAKE3_CACHE.miss();
+
+    let mut file = std::fs::File::open(&full)?;
     let mut hasher = blake3::Hasher::new();
-    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut buf = vec![0u8; state.chunk_size];
     loop {
-        let n = file.read(&mut buf).await?;
+        let n = file.read(&mut buf)?;
         if n == 0 {
             break;
         }
         hasher.update(&buf[..n]);
     }
-    Ok(hasher.finalize().to_hex().to_string())
+    let sum = hasher.finalize().to_hex().to_string();
+    {
+        let mut cache = BLAKE3_CACHE.blocking_write();
+        if cache.inner.len() >= state.blake3_cache_cap {
+            while let Some((old_k, old_at)) = cache.evict_q.pop_front() {
+                if let Some(entry) = cache.inner.get(&old_k)
+                    && entry.at == old_at
+                {
+                    cache.inner.remove(&old_k);
+                    break;
+                }
+            }
+        }
+        let now_i = Instant::now();
+        cache.evict_q.push_back((key.clone(), now_i));
+        cache.inner.insert(
+            key,
+            Blake3Entry {
+                sum: sum.clone(),
+                at: now_i,
+            },
+        );
+    }
+    Ok(sum)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use std::path::PathBuf;
+    use tower::util::ServiceExt;
+
+    #[tokio::test]
+    async fn hashes_missing_entries_and_keeps_sidecar_values() {
+        let root = PathBuf::from("fake_hub");
+        let repo_dir = root.join("tests_repo_blake3");
+        tokio::fs::create_dir_all(repo_dir.join("sub"))
+            .await
+            .unwrap();
+        tokio::fs::write(repo_dir.join("b.txt"), b"bbb")
+            .await
+            .unwrap();
+        tokio::fs::write(repo_dir.join("sub/a.txt"), b"aaa")
+            .await
+            .unwrap();
+        tokio::fs::write(repo_dir.join("c.txt"), b"ccc")
+            .await
+            .unwrap();
+        let sc = serde_json::json!({"entries": [
+            {"path": "b.txt", "type": "file", "size": 3},
+            {"path": "sub/a.txt", "type": "file", "size": 3},
+            {"path": "c.txt", "type": "file", "size": 3, "blake3": "cached"},
+        ]});
+        tokio::fs::write(repo_dir.join(".paths-info.json"), sc.to_string())
+            .await
+            .unwrap();
+        let app = Router::new()
+            .route("/api/blake3/{*repo}", get(get_repo_blake3))
+            .with_state(AppState::for_tests(root));
+
+        let req = axum::http::Request::builder()
+            .uri("/api/blake3/tests_repo_blake3")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let keys: Vec<&String> = v.as_object().unwrap().keys().collect();
+        assert_eq!(keys, ["b.txt", "c.txt", "sub/a.txt"]);
+        assert_eq!(v["c.txt"], "cached");
+        assert_eq!(v["b.txt"], blake3::hash(b"bbb").to_hex().to_string());
+        assert_eq!(v["sub/a.txt"], blake3::hash(b"aaa").to_hex().to_string());
+    }
+
+    #[test]
+    fn compute_blake3_served_from_cache_within_ttl() {
+        let repo_dir = PathBuf::from("fake_hub").join("tests_repo_blake3_cache");
+        std::fs::create_dir_all(&repo_dir).unwrap();
+        std::fs::write(repo_dir.join("x.bin"), b"cached-bytes").unwrap();
+        let base = std::fs::canonicalize(&repo_dir).unwrap();
+        let state = AppState::for_tests(PathBuf::from("fake_hub"));
+
+        let first = compute_blake3(&state, &base, "x.bin").unwrap();
+        assert_eq!(first, blake3::hash(b"cached-bytes").to_hex().to_string());
+
+        // Swap the content but keep size and mtime, so only a re-read would notice.
+        let path = base.join("x.bin");
+        let mtime = std::fs::metadata(&path).unwrap().modified().unwrap();
+        std::fs::write(&path, b"CACHED-BYTES").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+        let second = compute_blake3(&state, &base, "x.bin").unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[tokio::test]
+    async fn escaping_repo_id_is_forbidden() {
+        let app = Router::new()
+            .route("/api/blake3/{*repo}", get(get_repo_blake3))
+            .with_state(AppState::for_tests(PathBuf::from("fake_hub")));
+        let req = axum::http::Request::builder()
+            .uri("/api/blake3/../../etc")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let req = axum::http::Request::builder()
+            .uri("/api/blake3/tests_no_such_repo")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
 }
@@ -1,56 +1,19 @@
-use std::time::Instant;
-
-use axum::Json;
-use axum::extract::{Path as AxPath, Request as AxRequest, State};
-use axum::http::StatusCode;
+use axum::extract::{Path as AxPath, Query, Request as AxRequest, State};
+use axum::http::HeaderMap;
 use axum::response::IntoResponse;
-use serde_json::Value;
 
+use crate::RepoApiQuery;
 use crate::app_state::AppState;
-use crate::caches::{SIBLINGS_CACHE, SiblingsEntry};
-use crate::utils::paths::secure_join;
-use crate::utils::repo_json::{RepoJsonFlavor, RepoKind, build_repo_json};
-use crate::{http_error, http_not_found, paths_info_response};
+use crate::routes_repo_api::{repo_catchall_get, repo_paths_info_post};
+use crate::utils::repo_json::RepoKind;
 
 pub(crate) async fn get_dataset_catchall_get(
     State(state): State<AppState>,
     AxPath(rest): AxPath<String>,
+    Query(query): Query<RepoApiQuery>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
-    // rest can be "{repo_id}" or "{repo_id}/revision/{revision}"
-    let parts: Vec<&str> = rest.split('/').collect();
-    // Support tree listing: /api/datasets/{repo_id}/tree/{revision}
-    if parts.len() >= 3 && parts[parts.len() - 2] == "tree" {
-        let _revision = parts.last().unwrap_or(&"");
-        let repo_id = parts[..parts.len() - 2].join("/");
-        let ds_base = state.root.join("datasets");
-        let Some(ds_path) = secure_join(&ds_base, &repo_id) else {
-            return http_not_found("Dataset not found");
-        };
-        if !ds_path.is_dir() {
-            return http_not_found("Dataset not found");
-        }
-        if let Some(vals) = crate::utils::fs_walk::collect_paths_info_from_sidecar(&ds_path).await {
-            return Json(vals).into_response();
-        }
-        return http_error(
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Sidecar missing or incomplete",
-        );
-    }
-    if parts.len() >= 3 && parts[parts.len() - 2] == "revision" {
-        let revision = parts.last().unwrap_or(&"");
-        let repo_id = parts[..parts.len() - 2].join("/");
-        match build_dataset_response(&state, &repo_id, Some(revision)).await {
-            Ok(val) => Json(val).into_response(),
-            Err(e) => e,
-        }
-    } else {
-        let repo_id = rest;
-        match build_dataset_response(&state, &repo_id, None).await {
-            Ok(val) => Json(val).into_response(),
-            Err(e) => e,
-        }
-    }
+    repo_catchall_get(state, RepoKind::Dataset, rest, query, headers).await
 }
 
 pub(crate) async fn get_dataset_paths_info_post(
@@ -58,104 +21,139 @@ pub(crate) async fn get_dataset_paths_info_post(
     AxPath(rest): AxPath<String>,
     req: AxRequest,
 ) -> impl IntoResponse {
-    // expect "{repo_id}/paths-info/{revision}"
-    let parts: Vec<&str> = rest.split('/').collect();
-    if parts.len() >= 3 && parts[parts.len() - 2] == "paths-info" {
-        let _revision = parts.last().unwrap_or(&"");
-        let repo_id = parts[..parts.len() - 2].join("/");
-        let ds_base = state.root.join("datasets");
-        let Some(ds_path) = secure_join(&ds_base, &repo_id) else {
-            return http_not_found("Dataset not found");
-        };
-        if !ds_path.is_dir() {
-            return http_not_found("Dataset not found");
-        }
-        match paths_info_response(&state, &ds_path, req).await {
-            Ok(vals) => Json(vals).into_response(),
-            Err(e) => e,
-        }
-    } else {
-        http_not_found("Not Found")
-    }
+    repo_paths_info_post(state, RepoKind::Dataset, rest, req).await
 }
 
-async fn build_dataset_response(
-    state: &AppState,
-    repo_id: &str,
-    revision: Option<&str>,
-) -> Result<Value, axum::response::Response> {
-    let ds_base = state.root.join("datasets");
-    let Some(ds_path) = secure_join(&ds_base, repo_id) else {
-        return Err(http_not_found("Dataset not found"));
-    };
-    if !ds_path.is_dir() {
-        return Err(http_not_found("Dataset not found"));
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use serde_json::Value;
+    use tower::util::ServiceExt;
+
+    #[tokio::test]
+    async fn dataset_keys_match_across_cache_hit() {
+        let root = dunce::canonicalize("fake_hub").unwrap();
+        let ds_dir = root.join("datasets").join("tests_ds_cache_shape");
+        tokio::fs::create_dir_all(&ds_dir).await.unwrap();
+        let sc = serde_json::json!({"entries": [
+            {"path": "train.csv", "type": "file", "size": 3, "oid": "t"},
+        ]});
+        tokio::fs::write(ds_dir.join(".paths-info.json"), sc.to_string())
+            .await
+            .unwrap();
+        let app = Router::new()
+            .route("/api/datasets/{*rest}", get(get_dataset_catchall_get))
+            .with_state(AppState::for_tests(root));
+
+        // miss, then hit within the TTL
+        let mut key_sets = Vec::new();
+        for _ in 0..2 {
+            let req = axum::http::Request::builder()
+                .uri("/api/datasets/tests_ds_cache_shape")
+                .body(Body::empty())
+                .unwrap();
+            let resp = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let v: Value = serde_json::from_slice(&body).unwrap();
+            let mut keys: Vec<String> = v.as_object().unwrap().keys().cloned().collect();
+            keys.sort();
+            key_sets.push(keys);
+        }
+        assert_eq!(key_sets[0], key_sets[1]);
     }
-    // ds_path is canonical from secure_join; avoid redundant canonicalize
-    let cache_key = format!("dataset:{}", ds_path.display());
-    let now = Instant::now();
-    if let Some(hit) = {
-        let cache = SIBLINGS_CACHE.read().await;
-        cache.inner.get(&cache_key).cloned()
-    } {
-        if now.duration_since(hit.at) < state.cache_ttl {
-            let fresh = Instant::now();
-            let mut cachew = SIBLINGS_CACHE.write().await;
-            if let Some(entry) = cachew.inner.get_mut(&cache_key) {
-                entry.at = fresh;
-                cachew.evict_q.push_back((cache_key.clone(), fresh));
+
+    #[tokio::test]
+    async fn tree_expand_false_returns_slim_records() {
+        let root = dunce::canonicalize("fake_hub").unwrap();
+        let ds_dir = root.join("datasets").join("tests_ds_tree_expand");
+        tokio::fs::create_dir_all(&ds_dir).await.unwrap();
+        let sc = serde_json::json!({"entries": [
+            {"path": "w.bin", "type": "file", "size": 4, "oid": "o",
+             "lfs": {"oid": "sha256:ab", "size": 4}},
+        ]});
+        tokio::fs::write(ds_dir.join(".paths-info.json"), sc.to_string())
+            .await
+            .unwrap();
+        let app = Router::new()
+            .route("/api/datasets/{*rest}", get(get_dataset_catchall_get))
+            .with_state(AppState::for_tests(root));
+
+        for (query, full) in [("", true), ("?expand=1", true), ("?expand=false", false)] {
+            let req = axum::http::Request::builder()
+                .uri(format!(
+                    "/api/datasets/tests_ds_tree_expand/tree/main{query}"
+                ))
+                .body(Body::empty())
+                .unwrap();
+            let resp = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let v: Vec<Value> = serde_json::from_slice(&body).unwrap();
+            let keys: Vec<&String> = v[0].as_object().unwrap().keys().collect();
+            if full {
+                assert_eq!(v[0]["lfs"]["oid"], "sha256:ab", "{query}");
+                assert_eq!(v[0]["oid"], "o");
+            } else {
+                assert_eq!(keys, ["path", "size", "type"], "{query}");
             }
-            let val = build_repo_json(
-                RepoKind::Dataset,
-                repo_id,
-                revision,
-                &hit.siblings,
-                hit.total,
-                RepoJsonFlavor::Minimal,
-            );
-            return Ok(val);
         }
     }
 
-    let (siblings, total_size): (Vec<Value>, u64) =
-        if let Some((s, t)) = crate::utils::fs_walk::siblings_from_sidecar(&ds_path).await {
-            (s, t)
-        } else {
-            return Err(http_error(
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Sidecar missing or incomplete",
-            ));
+    #[tokio::test]
+    async fn tree_subpath_scopes_and_refuses_escapes() {
+        let root = dunce::canonicalize("fake_hub").unwrap();
+        let ds_dir = root.join("datasets").join("tests_ds_tree_subpath");
+        tokio::fs::create_dir_all(&ds_dir).await.unwrap();
+        let sc = serde_json::json!({"entries": [
+            {"path": "top.csv", "type": "file", "size": 1},
+            {"path": "train/part-0.parquet", "type": "file", "size": 2},
+            {"path": "train/part-1.parquet", "type": "file", "size": 3},
+            {"path": "test/part-0.parquet", "type": "file", "size": 4},
+        ]});
+        tokio::fs::write(ds_dir.join(".paths-info.json"), sc.to_string())
+            .await
+            .unwrap();
+        let app = Router::new()
+            .route("/api/datasets/{*rest}", get(get_dataset_catchall_get))
+            .with_state(AppState::for_tests(root));
+        let send = |uri: &'static str| {
+            let req = axum::http::Request::builder()
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap();
+            app.clone().oneshot(req)
         };
-    {
-        let mut cache = SIBLINGS_CACHE.write().await;
-        if cache.inner.len() >= state.siblings_cache_cap {
-            while let Some((old_k, old_at)) = cache.evict_q.pop_front() {
-                if let Some(entry) = cache.inner.get(&old_k) {
-                    if entry.at == old_at {
-                        cache.inner.remove(&old_k);
-                        break;
-                    }
-                }
-            }
+
+        for uri in [
+            "/api/datasets/tests_ds_tree_subpath/tree/main/train?recursive=1",
+            "/api/datasets/tests_ds_tree_subpath/tree/main/./test/../train/?recursive=1",
+        ] {
+            let resp = send(uri).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK, "{uri}");
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let v: Vec<Value> = serde_json::from_slice(&body).unwrap();
+            let paths: Vec<&str> = v.iter().map(|e| e["path"].as_str().unwrap()).collect();
+            assert_eq!(
+                paths,
+                ["train/part-0.parquet", "train/part-1.parquet"],
+                "{uri}"
+            );
         }
-        cache.evict_q.push_back((cache_key.clone(), now));
-        cache.inner.insert(
-            cache_key,
-            SiblingsEntry {
-                siblings: siblings.clone(),
-                total: total_size,
-                at: now,
-            },
-        );
-    }
 
-    let val = build_repo_json(
-        RepoKind::Dataset,
-        repo_id,
-        revision,
-        &siblings,
-        total_size,
-        RepoJsonFlavor::Rich,
-    );
-    Ok(val)
+        let resp = send("/api/datasets/tests_ds_tree_subpath/tree/main/train/../../x")
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
 }
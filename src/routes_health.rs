@@ -0,0 +1,89 @@
+use axum::Json;
+use axum::extract::State;
+use axum::response::{IntoResponse, Response};
+use serde_json::json;
+
+use crate::app_state::AppState;
+use crate::error::AppError;
+
+// GET /healthz: liveness only, never touches the filesystem.
+pub(crate) async fn get_healthz() -> impl IntoResponse {
+    Json(json!({"status": "ok"}))
+}
+
+// GET /readyz: ready once FAKE_HUB_ROOT exists and can be listed; 503 with the reason otherwise.
+pub(crate) async fn get_readyz(State(state): State<AppState>) -> Response {
+    match tokio::fs::read_dir(state.root.as_path()).await {
+        Ok(_) => Json(json!({"status": "ok"})).into_response(),
+        Err(e) => AppError::NotReady(format!("Root directory not readable: {e}")).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use std::path::PathBuf;
+    use tower::util::ServiceExt;
+
+    fn app(root: &str) -> Router {
+        Router::new()
+            .route("/healthz", get(get_healthz))
+            .route("/readyz", get(get_readyz))
+            .with_state(AppState::for_tests(PathBuf::from(root)))
+    }
+
+    async fn status(app: Router, uri: &str) -> StatusCode {
+        let req = axum::http::Request::builder()
+            .uri(uri)
+            .body(Body::empty())
+            .unwrap();
+        app.oneshot(req).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn probes() {
+        assert_eq!(status(app("fake_hub"), "/healthz").await, StatusCode::OK);
+        assert_eq!(status(app("fake_hub"), "/readyz").await, StatusCode::OK);
+        let missing = "fake_hub/tests_no_such_root";
+        assert_eq!(status(app(missing), "/healthz").await, StatusCode::OK);
+        assert_eq!(
+            status(app(missing), "/readyz").await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+    // The plain listener speaks HTTP/1.1 and h2c (prior knowledge) on the same port.
+    #[tokio::test]
+    async fn serves_http1_and_h2c() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app("fake_hub")).await.unwrap();
+        });
+        let url = format!("http://{addr}/healthz");
+
+        let resp = reqwest::Client::builder()
+            .http1_only()
+            .build()
+            .unwrap()
+            .get(&url)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.version(), reqwest::Version::HTTP_11);
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let h2 = reqwest::Client::builder()
+            .http2_prior_knowledge()
+            .build()
+            .unwrap();
+        let (a, b) = tokio::join!(h2.get(&url).send(), h2.get(&url).send());
+        for resp in [a.unwrap(), b.unwrap()] {
+            assert_eq!(resp.version(), reqwest::Version::HTTP_2);
+            assert_eq!(resp.status(), StatusCode::OK);
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use axum::Json;
+use axum::extract::Request as AxRequest;
+use axum::http::HeaderValue;
+use axum::response::{IntoResponse, Response};
+use percent_encoding::{AsciiSet, CONTROLS, utf8_percent_encode};
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::app_state::AppState;
+use crate::error::AppError;
+use crate::utils::body::read_body_limited;
+use crate::utils::headers::request_base_url;
+use crate::utils::sidecar::load_sidecar_map;
+
+// Escape characters that would break a path segment inside an href.
+const HREF_SEGMENT_ENCODE_SET: &AsciiSet = &CONTROLS.add(b' ').add(b'%').add(b'?').add(b'#');
+
+#[derive(Debug, Deserialize)]
+struct LfsBatchBody {
+    #[serde(default)]
+    operation: Option<String>,
+    #[serde(default)]
+    objects: Vec<LfsObject>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LfsObject {
+    oid: String,
+    #[serde(default)]
+    size: Option<u64>,
+}
+
+// LFS batch API (download only): map each requested sha256 OID to the file whose
+// sidecar `lfs.oid` matches and hand back an href into our own /resolve/ route.
+// `repo_path` is the URL prefix before `/resolve/`, e.g. "org/name" or "datasets/org/name".
+pub(crate) async fn lfs_batch_response(
+    state: &AppState,
+    base_dir: &Path,
+    repo_path: &str,
+    req: AxRequest,
+) -> Response {
+    let (parts, body) = req.into_parts();
+    let body_bytes = match read_body_limited(&parts.headers, body, state.max_json_body_bytes).await
+    {
+        Ok(b) => b,
+        Err(e) => return e.into_response(),
+    };
+    let Ok(batch) = serde_json::from_slice::<LfsBatchBody>(&body_bytes) else {
+        return AppError::InvalidBatch("Malformed batch request").into_response();
+    };
+    let operation = batch.operation.as_deref().unwrap_or("download");
+    if operation != "download" {
+        return AppError::InvalidBatch("Only the download operation is supported").into_response();
+    }
+
+    // Index sidecar entries by bare sha256 oid; smallest path wins for duplicates.
+    let sc_map = match load_sidecar_map(state, base_dir).await {
+        Ok(m) => m,
+        Err(e) => return e.into_response(),
+    };
+    let mut by_oid: HashMap<&str, (&str, u64)> = HashMap::new();
+    for (rel, entry) in sc_map.iter() {
+        let Some(lfs) = entry.get("lfs") else {
+            continue;
+        };
+        let Some(oid) = lfs.get("oid").and_then(|v| v.as_str()) else {
+            continue;
+        };
+        let oid = oid.strip_prefix("sha256:").unwrap_or(oid);
+        let size = lfs
+            .get("size")
+            .and_then(|v| v.as_u64())
+            .or_else(|| entry.get("size").and_then(|v| v.as_u64()))
+            .unwrap_or(0);
+        match by_oid.get(oid) {
+            Some((prev, _)) if *prev <= rel.as_str() => {}
+            _ => {
+                by_oid.insert(oid, (rel.as_str(), size));
+            }
+        }
+    }
+
+    let base_url = request_base_url(&parts.headers);
+    let auth = parts
+        .headers
+        .get("authorization")
+        .and_then(|v| v.to_str().ok());
+    let objects: Vec<Value> = batch
+        .objects
+        .iter()
+        .map(|obj| {
+            let oid = obj.oid.strip_prefix("sha256:").unwrap_or(&obj.oid);
+            let Some((rel, size)) = by_oid.get(oid).copied() else {
+                return json!({
+                    "oid": obj.oid,
+                    "size": obj.size,
+                    "error": {"code": 404, "message": "Object does not exist"},
+                });
+            };
+            if obj.size.is_some_and(|s| s != size) {
+                return json!({
+                    "oid": obj.oid,
+                    "size": obj.size,
+                    "error": {"code": 422, "message": "Object size mismatch"},
+                });
+            }
+            let mut header = serde_json::Map::new();
+            if let Some(a) = auth {
+                header.insert("Authorization".to_string(), json!(a));
+            }
+            json!({
+                "oid": obj.oid,
+                "size": size,
+                "authenticated": true,
+                "actions": {
+                    "download": {
+                        "href": format!(
+                            "{base_url}/{repo_path}/resolve/main/{}",
+                            utf8_percent_encode(rel, HREF_SEGMENT_ENCODE_SET)
+                        ),
+                        "header": header,
+                        "expires_in": 3600,
+                    }
+                },
+            })
+        })
+        .collect();
+
+    let mut resp = Json(json!({"transfer": "basic", "objects": objects})).into_response();
+    resp.headers_mut().insert(
+        "Content-Type",
+        HeaderValue::from_static("application/vnd.git-lfs+json"),
+    );
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app_state::AppState;
+    use axum::Router;
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use axum::routing::post;
+    use tower::util::ServiceExt;
+
+    #[tokio::test]
+    async fn batch_known_and_unknown_oids() {
+        let root = std::path::PathBuf::from("fake_hub");
+        let repo_dir = root.join("tests_repo_lfs_batch");
+        tokio::fs::create_dir_all(&repo_dir).await.unwrap();
+        let sc = json!({
+            "entries": [{
+                "path": "w/model.bin", "type": "file", "size": 3,
+                "lfs": {"oid": "sha256:aaaa", "size": 3}
+            }]
+        });
+        tokio::fs::write(repo_dir.join(".paths-info.json"), sc.to_string())
+            .await
+            .unwrap();
+        let app = Router::new()
+            .route(
+                "/api/models/{*rest}",
+                post(crate::routes_models::get_model_paths_info_post),
+            )
+            .with_state(AppState::for_tests(root));
+
+        let body = json!({
+            "operation": "download",
+            "transfers": ["basic"],
+            "objects": [{"oid": "aaaa", "size": 3}, {"oid": "bbbb", "size": 1}]
+        });
+        let req = axum::http::Request::builder()
+            .method("POST")
+            .uri("/api/models/tests_repo_lfs_batch/info/lfs-objects/batch")
+            .header("Host", "hub.local:8000")
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(v["transfer"], "basic");
+        assert_eq!(
+            v["objects"][0]["actions"]["download"]["href"],
+            "http://hub.local:8000/tests_repo_lfs_batch/resolve/main/w/model.bin"
+        );
+        assert_eq!(v["objects"][1]["error"]["code"], 404);
+    }
+}
@@ -0,0 +1,510 @@
+use std::path::PathBuf;
+
+use axum::Json;
+use axum::extract::{OriginalUri, Query, State};
+use axum::http::{HeaderMap, HeaderValue};
+use axum::response::{IntoResponse, Response};
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::app_state::AppState;
+use crate::error::AppError;
+use crate::routes_auth::{Auth, request_auth};
+use crate::utils::fs_walk::siblings_from_sidecar;
+use crate::utils::headers::request_base_url;
+use crate::utils::repo_json::{
+    RepoJsonFlavor, RepoKind, build_repo_json, read_repo_meta, repo_sha,
+};
+use crate::utils::safetensors::fill_safetensors;
+use crate::utils::sidecar::find_repo_dirs;
+use crate::{decode_cursor, encode_cursor};
+
+#[derive(Deserialize, Default)]
+pub(crate) struct RepoListQuery {
+    #[serde(default)]
+    pub search: Option<String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+    // opaque continuation from a previous page's `Link: rel="next"`
+    #[serde(default)]
+    pub cursor: Option<String>,
+    // `full=1`: rich repo JSON with `siblings`; `config=1`: add the `config` block
+    #[serde(default)]
+    pub full: Option<String>,
+    #[serde(default)]
+    pub config: Option<String>,
+}
+
+impl RepoListQuery {
+    pub(crate) fn full(&self) -> bool {
+        matches!(
+            self.full.as_deref(),
+            Some("1") | Some("true") | Some("True")
+        )
+    }
+
+    pub(crate) fn config(&self) -> bool {
+        matches!(
+            self.config.as_deref(),
+            Some("1") | Some("true") | Some("True")
+        )
+    }
+}
+
+// Page size when `limit` is absent, as on the Hub.
+const LIST_DEFAULT_LIMIT: usize = 50;
+
+// Keys kept in list entries unless `full=1` asks for the whole repo JSON.
+const MODEL_LIST_KEYS: [&str; 4] = ["id", "modelId", "sha", "private"];
+const DATASET_LIST_KEYS: [&str; 3] = ["id", "sha", "private"];
+
+// GET /api/models: what `list_models()` calls.
+pub(crate) async fn get_models_list(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Query(query): Query<RepoListQuery>,
+    headers: HeaderMap,
+) -> Response {
+    list_response(&state, RepoKind::Model, uri.path(), &query, &headers).await
+}
+
+// GET /api/datasets: what `list_datasets()` calls.
+pub(crate) async fn get_datasets_list(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Query(query): Query<RepoListQuery>,
+    headers: HeaderMap,
+) -> Response {
+    list_response(&state, RepoKind::Dataset, uri.path(), &query, &headers).await
+}
+
+// One page as a bare array; when more remain, `Link: <...>; rel="next"` carries the same
+// query (search, limit, full, config) with `cursor` set, which is what `huggingface_hub` follows.
+async fn list_response(
+    state: &AppState,
+    kind: RepoKind,
+    path: &str,
+    query: &RepoListQuery,
+    headers: &HeaderMap,
+) -> Response {
+    let auth = request_auth(state, headers);
+    let page = match list_repos(state, kind, query, auth).await {
+        Ok(page) => page,
+        Err(e) => return e.into_response(),
+    };
+    let mut resp = Json(page.items).into_response();
+    resp.headers_mut()
+        .insert("X-Total-Count", HeaderValue::from(page.total));
+    if let Some(cursor) = page.next {
+        let mut qs = Vec::new();
+        if let Some(search) = &query.search {
+            qs.push(format!(
+                "search={}",
+                utf8_percent_encode(search, NON_ALPHANUMERIC)
+            ));
+        }
+        if let Some(limit) = query.limit {
+            qs.push(format!("limit={limit}"));
+        }
+        if query.full() {
+            qs.push("full=1".to_string());
+        }
+        if query.config() {
+            qs.push("config=1".to_string());
+        }
+        qs.push(format!("cursor={cursor}"));
+        let link = format!(
+            "<{}{}?{}>; rel=\"next\"",
+            request_base_url(headers),
+            path,
+            qs.join("&")
+        );
+        if let Ok(hv) = HeaderValue::from_str(&link) {
+            resp.headers_mut().insert("Link", hv);
+        }
+    }
+    resp
+}
+
+struct ListPage {
+    items: Vec<Value>,
+    // cursor for the following page; None on the last one
+    next: Option<String>,
+    // every visible match across all pages (X-Total-Count)
+    total: usize,
+}
+
+// One page of entries for repos of `kind` whose id contains `search` (case-insensitive),
+// sorted by id. Private repos are only listed for a valid token, as on the Hub; repos with
+// a malformed `.repo-meta.json` are skipped and not counted.
+async fn list_repos(
+    state: &AppState,
+    kind: RepoKind,
+    query: &RepoListQuery,
+    auth: Auth,
+) -> Result<ListPage, AppError> {
+    let limit = query.limit.unwrap_or(LIST_DEFAULT_LIMIT);
+    if limit == 0 {
+        return Err(AppError::BadRequest("limit must be >= 1"));
+    }
+    let after = match query.cursor.as_deref().filter(|c| !c.is_empty()) {
+        Some(c) => Some(decode_cursor(c).ok_or(AppError::BadRequest("Invalid cursor"))?),
+        None => None,
+    };
+    let needle = query.search.as_deref().map(str::to_lowercase);
+    let (full, config) = (query.full(), query.config());
+    let keys: &[&str] = match kind {
+        RepoKind::Model => &MODEL_LIST_KEYS,
+        _ => &DATASET_LIST_KEYS,
+    };
+    let mut out = Vec::new();
+    let mut next = None;
+    let mut total = 0usize;
+    let mut last_id: Option<String> = None;
+    for (repo_id, dir) in repo_dirs(state, kind).await {
+        if let Some(n) = &needle
+            && !repo_id.to_lowercase().contains(n.as_str())
+        {
+            continue;
+        }
+        let Some(meta) = read_repo_meta(&dir).await else {
+            continue;
+        };
+        if meta.is_private() && auth != Auth::Valid {
+            continue;
+        }
+        total += 1;
+        if after.as_deref().is_some_and(|a| repo_id.as_str() <= a) {
+            continue;
+        }
+        if out.len() == limit {
+            if next.is_none() {
+                next = last_id.as_deref().map(encode_cursor);
+            }
+            continue;
+        }
+        let (siblings, used_storage, sha) = siblings_from_sidecar(state, &dir)
+            .await
+            .unwrap_or_else(|_| (Vec::new(), 0, None));
+        let flavor = if full || config {
+            RepoJsonFlavor::Rich
+        } else {
+            RepoJsonFlavor::Minimal
+        };
+        let siblings: &[Value] = if full { &siblings } else { &[] };
+        let mut val = build_repo_json(
+            kind,
+            &repo_id,
+            &repo_sha(None, sha.as_deref()),
+            siblings,
+            used_storage,
+            flavor,
+            Some(&meta.defaults(&state.repo_defaults)),
+        );
+        if full {
+            fill_safetensors(state, &mut val, &dir, siblings).await;
+        }
+        meta.apply(&mut val);
+        if let Some(obj) = val.as_object_mut() {
+            if !full {
+                obj.retain(|k, _| keys.contains(&k.as_str()) || k == "config");
+            }
+            if !config {
+                obj.remove("config");
+            }
+        }
+        out.push(val);
+        last_id = Some(repo_id);
+    }
+    Ok(ListPage {
+        items: out,
+        next,
+        total,
+    })
+}
+
+// (repo id, directory) pairs sorted by id. Models are every repo outside `datasets/` and
+// `spaces/`; datasets and spaces live under their prefix directory.
+async fn repo_dirs(state: &AppState, kind: RepoKind) -> Vec<(String, PathBuf)> {
+    let root = state.root.as_path();
+    let base = match kind {
+        RepoKind::Model => root.to_path_buf(),
+        RepoKind::Dataset => root.join("datasets"),
+        RepoKind::Space => root.join("spaces"),
+    };
+    let mut repos: Vec<(String, PathBuf)> = find_repo_dirs(state, &base)
+        .await
+        .into_iter()
+        .filter_map(|dir| {
+            let rel = dir.strip_prefix(&base).ok()?;
+            if kind == RepoKind::Model && (rel.starts_with("datasets") || rel.starts_with("spaces"))
+            {
+                return None;
+            }
+            let id: Vec<String> = rel
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect();
+            Some((id.join("/"), dir))
+        })
+        .filter(|(id, _)| !id.is_empty())
+        .collect();
+    repos.sort_by(|a, b| a.0.cmp(&b.0));
+    repos
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use std::sync::Arc;
+    use tower::util::ServiceExt;
+
+    #[tokio::test]
+    async fn lists_and_searches_models_and_datasets() {
+        let root = PathBuf::from("fake_hub").join("tests_list_root");
+        let _ = std::fs::remove_dir_all(&root);
+        let sc = r#"{"entries":[{"path":"a.bin","type":"file","size":3,"oid":"abc"}]}"#;
+        for dir in [
+            "org/bert-base",
+            "org/gpt2",
+            "Bert-large",
+            "hidden/secret-bert",
+            "datasets/org/squad",
+            "spaces/org/demo",
+        ] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+            std::fs::write(root.join(dir).join(".paths-info.json"), sc).unwrap();
+        }
+        std::fs::write(
+            root.join("hidden/secret-bert/.repo-meta.json"),
+            r#"{"private": true}"#,
+        )
+        .unwrap();
+        let mut state = AppState::for_tests(root);
+        state.hub_token = Some(Arc::new("hf_secret".to_string()));
+        let app = Router::new()
+            .route("/api/models", get(get_models_list))
+            .route("/api/datasets", get(get_datasets_list))
+            .with_state(state);
+        let list = |uri: &str, token: Option<&str>| {
+            let mut b = axum::http::Request::builder().uri(uri);
+            if let Some(t) = token {
+                b = b.header("Authorization", format!("Bearer {t}"));
+            }
+            let app = app.clone();
+            async move {
+                let resp = app.oneshot(b.body(Body::empty()).unwrap()).await.unwrap();
+                assert_eq!(resp.status(), StatusCode::OK);
+                let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                serde_json::from_slice::<Vec<Value>>(&body).unwrap()
+            }
+        };
+        let ids = |v: &[Value]| -> Vec<String> {
+            v.iter()
+                .map(|r| r["id"].as_str().unwrap().to_string())
+                .collect()
+        };
+
+        let all = list("/api/models", None).await;
+        assert_eq!(ids(&all), ["Bert-large", "org/bert-base", "org/gpt2"]);
+        assert_eq!(all[1]["modelId"], "org/bert-base");
+        assert_eq!(all[1]["private"], false);
+        assert_eq!(all[1]["sha"].as_str().unwrap().len(), 40);
+        assert!(all[1].get("siblings").is_none());
+
+        let found = list("/api/models?search=bert", Some("hf_secret")).await;
+        assert_eq!(
+            ids(&found),
+            ["Bert-large", "hidden/secret-bert", "org/bert-base"]
+        );
+        assert_eq!(found[1]["private"], true);
+        let found = list("/api/models?search=bert&limit=1", None).await;
+        assert_eq!(ids(&found), ["Bert-large"]);
+        // A limit below the match count truncates the page, not X-Total-Count
+        let req = axum::http::Request::builder()
+            .uri("/api/models?search=bert&limit=1")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(resp.headers()["X-Total-Count"], "2");
+        assert!(resp.headers().contains_key("Link"));
+
+        let ds = list("/api/datasets", None).await;
+        assert_eq!(ids(&ds), ["org/squad"]);
+        assert!(ds[0].get("modelId").is_none());
+    }
+
+    #[tokio::test]
+    async fn follows_link_header_across_pages() {
+        let root = PathBuf::from("fake_hub").join("tests_list_pages_root");
+        let _ = std::fs::remove_dir_all(&root);
+        let sc = r#"{"entries":[{"path":"a.bin","type":"file","size":1,"oid":"a"}]}"#;
+        let mut expected: Vec<String> = (0..55).map(|i| format!("org/m{i:02}")).collect();
+        expected.push("solo".to_string());
+        for id in &expected {
+            std::fs::create_dir_all(root.join(id)).unwrap();
+            std::fs::write(root.join(id).join(".paths-info.json"), sc).unwrap();
+        }
+        let app = Router::new()
+            .route("/api/models", get(get_models_list))
+            .with_state(AppState::for_tests(root));
+        let get_page = |uri: String| {
+            let app = app.clone();
+            async move {
+                let req = axum::http::Request::builder()
+                    .uri(uri)
+                    .header("Host", "hub.local:8000")
+                    .body(Body::empty())
+                    .unwrap();
+                let resp = app.oneshot(req).await.unwrap();
+                assert_eq!(resp.status(), StatusCode::OK);
+                let link = resp
+                    .headers()
+                    .get("Link")
+                    .map(|v| v.to_str().unwrap().to_string());
+                let total = resp.headers()["X-Total-Count"]
+                    .to_str()
+                    .unwrap()
+                    .to_string();
+                let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                let page: Vec<Value> = serde_json::from_slice(&body).unwrap();
+                (page, link, total)
+            }
+        };
+
+        // Default page size is 50; the total still counts every repo.
+        let (page, link, total) = get_page("/api/models".to_string()).await;
+        assert_eq!(page.len(), 50);
+        assert!(link.is_some());
+        assert_eq!(total, "56");
+
+        let mut seen = Vec::new();
+        let mut uri = Some("/api/models?search=m&limit=20".to_string());
+        let mut pages = 0;
+        while let Some(u) = uri.take() {
+            let (page, link, total) = get_page(u).await;
+            assert_eq!(total, "55");
+            pages += 1;
+            seen.extend(page.iter().map(|r| r["id"].as_str().unwrap().to_string()));
+            uri = link.map(|l| {
+                let url = l
+                    .strip_prefix("<http://hub.local:8000")
+                    .and_then(|l| l.strip_suffix(">; rel=\"next\""))
+                    .unwrap();
+                assert!(url.contains("search=m&limit=20&cursor="));
+                url.to_string()
+            });
+        }
+        assert_eq!(pages, 3);
+        assert_eq!(seen, expected[..55]);
+
+        let req = axum::http::Request::builder()
+            .uri("/api/models?cursor=***")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn full_and_config_expand_entries() {
+        let root = PathBuf::from("fake_hub").join("tests_list_expand_root");
+        let _ = std::fs::remove_dir_all(&root);
+        let sc = r#"{"entries":[{"path":"a.bin","type":"file","size":3,"oid":"abc"}]}"#;
+        for dir in ["org/model", "datasets/org/ds"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+            std::fs::write(root.join(dir).join(".paths-info.json"), sc).unwrap();
+        }
+        let app = Router::new()
+            .route("/api/models", get(get_models_list))
+            .route("/api/datasets", get(get_datasets_list))
+            .with_state(AppState::for_tests(root));
+        let first = |uri: &str| {
+            let req = axum::http::Request::builder()
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap();
+            let app = app.clone();
+            async move {
+                let resp = app.oneshot(req).await.unwrap();
+                let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                serde_json::from_slice::<Vec<Value>>(&body).unwrap()[0].clone()
+            }
+        };
+
+        let lean = first("/api/models").await;
+        assert_eq!(lean.as_object().unwrap().len(), 4);
+
+        let with_config = first("/api/models?config=1").await;
+        assert_eq!(with_config["config"]["model_type"], "gpt2");
+        assert!(with_config.get("siblings").is_none());
+        assert!(with_config.get("pipeline_tag").is_none());
+
+        let full = first("/api/models?full=true").await;
+        assert_eq!(full["siblings"][0]["rfilename"], "a.bin");
+        assert_eq!(full["pipeline_tag"], "text-generation");
+        assert!(full.get("config").is_none());
+
+        let full = first("/api/models?full=1&config=1").await;
+        assert!(full["siblings"].is_array());
+        assert!(full["config"].is_object());
+
+        let ds = first("/api/datasets?full=1").await;
+        assert_eq!(ds["siblings"][0]["rfilename"], "a.bin");
+        assert_eq!(ds["author"], "local-user");
+    }
+
+    #[tokio::test]
+    async fn unknown_api_paths_get_json_404() {
+        let app = Router::new()
+            .route("/api/models", get(get_models_list))
+            .route(
+                "/api/models/{*rest}",
+                get(crate::routes_models::get_model_catchall_get),
+            )
+            .route("/api/{*rest}", axum::routing::any(crate::unknown_api_route))
+            .route("/{*rest}", get(crate::resolve::resolve_catchall))
+            .with_state(AppState::for_tests(PathBuf::from("fake_hub")));
+        let send = |method: &str, uri: &str| {
+            let req = axum::http::Request::builder()
+                .method(method)
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap();
+            app.clone().oneshot(req)
+        };
+
+        let resp = send("GET", "/api/models?limit=1").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        for (method, uri) in [
+            ("GET", "/api/nope"),
+            ("GET", "/api/collections/x/y"),
+            ("POST", "/api/repos/foo"),
+        ] {
+            let resp = send(method, uri).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND, "{uri}");
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(v["error"], "unknown_api_route", "{uri}");
+        }
+        let resp = send("GET", "/api/models/tests_no_such_repo").await.unwrap();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(v["error"], "repo_not_found");
+    }
+}
@@ -1,55 +1,128 @@
 use std::time::Instant;
 
 use axum::Json;
-use axum::extract::{Path as AxPath, Request as AxRequest, State};
-use axum::http::StatusCode;
+use axum::extract::{Path as AxPath, Query, Request as AxRequest, State};
+use axum::http::HeaderMap;
 use axum::response::IntoResponse;
 use serde_json::Value;
 
 use crate::app_state::AppState;
 use crate::caches::{SIBLINGS_CACHE, SiblingsEntry};
-use crate::utils::paths::secure_join;
-use crate::utils::repo_json::{RepoJsonFlavor, RepoKind, build_repo_json};
-use crate::{http_error, http_not_found, paths_info_response};
+use crate::error::AppError;
+use crate::routes_auth::{Auth, accessible_repo, accessible_repo_path, repo_exists, request_auth};
+use crate::routes_lfs::lfs_batch_response;
+use crate::routes_xet::xet_read_token_response;
+use crate::utils::refs::{commits_from_dir, refs_from_dir};
+use crate::utils::repo_json::{RepoKind, build_repo_json, repo_sha};
+use crate::utils::safetensors::fill_safetensors;
+use crate::{RepoApiQuery, paths_info_response};
 
 pub(crate) async fn get_model_catchall_get(
     State(state): State<AppState>,
     AxPath(rest): AxPath<String>,
+    Query(query): Query<RepoApiQuery>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
+    let auth = request_auth(&state, &headers);
     // rest can be "{repo_id}" or "{repo_id}/revision/{revision}"
     let parts: Vec<&str> = rest.split('/').collect();
-    // Support tree listing: /api/models/{repo_id}/tree/{revision}
-    if parts.len() >= 3 && parts[parts.len() - 2] == "tree" {
-        let _revision = parts.last().unwrap_or(&"");
+    // Experimental xet probe: /api/models/{repo_id}/xet-read-token/{revision}
+    if state.xet_stub && parts.len() >= 3 && parts[parts.len() - 2] == "xet-read-token" {
         let repo_id = parts[..parts.len() - 2].join("/");
-        let Some(repo_path) = secure_join(&state.root, &repo_id) else {
-            return http_not_found("Repository not found");
+        return xet_read_token_response(&state, &headers, RepoKind::Model, &repo_id).await;
+    }
+    // Single-file metadata: /api/models/{repo_id}/file-metadata/{revision}/{path...}
+    if let Some(t) =
+        crate::file_metadata_segment(&parts, |id| repo_exists(&state, RepoKind::Model, id))
+    {
+        let repo_id = parts[..t].join("/");
+        let Some(rel) = crate::tree_sub_path(&parts[t + 2..]) else {
+            return AppError::PathForbidden.into_response();
+        };
+        let repo_path = match accessible_repo_path(&state, RepoKind::Model, &repo_id, auth).await {
+            Ok(p) => p,
+            Err(e) => return e.into_response(),
+        };
+        return match crate::file_metadata_response(&state, &repo_path, &rel).await {
+            Ok(val) => Json(val).into_response(),
+            Err(e) => e.into_response(),
+        };
+    }
+    // Support tree listing: /api/models/{repo_id}/tree/{revision}[/{path...}]
+    if let Some(t) = crate::tree_segment(&parts, |id| repo_exists(&state, RepoKind::Model, id)) {
+        let repo_id = parts[..t].join("/");
+        // Only a filter over sidecar entries, but `..` is still refused like secure_join does
+        let Some(sub_path) = crate::tree_sub_path(&parts[t + 2..]) else {
+            return AppError::PathForbidden.into_response();
+        };
+        let repo_path = match accessible_repo_path(&state, RepoKind::Model, &repo_id, auth).await {
+            Ok(p) => p,
+            Err(e) => return e.into_response(),
         };
-        if !repo_path.is_dir() {
-            return http_not_found("Repository not found");
-        }
         // Sidecar required: error if missing/incomplete
-        if let Some(vals) = crate::utils::fs_walk::collect_paths_info_from_sidecar(&repo_path).await
+        let vals = match crate::utils::fs_walk::collect_paths_info_from_sidecar(&state, &repo_path)
+            .await
         {
-            return Json(vals).into_response();
+            Ok(vals) => vals,
+            Err(e) => return e.into_response(),
+        };
+        let mut vals =
+            crate::utils::fs_walk::scope_tree_records(vals, &sub_path, query.recursive());
+        if vals.is_empty() && !sub_path.is_empty() {
+            return AppError::FileNotFound.into_response();
         }
-        return http_error(
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Sidecar missing or incomplete",
-        );
+        if !query.expand() {
+            crate::slim_tree_records(&mut vals);
+        }
+        if query.with_mtime() {
+            crate::utils::fs_walk::add_last_modified(&state, &repo_path, &mut vals).await;
+        }
+        return Json(vals).into_response();
+    }
+    // Refs listing: /api/models/{repo_id}/refs
+    if parts.len() >= 2
+        && parts[parts.len() - 1] == "refs"
+        && repo_exists(&state, RepoKind::Model, &parts[..parts.len() - 1].join("/"))
+    {
+        let repo_id = parts[..parts.len() - 1].join("/");
+        let repo_path = match accessible_repo_path(&state, RepoKind::Model, &repo_id, auth).await {
+            Ok(p) => p,
+            Err(e) => return e.into_response(),
+        };
+        return match refs_from_dir(&repo_path).await {
+            Some(v) => Json(v).into_response(),
+            None => AppError::SidecarMalformed("Refs sidecar malformed").into_response(),
+        };
+    }
+    // Commit history: /api/models/{repo_id}/commits/{revision}
+    if parts.len() >= 3 && parts[parts.len() - 2] == "commits" {
+        let revision = parts.last().unwrap_or(&"");
+        let repo_id = parts[..parts.len() - 2].join("/");
+        let repo_path = match accessible_repo_path(&state, RepoKind::Model, &repo_id, auth).await {
+            Ok(p) => p,
+            Err(e) => return e.into_response(),
+        };
+        let Some(mut commits) = commits_from_dir(&repo_path, revision, &state.hub_user).await
+        else {
+            return AppError::SidecarMalformed("Commits sidecar malformed").into_response();
+        };
+        if let Some(limit) = query.limit {
+            commits.truncate(limit);
+        }
+        return Json(commits).into_response();
     }
     if parts.len() >= 3 && parts[parts.len() - 2] == "revision" {
         let revision = parts.last().unwrap_or(&"");
         let repo_id = parts[..parts.len() - 2].join("/");
-        match build_model_response(&state, &repo_id, Some(revision)).await {
+        match build_model_response(&state, &repo_id, Some(revision), auth).await {
             Ok(val) => Json(val).into_response(),
-            Err(e) => e,
+            Err(e) => e.into_response(),
         }
     } else {
         let repo_id = rest;
-        match build_model_response(&state, &repo_id, None).await {
+        match build_model_response(&state, &repo_id, None, auth).await {
             Ok(val) => Json(val).into_response(),
-            Err(e) => e,
+            Err(e) => e.into_response(),
         }
     }
 }
@@ -59,23 +132,38 @@ pub(crate) async fn get_model_paths_info_post(
     AxPath(rest): AxPath<String>,
     req: AxRequest,
 ) -> impl IntoResponse {
-    // expect "{repo_id}/paths-info/{revision}"
+    let auth = request_auth(&state, req.headers());
+    // LFS batch API: "{repo_id}/info/lfs-objects/batch"
+    if let Some(repo_id) = rest.strip_suffix("/info/lfs-objects/batch") {
+        let repo_path = match accessible_repo_path(&state, RepoKind::Model, repo_id, auth).await {
+            Ok(p) => p,
+            Err(e) => return e.into_response(),
+        };
+        return lfs_batch_response(&state, &repo_path, repo_id, req).await;
+    }
     let parts: Vec<&str> = rest.split('/').collect();
+    // Upload-mode negotiation: "{repo_id}/preupload/{revision}"
+    if parts.len() >= 3 && parts[parts.len() - 2] == "preupload" {
+        let repo_id = parts[..parts.len() - 2].join("/");
+        if let Err(e) = accessible_repo_path(&state, RepoKind::Model, &repo_id, auth).await {
+            return e.into_response();
+        }
+        return crate::upload::preupload_response(&state, req).await;
+    }
+    // expect "{repo_id}/paths-info/{revision}"
     if parts.len() >= 3 && parts[parts.len() - 2] == "paths-info" {
         let _revision = parts.last().unwrap_or(&"");
         let repo_id = parts[..parts.len() - 2].join("/");
-        let Some(repo_path) = secure_join(&state.root, &repo_id) else {
-            return http_not_found("Repository not found");
+        let repo_path = match accessible_repo_path(&state, RepoKind::Model, &repo_id, auth).await {
+            Ok(p) => p,
+            Err(e) => return e.into_response(),
         };
-        if !repo_path.is_dir() {
-            return http_not_found("Repository not found");
-        }
         match paths_info_response(&state, &repo_path, req).await {
             Ok(vals) => Json(vals).into_response(),
-            Err(e) => e,
+            Err(e) => e.into_response(),
         }
     } else {
-        http_not_found("Not Found")
+        AppError::NotFound.into_response()
     }
 }
 
@@ -83,13 +171,9 @@ async fn build_model_response(
     state: &AppState,
     repo_id: &str,
     revision: Option<&str>,
-) -> Result<Value, axum::response::Response> {
-    let Some(repo_path) = secure_join(&state.root, repo_id) else {
-        return Err(http_not_found("Repository not found"));
-    };
-    if !repo_path.is_dir() {
-        return Err(http_not_found("Repository not found"));
-    }
+    auth: Auth,
+) -> Result<Value, AppError> {
+    let (repo_path, meta) = accessible_repo(state, RepoKind::Model, repo_id, auth).await?;
     // repo_path is canonical from secure_join; avoid redundant canonicalize
     let cache_key = format!("model:{}", repo_path.display());
     let now = Instant::now();
@@ -97,47 +181,45 @@ async fn build_model_response(
     if let Some(hit) = {
         let cache = SIBLINGS_CACHE.read().await;
         cache.inner.get(&cache_key).cloned()
-    } {
-        if now.duration_since(hit.at) < state.cache_ttl {
-            // LRU refresh on hit
-            let fresh = Instant::now();
-            let mut cachew = SIBLINGS_CACHE.write().await;
-            if let Some(entry) = cachew.inner.get_mut(&cache_key) {
-                entry.at = fresh;
-                cachew.evict_q.push_back((cache_key.clone(), fresh));
-            }
-            let val = build_repo_json(
-                RepoKind::Model,
-                repo_id,
-                revision,
-                &hit.siblings,
-                hit.total,
-                RepoJsonFlavor::Rich,
-            );
-            return Ok(val);
+    } && now.duration_since(hit.at) < state.siblings_cache_ttl
+    {
+        crate::metrics::SIBLINGS_CACHE.hit();
+        // LRU refresh on hit
+        let fresh = Instant::now();
+        let mut cachew = SIBLINGS_CACHE.write().await;
+        if let Some(entry) = cachew.inner.get_mut(&cache_key) {
+            entry.at = fresh;
+            cachew.evict_q.push_back((cache_key.clone(), fresh));
         }
+        let mut val = build_repo_json(
+            RepoKind::Model,
+            repo_id,
+            &repo_sha(revision, hit.sha.as_deref()),
+            &hit.siblings,
+            hit.total,
+            meta.flavor,
+            Some(&meta.defaults(&state.repo_defaults)),
+        );
+        fill_safetensors(state, &mut val, &repo_path, &hit.siblings).await;
+        meta.apply(&mut val);
+        return Ok(val);
     }
 
+    crate::metrics::SIBLINGS_CACHE.miss();
+
     // Sidecar required: compute siblings strictly from sidecar
-    let (siblings, total_size): (Vec<Value>, u64) =
-        if let Some((s, t)) = crate::utils::fs_walk::siblings_from_sidecar(&repo_path).await {
-            (s, t)
-        } else {
-            return Err(http_error(
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Sidecar missing or incomplete",
-            ));
-        };
+    let (siblings, total_size, content_sha) =
+        crate::utils::fs_walk::siblings_from_sidecar(state, &repo_path).await?;
     // Insert to cache (bounded)
     {
         let mut cache = SIBLINGS_CACHE.write().await;
         if cache.inner.len() >= state.siblings_cache_cap {
             while let Some((old_k, old_at)) = cache.evict_q.pop_front() {
-                if let Some(entry) = cache.inner.get(&old_k) {
-                    if entry.at == old_at {
-                        cache.inner.remove(&old_k);
-                        break;
-                    }
+                if let Some(entry) = cache.inner.get(&old_k)
+                    && entry.at == old_at
+                {
+                    cache.inner.remove(&old_k);
+                    break;
                 }
             }
         }
@@ -147,18 +229,634 @@ async fn build_model_response(
             SiblingsEntry {
                 siblings: siblings.clone(),
                 total: total_size,
+                sha: content_sha.clone(),
                 at: now,
             },
         );
     }
 
-    let val = build_repo_json(
+    let mut val = build_repo_json(
         RepoKind::Model,
         repo_id,
-        revision,
+        &repo_sha(revision, content_sha.as_deref()),
         &siblings,
         total_size,
-        RepoJsonFlavor::Minimal,
+        meta.flavor,
+        Some(&meta.defaults(&state.repo_defaults)),
     );
+    fill_safetensors(state, &mut val, &repo_path, &siblings).await;
+    meta.apply(&mut val);
     Ok(val)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use tower::util::ServiceExt;
+
+    #[tokio::test]
+    async fn commits_route_honors_limit() {
+        let root = std::path::PathBuf::from("fake_hub");
+        let repo_dir = root.join("tests_repo_commits_route");
+        tokio::fs::create_dir_all(&repo_dir).await.unwrap();
+        let sc = serde_json::json!([
+            {"id": "c3", "title": "t3", "message": "", "authors": [], "date": "1970-01-01T00:00:00.000Z"},
+            {"id": "c2", "title": "t2", "message": "", "authors": [], "date": "1970-01-01T00:00:00.000Z"},
+            {"id": "c1", "title": "t1", "message": "", "authors": [], "date": "1970-01-01T00:00:00.000Z"},
+        ]);
+        tokio::fs::write(repo_dir.join(".commits.json"), sc.to_string())
+            .await
+            .unwrap();
+        let app = Router::new()
+            .route("/api/models/{*rest}", get(get_model_catchall_get))
+            .with_state(AppState::for_tests(root));
+
+        let req = axum::http::Request::builder()
+            .uri("/api/models/tests_repo_commits_route/commits/main?limit=2")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: Vec<Value> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(v.len(), 2);
+        assert_eq!(v[0]["id"], "c3");
+    }
+
+    #[tokio::test]
+    async fn repo_json_shape_is_stable_across_cache() {
+        let root = dunce::canonicalize("fake_hub").unwrap();
+        let repo_dir = root.join("tests_repo_meta_model");
+        tokio::fs::create_dir_all(&repo_dir).await.unwrap();
+        let sc = serde_json::json!({"entries": [
+            {"path": "a.txt", "type": "file", "size": 1, "oid": "a"},
+        ]});
+        tokio::fs::write(repo_dir.join(".paths-info.json"), sc.to_string())
+            .await
+            .unwrap();
+        let meta = serde_json::json!({"pipeline_tag": "fill-mask", "library_name": "timm"});
+        tokio::fs::write(repo_dir.join(".repo-meta.json"), meta.to_string())
+            .await
+            .unwrap();
+        let app = Router::new()
+            .route("/api/models/{*rest}", get(get_model_catchall_get))
+            .with_state(AppState::for_tests(root));
+
+        // first call fills the siblings cache, second is served from it
+        let mut bodies = Vec::new();
+        for _ in 0..2 {
+            let req = axum::http::Request::builder()
+                .uri("/api/models/tests_repo_meta_model")
+                .body(Body::empty())
+                .unwrap();
+            let resp = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            bodies.push(serde_json::from_slice::<Value>(&body).unwrap());
+        }
+        assert_eq!(bodies[0], bodies[1]);
+        assert_eq!(bodies[0]["pipeline_tag"], "fill-mask");
+        assert_eq!(bodies[0]["library_name"], "timm");
+        assert!(bodies[0].get("cardData").is_some());
+    }
+
+    #[tokio::test]
+    async fn paths_info_paginates_with_cursor() {
+        let root = std::path::PathBuf::from("fake_hub");
+        let repo_dir = root.join("tests_repo_paths_info_page");
+        tokio::fs::create_dir_all(&repo_dir).await.unwrap();
+        let sc = serde_json::json!({"entries": [
+            {"path": "c.txt", "type": "file", "size": 1, "oid": "c"},
+            {"path": "a.txt", "type": "file", "size": 1, "oid": "a"},
+            {"path": "b.txt", "type": "file", "size": 1, "oid": "b"},
+        ]});
+        tokio::fs::write(repo_dir.join(".paths-info.json"), sc.to_string())
+            .await
+            .unwrap();
+        let app = Router::new()
+            .route(
+                "/api/models/{*rest}",
+                axum::routing::post(get_model_paths_info_post),
+            )
+            .with_state(AppState::for_tests(root));
+
+        let mut seen: Vec<String> = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let body = match &cursor {
+                Some(c) => serde_json::json!({"limit": 2, "cursor": c}),
+                None => serde_json::json!({"limit": 2}),
+            };
+            let req = axum::http::Request::builder()
+                .method("POST")
+                .uri("/api/models/tests_repo_paths_info_page/paths-info/main")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap();
+            let resp = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+            let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let v: Value = serde_json::from_slice(&bytes).unwrap();
+            let items = v["items"].as_array().unwrap();
+            assert!(items.len() <= 2);
+            seen.extend(
+                items
+                    .iter()
+                    .map(|it| it["path"].as_str().unwrap().to_string()),
+            );
+            match v["next_cursor"].as_str() {
+                Some(c) => cursor = Some(c.to_string()),
+                None => break,
+            }
+        }
+        assert_eq!(seen, vec!["a.txt", "b.txt", "c.txt"]);
+
+        let req = axum::http::Request::builder()
+            .method("POST")
+            .uri("/api/models/tests_repo_paths_info_page/paths-info/main?cursor=%21%21")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn paths_info_rejects_oversized_body() {
+        let root = dunce::canonicalize("fake_hub").unwrap();
+        let repo_dir = root.join("tests_repo_body_limit");
+        tokio::fs::create_dir_all(&repo_dir).await.unwrap();
+        let sc = serde_json::json!({"entries": [
+            {"path": "a.txt", "type": "file", "size": 1, "oid": "a"},
+        ]});
+        tokio::fs::write(repo_dir.join(".paths-info.json"), sc.to_string())
+            .await
+            .unwrap();
+        let mut state = AppState::for_tests(root);
+        state.max_json_body_bytes = 64;
+        let app = Router::new()
+            .route(
+                "/api/models/{*rest}",
+                axum::routing::post(get_model_paths_info_post),
+            )
+            .with_state(state);
+        let uri = "/api/models/tests_repo_body_limit/paths-info/main";
+        let big = serde_json::json!({"paths": ["x".repeat(200)]}).to_string();
+
+        // declared Content-Length over the limit
+        let req = axum::http::Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header("content-length", big.len())
+            .body(Body::from(big.clone()))
+            .unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: Value = serde_json::from_slice(&bytes).unwrap();
+        assert!(v["detail"].is_string());
+
+        // no Content-Length: the streamed read stops at the limit
+        let chunks = big.into_bytes();
+        let stream = async_stream::stream! {
+            for c in chunks.chunks(16) {
+                yield Ok::<_, std::io::Error>(c.to_vec());
+            }
+        };
+        let req = axum::http::Request::builder()
+            .method("POST")
+            .uri(uri)
+            .body(Body::from_stream(stream))
+            .unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let req = axum::http::Request::builder()
+            .method("POST")
+            .uri(uri)
+            .body(Body::from(r#"{"paths":["a.txt"]}"#))
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn paths_info_expand_field_list_filters_keys() {
+        let root = std::path::PathBuf::from("fake_hub");
+        let repo_dir = root.join("tests_repo_paths_info_fields");
+        tokio::fs::create_dir_all(&repo_dir).await.unwrap();
+        let sc = serde_json::json!({"entries": [
+            {"path": "w.bin", "type": "file", "size": 4, "oid": "o",
+             "lfs": {"oid": "sha256:ab", "size": 4}},
+        ]});
+        tokio::fs::write(repo_dir.join(".paths-info.json"), sc.to_string())
+            .await
+            .unwrap();
+        let app = Router::new()
+            .route(
+                "/api/models/{*rest}",
+                axum::routing::post(get_model_paths_info_post),
+            )
+            .with_state(AppState::for_tests(root));
+
+        let body = serde_json::json!({"paths": ["w.bin"], "expand": ["lfs", "securityStatus"]});
+        let req = axum::http::Request::builder()
+            .method("POST")
+            .uri("/api/models/tests_repo_paths_info_fields/paths-info/main")
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: Vec<Value> = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(v.len(), 1);
+        assert_eq!(v[0]["size"], 4);
+        assert_eq!(v[0]["lfs"]["oid"], "sha256:ab");
+        assert!(v[0].get("oid").is_none());
+        assert!(v[0].get("securityStatus").is_none());
+    }
+
+    #[tokio::test]
+    async fn tree_with_mtime_stats_files() {
+        let root = std::path::PathBuf::from("fake_hub");
+        let repo_dir = root.join("tests_repo_tree_mtime");
+        tokio::fs::create_dir_all(&repo_dir).await.unwrap();
+        tokio::fs::write(repo_dir.join("here.txt"), b"x")
+            .await
+            .unwrap();
+        let sc = serde_json::json!({"entries": [
+            {"path": "here.txt", "type": "file", "size": 1},
+            {"path": "gone.txt", "type": "file", "size": 1},
+        ]});
+        tokio::fs::write(repo_dir.join(".paths-info.json"), sc.to_string())
+            .await
+            .unwrap();
+        let app = Router::new()
+            .route("/api/models/{*rest}", get(get_model_catchall_get))
+            .with_state(AppState::for_tests(root));
+
+        for (uri, expect_mtime) in [
+            ("/api/models/tests_repo_tree_mtime/tree/main", false),
+            (
+                "/api/models/tests_repo_tree_mtime/tree/main?with_mtime=1",
+                true,
+            ),
+        ] {
+            let req = axum::http::Request::builder()
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap();
+            let resp = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let v: Vec<Value> = serde_json::from_slice(&body).unwrap();
+            let here = v.iter().find(|e| e["path"] == "here.txt").unwrap();
+            let gone = v.iter().find(|e| e["path"] == "gone.txt").unwrap();
+            if expect_mtime {
+                let ts = here["lastModified"].as_str().unwrap();
+                assert!(ts.ends_with('Z') && ts.contains('T'), "{ts}");
+                assert!(gone["lastModified"].is_null());
+                assert!(gone.get("lastModified").is_some());
+            } else {
+                assert!(here.get("lastModified").is_none());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn tree_lists_one_level_unless_recursive() {
+        let root = std::path::PathBuf::from("fake_hub");
+        let repo_dir = root.join("tests_repo_tree_levels");
+        tokio::fs::create_dir_all(&repo_dir).await.unwrap();
+        let sc = serde_json::json!({"entries": [
+            {"path": "README.md", "type": "file", "size": 1},
+            {"path": "data/a.bin", "type": "file", "size": 2},
+            {"path": "data/sub/b.bin", "type": "file", "size": 3},
+            {"path": "data/sub/deep/c.bin", "type": "file", "size": 4},
+            {"path": "docs/x.md", "type": "file", "size": 5},
+        ]});
+        tokio::fs::write(repo_dir.join(".paths-info.json"), sc.to_string())
+            .await
+            .unwrap();
+        let app = Router::new()
+            .route("/api/models/{*rest}", get(get_model_catchall_get))
+            .with_state(AppState::for_tests(root));
+
+        for (uri, expect) in [
+            (
+                "/api/models/tests_repo_tree_levels/tree/main",
+                vec![
+                    ("README.md", "file"),
+                    ("data", "directory"),
+                    ("docs", "directory"),
+                ],
+            ),
+            (
+                "/api/models/tests_repo_tree_levels/tree/main/data",
+                vec![("data/a.bin", "file"), ("data/sub", "directory")],
+            ),
+            (
+                "/api/models/tests_repo_tree_levels/tree/main/data?recursive=1",
+                vec![
+                    ("data/a.bin", "file"),
+                    ("data/sub/b.bin", "file"),
+                    ("data/sub/deep/c.bin", "file"),
+                ],
+            ),
+            (
+                "/api/models/tests_repo_tree_levels/tree/main?recursive=true",
+                vec![
+                    ("README.md", "file"),
+                    ("data/a.bin", "file"),
+                    ("data/sub/b.bin", "file"),
+                    ("data/sub/deep/c.bin", "file"),
+                    ("docs/x.md", "file"),
+                ],
+            ),
+        ] {
+            let req = axum::http::Request::builder()
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap();
+            let resp = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK, "{uri}");
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let v: Vec<Value> = serde_json::from_slice(&body).unwrap();
+            let got: Vec<(&str, &str)> = v
+                .iter()
+                .map(|e| (e["path"].as_str().unwrap(), e["type"].as_str().unwrap()))
+                .collect();
+            assert_eq!(got, expect, "{uri}");
+        }
+
+        let req = axum::http::Request::builder()
+            .uri("/api/models/tests_repo_tree_levels/tree/main/nope")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn preupload_picks_lfs_for_large_or_binary_files() {
+        let root = std::path::PathBuf::from("fake_hub");
+        tokio::fs::create_dir_all(root.join("tests_repo_preupload"))
+            .await
+            .unwrap();
+        let mut state = AppState::for_tests(root);
+        state.lfs_threshold = 1000;
+        let app = Router::new()
+            .route(
+                "/api/models/{*rest}",
+                axum::routing::post(get_model_paths_info_post),
+            )
+            .with_state(state);
+        let body = serde_json::json!({"files": [
+            {"path": "README.md", "size": 12, "sample": "IyBoZWxsbyB3b3JsZA=="},
+            {"path": "big.txt", "size": 1000, "sample": "YWJj"},
+            {"path": "w.bin", "size": 4, "sample": "AAECAw=="},
+        ]});
+        let send = |uri: &str, body: String| {
+            let req = axum::http::Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header("Content-Type", "application/json")
+                .body(Body::from(body))
+                .unwrap();
+            app.clone().oneshot(req)
+        };
+
+        let resp = send(
+            "/api/models/tests_repo_preupload/preupload/main",
+            body.to_string(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: Value = serde_json::from_slice(&bytes).unwrap();
+        let modes: Vec<(&str, &str)> = v["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|f| {
+                assert_eq!(f["shouldIgnore"], false);
+                (
+                    f["path"].as_str().unwrap(),
+                    f["uploadMode"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            modes,
+            [
+                ("README.md", "regular"),
+                ("big.txt", "lfs"),
+                ("w.bin", "lfs")
+            ]
+        );
+
+        let resp = send(
+            "/api/models/tests_no_such_repo/preupload/main",
+            body.to_string(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn file_metadata_returns_single_record() {
+        let root = dunce::canonicalize("fake_hub").unwrap();
+        let repo_dir = root.join("tests_repo_file_meta");
+        tokio::fs::create_dir_all(&repo_dir).await.unwrap();
+        let sc = serde_json::json!({"entries": [
+            {"path": "config.json", "type": "file", "size": 5, "oid": "abc"},
+            {"path": "w/model.bin", "type": "file", "size": 9, "oid": "def",
+             "lfs": {"oid": "sha256:00", "size": 9}},
+        ]});
+        tokio::fs::write(repo_dir.join(".paths-info.json"), sc.to_string())
+            .await
+            .unwrap();
+        let app = Router::new()
+            .route("/api/models/{*rest}", get(get_model_catchall_get))
+            .with_state(AppState::for_tests(root));
+        let send = |uri: &'static str| {
+            let req = axum::http::Request::builder()
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap();
+            app.clone().oneshot(req)
+        };
+
+        let resp = send("/api/models/tests_repo_file_meta/file-metadata/main/w/model.bin")
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            v,
+            serde_json::json!({"path": "w/model.bin", "type": "file", "size": 9, "oid": "def",
+                "lfs": {"oid": "sha256:00", "size": 9}})
+        );
+
+        for (uri, status) in [
+            (
+                "/api/models/tests_repo_file_meta/file-metadata/main/missing.txt",
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                "/api/models/tests_repo_file_meta/file-metadata/main/w",
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                "/api/models/tests_repo_file_meta/file-metadata/main/../x",
+                StatusCode::FORBIDDEN,
+            ),
+        ] {
+            assert_eq!(send(uri).await.unwrap().status(), status, "{uri}");
+        }
+    }
+
+    #[tokio::test]
+    async fn repo_named_tree_is_not_split_on_its_name() {
+        let root = dunce::canonicalize("fake_hub").unwrap();
+        let repo_dir = root.join("tests_tree_org").join("tree");
+        tokio::fs::create_dir_all(&repo_dir).await.unwrap();
+        let sc = serde_json::json!({"entries": [
+            {"path": "tree/a.bin", "type": "file", "size": 1},
+        ]});
+        tokio::fs::write(repo_dir.join(".paths-info.json"), sc.to_string())
+            .await
+            .unwrap();
+        let app = Router::new()
+            .route("/api/models/{*rest}", get(get_model_catchall_get))
+            .with_state(AppState::for_tests(root));
+        let send = |uri: &'static str| {
+            let req = axum::http::Request::builder()
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap();
+            app.clone().oneshot(req)
+        };
+
+        let resp = send("/api/models/tests_tree_org/tree/revision/main")
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(v["id"], "tests_tree_org/tree");
+
+        let resp = send("/api/models/tests_tree_org/tree/tree/main/tree")
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: Vec<Value> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(v.len(), 1);
+        assert_eq!(v[0]["path"], "tree/a.bin");
+    }
+
+    #[tokio::test]
+    async fn file_metadata_dir_inside_tree_path_stays_a_tree_listing() {
+        let root = dunce::canonicalize("fake_hub").unwrap();
+        let repo_dir = root.join("tests_repo_tree_fm");
+        tokio::fs::create_dir_all(&repo_dir).await.unwrap();
+        let sc = serde_json::json!({"entries": [
+            {"path": "a/file-metadata/b/c/x.bin", "type": "file", "size": 1},
+        ]});
+        tokio::fs::write(repo_dir.join(".paths-info.json"), sc.to_string())
+            .await
+            .unwrap();
+        let app = Router::new()
+            .route("/api/models/{*rest}", get(get_model_catchall_get))
+            .with_state(AppState::for_tests(root));
+
+        let req = axum::http::Request::builder()
+            .uri("/api/models/tests_repo_tree_fm/tree/main/a/file-metadata/b/c")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: Vec<Value> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(v.len(), 1);
+        assert_eq!(v[0]["path"], "a/file-metadata/b/c/x.bin");
+
+        let req = axum::http::Request::builder()
+            .uri("/api/models/tests_repo_tree_fm/file-metadata/main/a/file-metadata/b/c/x.bin")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(v["path"], "a/file-metadata/b/c/x.bin");
+    }
+
+    #[tokio::test]
+    async fn repo_named_refs_is_info_not_a_refs_listing() {
+        let root = dunce::canonicalize("fake_hub").unwrap();
+        let repo_dir = root.join("tests_refs_org").join("refs");
+        tokio::fs::create_dir_all(&repo_dir).await.unwrap();
+        tokio::fs::write(repo_dir.join(".paths-info.json"), r#"{"entries": []}"#)
+            .await
+            .unwrap();
+        let app = Router::new()
+            .route("/api/models/{*rest}", get(get_model_catchall_get))
+            .with_state(AppState::for_tests(root));
+
+        for (uri, is_refs) in [
+            ("/api/models/tests_refs_org/refs", false),
+            ("/api/models/tests_refs_org/refs/refs", true),
+        ] {
+            let req = axum::http::Request::builder()
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap();
+            let resp = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK, "{uri}");
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let v: Value = serde_json::from_slice(&body).unwrap();
+            if is_refs {
+                assert_eq!(v["branches"][0]["name"], "main");
+            } else {
+                assert_eq!(v["id"], "tests_refs_org/refs");
+            }
+        }
+    }
+}
@@ -0,0 +1,246 @@
+// Per-repo API routes of datasets and spaces, which differ only in their RepoKind: the
+// base directory, the URL prefix and the JSON shape `build_repo_json` picks.
+use std::time::Instant;
+
+use axum::Json;
+use axum::extract::Request as AxRequest;
+use axum::http::HeaderMap;
+use axum::response::{IntoResponse, Response};
+use serde_json::Value;
+
+use crate::app_state::AppState;
+use crate::caches::{SIBLINGS_CACHE, SiblingsEntry};
+use crate::error::AppError;
+use crate::routes_auth::{Auth, accessible_repo, accessible_repo_path, repo_exists, request_auth};
+use crate::routes_lfs::lfs_batch_response;
+use crate::routes_xet::xet_read_token_response;
+use crate::utils::refs::refs_from_dir;
+use crate::utils::repo_json::{RepoKind, build_repo_json, repo_sha};
+use crate::{RepoApiQuery, paths_info_response};
+
+// GET /api/{datasets,spaces}/{*rest}: repo info plus the per-repo sub-routes below.
+pub(crate) async fn repo_catchall_get(
+    state: AppState,
+    kind: RepoKind,
+    rest: String,
+    query: RepoApiQuery,
+    headers: HeaderMap,
+) -> Response {
+    let auth = request_auth(&state, &headers);
+    // rest can be "{repo_id}" or "{repo_id}/revision/{revision}"
+    let parts: Vec<&str> = rest.split('/').collect();
+    // Experimental xet probe: /api/{datasets,spaces}/{repo_id}/xet-read-token/{revision}
+    if state.xet_stub && parts.len() >= 3 && parts[parts.len() - 2] == "xet-read-token" {
+        let repo_id = parts[..parts.len() - 2].join("/");
+        return xet_read_token_response(&state, &headers, kind, &repo_id).await;
+    }
+    // Single-file metadata: /api/{datasets,spaces}/{repo_id}/file-metadata/{revision}/{path...}
+    if let Some(t) = crate::file_metadata_segment(&parts, |id| repo_exists(&state, kind, id)) {
+        let repo_id = parts[..t].join("/");
+        let Some(rel) = crate::tree_sub_path(&parts[t + 2..]) else {
+            return AppError::PathForbidden.into_response();
+        };
+        let repo_path = match accessible_repo_path(&state, kind, &repo_id, auth).await {
+            Ok(p) => p,
+            Err(e) => return e.into_response(),
+        };
+        return match crate::file_metadata_response(&state, &repo_path, &rel).await {
+            Ok(val) => Json(val).into_response(),
+            Err(e) => e.into_response(),
+        };
+    }
+    // Support tree listing: /api/{datasets,spaces}/{repo_id}/tree/{revision}[/{path...}]
+    if let Some(t) = crate::tree_segment(&parts, |id| repo_exists(&state, kind, id)) {
+        let repo_id = parts[..t].join("/");
+        // Only a filter over sidecar entries, but `..` is still refused like secure_join does
+        let Some(sub_path) = crate::tree_sub_path(&parts[t + 2..]) else {
+            return AppError::PathForbidden.into_response();
+        };
+        let repo_path = match accessible_repo_path(&state, kind, &repo_id, auth).await {
+            Ok(p) => p,
+            Err(e) => return e.into_response(),
+        };
+        let vals = match crate::utils::fs_walk::collect_paths_info_from_sidecar(&state, &repo_path)
+            .await
+        {
+            Ok(vals) => vals,
+            Err(e) => return e.into_response(),
+        };
+        let mut vals =
+            crate::utils::fs_walk::scope_tree_records(vals, &sub_path, query.recursive());
+        if vals.is_empty() && !sub_path.is_empty() {
+            return AppError::FileNotFound.into_response();
+        }
+        if !query.expand() {
+            crate::slim_tree_records(&mut vals);
+        }
+        if query.with_mtime() {
+            crate::utils::fs_walk::add_last_modified(&state, &repo_path, &mut vals).await;
+        }
+        return Json(vals).into_response();
+    }
+    // Refs listing: /api/{datasets,spaces}/{repo_id}/refs
+    if parts.len() >= 2
+        && parts[parts.len() - 1] == "refs"
+        && repo_exists(&state, kind, &parts[..parts.len() - 1].join("/"))
+    {
+        let repo_id = parts[..parts.len() - 1].join("/");
+        let repo_path = match accessible_repo_path(&state, kind, &repo_id, auth).await {
+            Ok(p) => p,
+            Err(e) => return e.into_response(),
+        };
+        return match refs_from_dir(&repo_path).await {
+            Some(v) => Json(v).into_response(),
+            None => AppError::SidecarMalformed("Refs sidecar malformed").into_response(),
+        };
+    }
+    if parts.len() >= 3 && parts[parts.len() - 2] == "revision" {
+        let revision = parts.last().unwrap_or(&"");
+        let repo_id = parts[..parts.len() - 2].join("/");
+        match build_repo_response(&state, kind, &repo_id, Some(revision), auth).await {
+            Ok(val) => Json(val).into_response(),
+            Err(e) => e.into_response(),
+        }
+    } else {
+        let repo_id = rest;
+        match build_repo_response(&state, kind, &repo_id, None, auth).await {
+            Ok(val) => Json(val).into_response(),
+            Err(e) => e.into_response(),
+        }
+    }
+}
+
+// POST /api/{datasets,spaces}/{*rest}: LFS batch, preupload and paths-info.
+pub(crate) async fn repo_paths_info_post(
+    state: AppState,
+    kind: RepoKind,
+    rest: String,
+    req: AxRequest,
+) -> Response {
+    let auth = request_auth(&state, req.headers());
+    // LFS batch API: "{repo_id}/info/lfs-objects/batch"
+    if let Some(repo_id) = rest.strip_suffix("/info/lfs-objects/batch") {
+        let repo_path = match accessible_repo_path(&state, kind, repo_id, auth).await {
+            Ok(p) => p,
+            Err(e) => return e.into_response(),
+        };
+        return lfs_batch_response(
+            &state,
+            &repo_path,
+            &format!("{}/{repo_id}", kind_prefix(kind)),
+            req,
+        )
+        .await;
+    }
+    let parts: Vec<&str> = rest.split('/').collect();
+    // Upload-mode negotiation: "{repo_id}/preupload/{revision}"
+    if parts.len() >= 3 && parts[parts.len() - 2] == "preupload" {
+        let repo_id = parts[..parts.len() - 2].join("/");
+        if let Err(e) = accessible_repo_path(&state, kind, &repo_id, auth).await {
+            return e.into_response();
+        }
+        return crate::upload::preupload_response(&state, req).await;
+    }
+    // expect "{repo_id}/paths-info/{revision}"
+    if parts.len() >= 3 && parts[parts.len() - 2] == "paths-info" {
+        let _revision = parts.last().unwrap_or(&"");
+        let repo_id = parts[..parts.len() - 2].join("/");
+        let repo_path = match accessible_repo_path(&state, kind, &repo_id, auth).await {
+            Ok(p) => p,
+            Err(e) => return e.into_response(),
+        };
+        match paths_info_response(&state, &repo_path, req).await {
+            Ok(vals) => Json(vals).into_response(),
+            Err(e) => e.into_response(),
+        }
+    } else {
+        AppError::NotFound.into_response()
+    }
+}
+
+async fn build_repo_response(
+    state: &AppState,
+    kind: RepoKind,
+    repo_id: &str,
+    revision: Option<&str>,
+    auth: Auth,
+) -> Result<Value, AppError> {
+    let (repo_path, meta) = accessible_repo(state, kind, repo_id, auth).await?;
+    // repo_path is canonical from secure_join; avoid redundant canonicalize
+    let cache_key = match kind {
+        RepoKind::Space => format!("space:{}", repo_path.display()),
+        _ => format!("dataset:{}", repo_path.display()),
+    };
+    let now = Instant::now();
+    if let Some(hit) = {
+        let cache = SIBLINGS_CACHE.read().await;
+        cache.inner.get(&cache_key).cloned()
+    } && now.duration_since(hit.at) < state.siblings_cache_ttl
+    {
+        crate::metrics::SIBLINGS_CACHE.hit();
+        let fresh = Instant::now();
+        let mut cachew = SIBLINGS_CACHE.write().await;
+        if let Some(entry) = cachew.inner.get_mut(&cache_key) {
+            entry.at = fresh;
+            cachew.evict_q.push_back((cache_key.clone(), fresh));
+        }
+        let mut val = build_repo_json(
+            kind,
+            repo_id,
+            &repo_sha(revision, hit.sha.as_deref()),
+            &hit.siblings,
+            hit.total,
+            meta.flavor,
+            Some(&meta.defaults(&state.repo_defaults)),
+        );
+        meta.apply(&mut val);
+        return Ok(val);
+    }
+    crate::metrics::SIBLINGS_CACHE.miss();
+
+    let (siblings, total_size, content_sha) =
+        crate::utils::fs_walk::siblings_from_sidecar(state, &repo_path).await?;
+    {
+        let mut cache = SIBLINGS_CACHE.write().await;
+        if cache.inner.len() >= state.siblings_cache_cap {
+            while let Some((old_k, old_at)) = cache.evict_q.pop_front() {
+                if let Some(entry) = cache.inner.get(&old_k)
+                    && entry.at == old_at
+                {
+                    cache.inner.remove(&old_k);
+                    break;
+                }
+            }
+        }
+        cache.evict_q.push_back((cache_key.clone(), now));
+        cache.inner.insert(
+            cache_key,
+            SiblingsEntry {
+                siblings: siblings.clone(),
+                total: total_size,
+                sha: content_sha.clone(),
+                at: now,
+            },
+        );
+    }
+
+    let mut val = build_repo_json(
+        kind,
+        repo_id,
+        &repo_sha(revision, content_sha.as_deref()),
+        &siblings,
+        total_size,
+        meta.flavor,
+        Some(&meta.defaults(&state.repo_defaults)),
+    );
+    meta.apply(&mut val);
+    Ok(val)
+}
+
+// URL/directory prefix of a non-model kind.
+fn kind_prefix(kind: RepoKind) -> &'static str {
+    match kind {
+        RepoKind::Space => "spaces",
+        _ => "datasets",
+    }
+}
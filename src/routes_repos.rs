@@ -0,0 +1,378 @@
+use std::io;
+use std::path::Path;
+
+use axum::Json;
+use axum::extract::{Request as AxRequest, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use serde::Deserialize;
+use serde_json::json;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::caches::evict_repo;
+use crate::error::AppError;
+use crate::routes_admin::admin_token_rejection;
+use crate::routes_auth::{Auth, request_auth};
+use crate::utils::body::read_body_limited;
+use crate::utils::headers::request_base_url;
+use crate::utils::paths::secure_join;
+use crate::utils::repo_json::RepoKind;
+use crate::utils::sidecar::{SIDECAR_VERSION, is_repo_dir};
+
+// Top-level names a model id cannot start with: they are other repo kinds' roots or routes.
+const RESERVED_MODEL_ROOTS: &[&str] = &["datasets", "spaces", "api", "admin"];
+
+#[derive(Debug, Deserialize)]
+struct CreateRepoBody {
+    name: String,
+    #[serde(default)]
+    organization: Option<String>,
+    #[serde(default, rename = "type")]
+    repo_type: Option<String>,
+    #[serde(default)]
+    private: Option<bool>,
+    #[serde(default)]
+    exist_ok: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeleteRepoBody {
+    name: String,
+    #[serde(default)]
+    organization: Option<String>,
+    #[serde(default, rename = "type")]
+    repo_type: Option<String>,
+}
+
+// Repo writes need a valid Bearer token, plus a matching X-Admin-Token when ADMIN_TOKEN is set.
+fn write_rejection(state: &AppState, headers: &HeaderMap) -> Option<Response> {
+    if request_auth(state, headers) != Auth::Valid {
+        return Some(
+            AppError::Unauthorized("Invalid credentials in Authorization header").into_response(),
+        );
+    }
+    admin_token_rejection(state, headers)
+}
+
+// Kind, URL/dir prefix ("" for models) and repo id from the Hub's `{type, organization, name}`.
+fn repo_target(
+    repo_type: Option<&str>,
+    organization: Option<&str>,
+    name: &str,
+) -> Result<(RepoKind, &'static str, String), AppError> {
+    let (kind, prefix) = match repo_type {
+        None | Some("model") => (RepoKind::Model, ""),
+        Some("dataset") => (RepoKind::Dataset, "datasets"),
+        Some("space") => (RepoKind::Space, "spaces"),
+        Some(_) => return Err(AppError::BadRequest("Invalid repo type")),
+    };
+    let mut segments: Vec<&str> = Vec::new();
+    if let Some(org) = organization.filter(|o| !o.is_empty()) {
+        segments.push(org);
+    }
+    segments.push(name);
+    if !segments.iter().all(|s| valid_name_segment(s)) {
+        return Err(AppError::BadRequest("Invalid repo name"));
+    }
+    if kind == RepoKind::Model && RESERVED_MODEL_ROOTS.contains(&segments[0]) {
+        return Err(AppError::BadRequest("Reserved repo name"));
+    }
+    Ok((kind, prefix, segments.join("/")))
+}
+
+// POST /api/repos/create: materialize an empty repo dir (an empty `.paths-info.json`, plus
+// `.repo-meta.json` when private) under FAKE_HUB_ROOT.
+pub(crate) async fn post_repo_create(State(state): State<AppState>, req: AxRequest) -> Response {
+    if let Some(resp) = write_rejection(&state, req.headers()) {
+        return resp;
+    }
+    let base_url = request_base_url(req.headers());
+    let (parts, body) = req.into_parts();
+    let body_bytes = match read_body_limited(&parts.headers, body, state.max_json_body_bytes).await
+    {
+        Ok(b) => b,
+        Err(e) => return e.into_response(),
+    };
+    let Ok(body) = serde_json::from_slice::<CreateRepoBody>(&body_bytes) else {
+        return AppError::BadRequest("Malformed create request").into_response();
+    };
+    let (kind, prefix, repo_id) = match repo_target(
+        body.repo_type.as_deref(),
+        body.organization.as_deref(),
+        &body.name,
+    ) {
+        Ok(t) => t,
+        Err(e) => return e.into_response(),
+    };
+
+    let kind_base = state.root.join(prefix);
+    let created =
+        match create_repo_dir(&state, &kind_base, &repo_id, body.private == Some(true)).await {
+            Ok(created) => created,
+            Err(e) => {
+                error!(target: "fakehub", "create repo {} failed: {}", repo_id, e);
+                return AppError::WriteFailed("Could not create repo").into_response();
+            }
+        };
+    if !created && !body.exist_ok {
+        return AppError::RepoExists(kind).into_response();
+    }
+    let url_path = if prefix.is_empty() {
+        repo_id.clone()
+    } else {
+        format!("{prefix}/{repo_id}")
+    };
+    let oid = Uuid::new_v4().simple().to_string();
+    Json(json!({
+        "url": format!("{base_url}/{url_path}"),
+        "name": repo_id,
+        "id": &oid[..24],
+    }))
+    .into_response()
+}
+
+// DELETE /api/repos/delete: remove a repo dir (one holding a sidecar or `.repo-meta.json`)
+// and evict every cache entry derived from it.
+pub(crate) async fn delete_repo(State(state): State<AppState>, req: AxRequest) -> Response {
+    if let Some(resp) = write_rejection(&state, req.headers()) {
+        return resp;
+    }
+    let (parts, body) = req.into_parts();
+    let body_bytes = match read_body_limited(&parts.headers, body, state.max_json_body_bytes).await
+    {
+        Ok(b) => b,
+        Err(e) => return e.into_response(),
+    };
+    let Ok(body) = serde_json::from_slice::<DeleteRepoBody>(&body_bytes) else {
+        return AppError::BadRequest("Malformed delete request").into_response();
+    };
+    let (kind, prefix, repo_id) = match repo_target(
+        body.repo_type.as_deref(),
+        body.organization.as_deref(),
+        &body.name,
+    ) {
+        Ok(t) => t,
+        Err(e) => return e.into_response(),
+    };
+    // An org folder or other plain directory is never treated as a repo
+    let Some(dir) = secure_join(
+        &state.root.join(prefix),
+        &repo_id,
+        state.allow_symlink_escape,
+    )
+    .filter(|d| is_repo_dir(&state, d)) else {
+        return AppError::RepoNotFound(kind).into_response();
+    };
+    if let Err(e) = tokio::fs::remove_dir_all(&dir).await {
+        error!(target: "fakehub", "delete repo {} failed: {}", repo_id, e);
+        return AppError::WriteFailed("Could not delete repo").into_response();
+    }
+    evict_repo(&dir).await;
+    StatusCode::OK.into_response()
+}
+
+// Hub-style name part: ASCII letters, digits, '-', '_' and '.', not starting with '.' or '-'.
+fn valid_name_segment(seg: &str) -> bool {
+    !seg.is_empty()
+        && seg.len() <= 96
+        && !seg.starts_with(['.', '-'])
+        && seg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+// Ok(false) when the repo dir already exists; the leaf is created with `create_dir` so two
+// concurrent requests cannot both report success.
+async fn create_repo_dir(
+    state: &AppState,
+    kind_base: &Path,
+    repo_id: &str,
+    private: bool,
+) -> io::Result<bool> {
+    tokio::fs::create_dir_all(kind_base).await?;
+    let dir = secure_join(kind_base, repo_id, state.allow_symlink_escape)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::PermissionDenied, "path escapes root"))?;
+    if let Some(parent) = dir.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    match tokio::fs::create_dir(&dir).await {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => return Ok(false),
+        Err(e) => return Err(e),
+    }
+    let sidecar = json!({"version": SIDECAR_VERSION, "entries": []});
+    tokio::fs::write(dir.join(".paths-info.json"), sidecar.to_string()).await?;
+    if private {
+        tokio::fs::write(dir.join(".repo-meta.json"), r#"{"private": true}"#).await?;
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::body::Body;
+    use axum::routing::post;
+    use std::path::PathBuf;
+    use tower::util::ServiceExt;
+
+    #[tokio::test]
+    async fn create_materializes_repo_and_conflicts_on_repeat() {
+        let root = PathBuf::from("fake_hub").join("tests_create_root");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let app = Router::new()
+            .route("/api/repos/create", post(post_repo_create))
+            .with_state(AppState::for_tests(root.clone()));
+        let send = |body: serde_json::Value, token: Option<&str>| {
+            let mut b = axum::http::Request::builder()
+                .method("POST")
+                .uri("/api/repos/create")
+                .header("Host", "hub.local:8000")
+                .header("Content-Type", "application/json");
+            if let Some(t) = token {
+                b = b.header("Authorization", format!("Bearer {t}"));
+            }
+            app.clone()
+                .oneshot(b.body(Body::from(body.to_string())).unwrap())
+        };
+        let status =
+            |body: serde_json::Value| async { send(body, Some("hf_x")).await.unwrap().status() };
+
+        let model = json!({"name": "m", "organization": "org"});
+        let resp = send(model.clone(), None).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert!(!root.join("org").exists());
+
+        let resp = send(model.clone(), Some("hf_x")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(v["name"], "org/m");
+        assert_eq!(v["url"], "http://hub.local:8000/org/m");
+        assert_eq!(v["id"].as_str().unwrap().len(), 24);
+        let sc = std::fs::read_to_string(root.join("org/m/.paths-info.json")).unwrap();
+        let sc: serde_json::Value = serde_json::from_str(&sc).unwrap();
+        assert_eq!(sc["entries"], json!([]));
+
+        assert_eq!(status(model.clone()).await, StatusCode::CONFLICT);
+        assert_eq!(
+            status(json!({"name": "m", "organization": "org", "exist_ok": true})).await,
+            StatusCode::OK
+        );
+
+        let resp = send(
+            json!({"name": "d", "type": "dataset", "private": true}),
+            Some("hf_x"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(v["url"], "http://hub.local:8000/datasets/d");
+        let meta = crate::utils::repo_json::read_repo_meta(&root.join("datasets/d"))
+            .await
+            .unwrap();
+        assert!(meta.is_private());
+
+        for bad in [
+            json!({"name": ".."}),
+            json!({"name": "a/b"}),
+            json!({"name": "x", "organization": "../up"}),
+            json!({"name": "x", "organization": "datasets"}),
+            json!({"name": "x", "type": "bucket"}),
+        ] {
+            assert_eq!(status(bad.clone()).await, StatusCode::BAD_REQUEST, "{bad}");
+        }
+        assert!(!root.join("datasets/x").exists());
+    }
+
+    #[tokio::test]
+    async fn delete_removes_repo_and_its_cache_entries() {
+        let root = PathBuf::from("fake_hub").join("tests_delete_root");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let mut state = AppState::for_tests(root.clone());
+        state.admin_token = Some(std::sync::Arc::new("adm".to_string()));
+        let app = Router::new()
+            .route("/api/repos/create", post(post_repo_create))
+            .route("/api/repos/delete", axum::routing::delete(delete_repo))
+            .route(
+                "/api/models/{*rest}",
+                axum::routing::get(crate::routes_models::get_model_catchall_get),
+            )
+            .with_state(state);
+        let send = |method: &str, uri: &str, body: serde_json::Value, admin: bool| {
+            let mut b = axum::http::Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("Authorization", "Bearer hf_x");
+            if admin {
+                b = b.header("X-Admin-Token", "adm");
+            }
+            app.clone()
+                .oneshot(b.body(Body::from(body.to_string())).unwrap())
+        };
+        let repo = json!({"name": "gone", "organization": "org"});
+
+        let resp = send("POST", "/api/repos/create", repo.clone(), false)
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let resp = send("POST", "/api/repos/create", repo.clone(), true)
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        // prime the sidecar and siblings caches
+        let resp = send("GET", "/api/models/org/gone", json!(null), false)
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let dir = dunce::canonicalize(root.join("org/gone")).unwrap();
+        let cached = || async {
+            let sidecar = crate::caches::SIDECAR_CACHE
+                .read()
+                .await
+                .inner
+                .keys()
+                .any(|(p, _, _)| p.starts_with(&dir));
+            let siblings = crate::caches::SIBLINGS_CACHE
+                .read()
+                .await
+                .inner
+                .contains_key(&format!("model:{}", dir.display()));
+            sidecar || siblings
+        };
+        assert!(cached().await);
+
+        let resp = send("DELETE", "/api/repos/delete", repo.clone(), false)
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        // the org folder itself is not a repo
+        let resp = send("DELETE", "/api/repos/delete", json!({"name": "org"}), true)
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let resp = send("DELETE", "/api/repos/delete", repo.clone(), true)
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(!dir.exists());
+        assert!(root.join("org").is_dir());
+        assert!(!cached().await);
+
+        let resp = send("DELETE", "/api/repos/delete", repo, true)
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,68 @@
+use axum::extract::{Path as AxPath, Query, Request as AxRequest, State};
+use axum::http::HeaderMap;
+use axum::response::IntoResponse;
+
+use crate::RepoApiQuery;
+use crate::app_state::AppState;
+use crate::routes_repo_api::{repo_catchall_get, repo_paths_info_post};
+use crate::utils::repo_json::RepoKind;
+
+pub(crate) async fn get_space_catchall_get(
+    State(state): State<AppState>,
+    AxPath(rest): AxPath<String>,
+    Query(query): Query<RepoApiQuery>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    repo_catchall_get(state, RepoKind::Space, rest, query, headers).await
+}
+
+pub(crate) async fn get_space_paths_info_post(
+    State(state): State<AppState>,
+    AxPath(rest): AxPath<String>,
+    req: AxRequest,
+) -> impl IntoResponse {
+    repo_paths_info_post(state, RepoKind::Space, rest, req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use serde_json::Value;
+    use tower::util::ServiceExt;
+
+    #[tokio::test]
+    async fn space_info_served_from_spaces_subdir() {
+        let root = std::path::PathBuf::from("fake_hub");
+        let sp_dir = root.join("spaces").join("tests_space_info");
+        tokio::fs::create_dir_all(&sp_dir).await.unwrap();
+        tokio::fs::write(sp_dir.join("app.py"), b"print(1)\n")
+            .await
+            .unwrap();
+        let sc = serde_json::json!({"entries": [
+            {"path": "app.py", "type": "file", "size": 9, "oid": "abc"},
+        ]});
+        tokio::fs::write(sp_dir.join(".paths-info.json"), sc.to_string())
+            .await
+            .unwrap();
+        let app = Router::new()
+            .route("/api/spaces/{*rest}", get(get_space_catchall_get))
+            .with_state(AppState::for_tests(root));
+
+        let req = axum::http::Request::builder()
+            .uri("/api/spaces/tests_space_info")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(v["id"], "tests_space_info");
+        assert_eq!(v["siblings"][0]["rfilename"], "app.py");
+    }
+}
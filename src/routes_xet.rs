@@ -0,0 +1,101 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::Json;
+use axum::http::HeaderMap;
+use axum::response::{IntoResponse, Response};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::routes_auth::{accessible_repo_path, request_auth};
+use crate::utils::headers::request_base_url;
+use crate::utils::repo_json::RepoKind;
+
+// Lifetime advertised in `exp`; nothing checks the token, so this is cosmetic.
+const XET_TOKEN_TTL_SECS: u64 = 3600;
+
+// GET .../xet-read-token/{revision} (XET_STUB=1): a well-formed `{casUrl, accessToken, exp}`
+// so clients probing for xet support see the capability. The CAS URL points back at this
+// server, which serves no chunks; the token is random and never validated.
+pub(crate) async fn xet_read_token_response(
+    state: &AppState,
+    headers: &HeaderMap,
+    kind: RepoKind,
+    repo_id: &str,
+) -> Response {
+    let auth = request_auth(state, headers);
+    if let Err(e) = accessible_repo_path(state, kind, repo_id, auth).await {
+        return e.into_response();
+    }
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    Json(json!({
+        "casUrl": format!("{}/xet-cas", request_base_url(headers)),
+        "accessToken": format!("xet_{}", Uuid::new_v4().simple()),
+        "exp": now + XET_TOKEN_TTL_SECS,
+    }))
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use serde_json::Value;
+    use tower::util::ServiceExt;
+
+    #[tokio::test]
+    async fn xet_read_token_only_behind_flag() {
+        let root = dunce::canonicalize("fake_hub").unwrap();
+        let ds_dir = root.join("datasets").join("tests_ds_xet");
+        tokio::fs::create_dir_all(&ds_dir).await.unwrap();
+        tokio::fs::write(ds_dir.join(".paths-info.json"), r#"{"entries": []}"#)
+            .await
+            .unwrap();
+        let app = |state: AppState| {
+            Router::new()
+                .route(
+                    "/api/datasets/{*rest}",
+                    get(crate::routes_datasets::get_dataset_catchall_get),
+                )
+                .with_state(state)
+        };
+        let get_uri = |app: Router, uri: &'static str| {
+            let req = axum::http::Request::builder()
+                .uri(uri)
+                .header("host", "hub.local:8000")
+                .body(Body::empty())
+                .unwrap();
+            app.oneshot(req)
+        };
+        let uri = "/api/datasets/tests_ds_xet/xet-read-token/main";
+        let mut state = AppState::for_tests(root);
+
+        let resp = get_uri(app(state.clone()), uri).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        state.xet_stub = true;
+        let resp = get_uri(app(state.clone()), uri).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(v["casUrl"], "http://hub.local:8000/xet-cas");
+        assert!(v["accessToken"].as_str().unwrap().starts_with("xet_"));
+        assert!(v["exp"].as_u64().unwrap() > 0);
+
+        let resp = get_uri(
+            app(state),
+            "/api/datasets/tests_ds_xet_missing/xet-read-token/main",
+        )
+        .await
+        .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,403 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use axum::Json;
+use axum::body::Body;
+use axum::extract::{Request as AxRequest, State};
+use axum::http::{HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use http_body_util::BodyExt;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tokio::io::AsyncWriteExt;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::caches::evict_repo;
+use crate::error::AppError;
+use crate::routes_auth::{Auth, request_auth};
+use crate::utils::body::{declared_length, read_body_limited};
+use crate::utils::digest::FileDigests;
+use crate::utils::paths::{
+    JoinError, decode_path_segments, has_invalid_path_chars, is_sidecar_path, secure_join,
+    try_secure_join,
+};
+use crate::utils::repo_json::RepoKind;
+use crate::utils::sidecar::{find_sidecar, upsert_sidecar_entry};
+
+#[derive(Debug, Deserialize)]
+struct PreuploadBody {
+    #[serde(default)]
+    files: Vec<PreuploadFile>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PreuploadFile {
+    path: String,
+    #[serde(default)]
+    size: u64,
+    // base64 of the file's first bytes (huggingface_hub sends 512)
+    #[serde(default)]
+    sample: Option<String>,
+}
+
+// PUT /{repo_id}/resolve/{revision}/{filename...}: store the body under the repo and record
+// it in the sidecar with the same fields fetch_repo writes (size, sha1 `oid`, `blake3`, and
+// `lfs` from LFS_THRESHOLD_BYTES up). Needs ALLOW_WRITES and a valid Bearer token; the
+// revision is ignored.
+pub(crate) async fn resolve_put(State(state): State<AppState>, req: AxRequest) -> Response {
+    if !state.allow_writes {
+        return AppError::WritesDisabled.into_response();
+    }
+    if request_auth(&state, req.headers()) != Auth::Valid {
+        return AppError::Unauthorized("Invalid credentials in Authorization header")
+            .into_response();
+    }
+    // Per-segment decode as for GET, so an encoded `%2F` cannot create directories
+    let Some(path) = decode_path_segments(req.uri().path()) else {
+        return AppError::InvalidPath.into_response();
+    };
+    if has_invalid_path_chars(&path) {
+        return AppError::InvalidPath.into_response();
+    }
+    let needle = "/resolve/";
+    let Some(idx) = path.rfind(needle) else {
+        return AppError::NotFound.into_response();
+    };
+    let left = &path[1..idx];
+    let mut right_parts = path[(idx + needle.len())..].splitn(2, '/');
+    let revision = right_parts.next().unwrap_or("");
+    let filename = right_parts.next().unwrap_or("");
+    if left.is_empty() || revision.is_empty() || filename.is_empty() {
+        return AppError::NotFound.into_response();
+    }
+    if is_sidecar_path(filename) {
+        return AppError::PathForbidden.into_response();
+    }
+    let kind = if left.starts_with("datasets/") {
+        RepoKind::Dataset
+    } else if left.starts_with("spaces/") {
+        RepoKind::Space
+    } else {
+        RepoKind::Model
+    };
+    // Only existing repos (see /api/repos/create) accept files
+    let Some(repo_dir) = secure_join(&state.root, left, state.allow_symlink_escape)
+        .filter(|d| d.is_dir())
+        .filter(|d| find_sidecar(&state, d).is_some())
+    else {
+        return AppError::RepoNotFound(kind).into_response();
+    };
+    let filepath = match try_secure_join(&repo_dir, filename, state.allow_symlink_escape) {
+        Ok(p) => p,
+        Err(JoinError::Escapes) => return AppError::PathForbidden.into_response(),
+        Err(JoinError::BaseMissing) => return AppError::RepoNotFound(kind).into_response(),
+    };
+    if filepath.is_dir() {
+        return AppError::BadRequest("Path is a directory").into_response();
+    }
+    // Sidecar path from the normalized join, so `a/./b` and `a//b` record as `a/b`
+    let Ok(rel) = filepath.strip_prefix(&repo_dir) else {
+        return AppError::PathForbidden.into_response();
+    };
+    let rel = rel.to_string_lossy().replace('\\', "/");
+
+    if declared_length(req.headers()).is_some_and(|cl| cl as u64 > state.max_upload_bytes) {
+        return AppError::PayloadTooLarge.into_response();
+    }
+
+    // Same path, one writer at a time: the file and its sidecar entry must describe one body
+    let lock = path_lock(&filepath);
+    let _guard = lock.lock().await;
+    let entry = match store_upload(&state, &filepath, &rel, req.into_body()).await {
+        Ok(entry) => entry,
+        Err(e) if e.kind() == std::io::ErrorKind::FileTooLarge => {
+            return AppError::PayloadTooLarge.into_response();
+        }
+        Err(e) => {
+            error!(target: "fakehub", "upload {}/{} failed: {}", left, rel, e);
+            return AppError::WriteFailed("Could not store upload").into_response();
+        }
+    };
+    if let Err(e) = upsert_sidecar_entry(&state, &repo_dir, entry.clone()).await {
+        error!(target: "fakehub", "sidecar update for {}/{} failed: {}", left, rel, e);
+        return AppError::WriteFailed("Could not update sidecar").into_response();
+    }
+    evict_repo(&repo_dir).await;
+
+    let etag = entry
+        .get("lfs")
+        .and_then(|l| l.get("oid"))
+        .and_then(|v| v.as_str())
+        .map(|o| o.trim_start_matches("sha256:"))
+        .or_else(|| entry.get("oid").and_then(|v| v.as_str()))
+        .unwrap_or_default();
+    let mut resp = (StatusCode::CREATED, Json(entry.clone())).into_response();
+    if let Ok(hv) = HeaderValue::from_str(&format!("\"{etag}\"")) {
+        resp.headers_mut().insert("ETag", hv);
+    }
+    resp
+}
+
+// POST .../preupload/{revision}: upload-mode negotiation. Files of at least LFS_THRESHOLD_BYTES
+// or whose sample contains a NUL byte (git's binary heuristic) go to "lfs", the rest are
+// "regular"; nothing is ignored and nothing is stored.
+pub(crate) async fn preupload_response(state: &AppState, req: AxRequest) -> Response {
+    let (parts, body) = req.into_parts();
+    let body_bytes = match read_body_limited(&parts.headers, body, state.max_json_body_bytes).await
+    {
+        Ok(b) => b,
+        Err(e) => return e.into_response(),
+    };
+    let Ok(body) = serde_json::from_slice::<PreuploadBody>(&body_bytes) else {
+        return AppError::BadRequest("Malformed preupload request").into_response();
+    };
+    let mut files = Vec::with_capacity(body.files.len());
+    for f in body.files {
+        let binary = match f.sample.as_deref() {
+            Some(s) => match STANDARD.decode(s) {
+                Ok(bytes) => bytes.contains(&0),
+                Err(_) => return AppError::BadRequest("Invalid sample encoding").into_response(),
+            },
+            None => false,
+        };
+        let mode = if f.size >= state.lfs_threshold || binary {
+            "lfs"
+        } else {
+            "regular"
+        };
+        files.push(json!({"path": f.path, "uploadMode": mode, "shouldIgnore": false}));
+    }
+    Json(json!({"files": files})).into_response()
+}
+
+// Writers of one target path (uploads and proxy fetches) take its lock around storing the
+// file and upserting its sidecar entry.
+type PathLocks = HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>;
+static PATH_LOCKS: once_cell::sync::Lazy<std::sync::Mutex<PathLocks>> =
+    once_cell::sync::Lazy::new(Default::default);
+
+pub(crate) fn path_lock(filepath: &Path) -> Arc<tokio::sync::Mutex<()>> {
+    let mut locks = PATH_LOCKS.lock().unwrap_or_else(|e| e.into_inner());
+    locks.retain(|_, l| Arc::strong_count(l) > 1);
+    locks.entry(filepath.to_path_buf()).or_default().clone()
+}
+
+// Partial uploads are staged here, a hidden dir under FAKE_HUB_ROOT (so the final rename
+// stays on one filesystem) that no repo listing or sidecar walk looks into.
+const UPLOAD_STAGING_DIR: &str = ".upload-tmp";
+
+// Stream the body to a staging file while hashing, then rename it into place; returns the
+// sidecar entry. A body over MAX_UPLOAD_BYTES is a `FileTooLarge` error and leaves nothing
+// behind.
+pub(crate) async fn store_upload(
+    state: &AppState,
+    filepath: &Path,
+    rel: &str,
+    body: Body,
+) -> std::io::Result<Value> {
+    let parent = filepath
+        .parent()
+        .ok_or_else(|| std::io::Error::other("upload path has no parent"))?;
+    tokio::fs::create_dir_all(parent).await?;
+    let staging = state.root.join(UPLOAD_STAGING_DIR);
+    tokio::fs::create_dir_all(&staging).await?;
+    let tmp = staging.join(Uuid::new_v4().simple().to_string());
+    let result = write_hashed(&tmp, body, state.max_upload_bytes).await;
+    let (size, sha1_hex, sha256_hex, blake3_hex) = match result {
+        Ok(r) => r,
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&tmp).await;
+            return Err(e);
+        }
+    };
+    if let Err(e) = tokio::fs::rename(&tmp, filepath).await {
+        let _ = tokio::fs::remove_file(&tmp).await;
+        return Err(e);
+    }
+    let mut rec = serde_json::Map::new();
+    rec.insert("path".to_string(), json!(rel));
+    rec.insert("type".to_string(), json!("file"));
+    rec.insert("size".to_string(), json!(size));
+    rec.insert("oid".to_string(), json!(sha1_hex));
+    rec.insert("blake3".to_string(), json!(blake3_hex));
+    if size >= state.lfs_threshold {
+        rec.insert(
+            "lfs".to_string(),
+            json!({"oid": format!("sha256:{sha256_hex}"), "size": size}),
+        );
+    }
+    Ok(Value::Object(rec))
+}
+
+// (size, sha1, sha256, blake3) of everything written to `tmp`; stops with `FileTooLarge`
+// once more than `max_bytes` arrived.
+async fn write_hashed(
+    tmp: &Path,
+    mut body: Body,
+    max_bytes: u64,
+) -> std::io::Result<(u64, String, String, String)> {
+    let mut file = tokio::fs::File::create(tmp).await?;
+    let mut digests = FileDigests::default();
+    let mut size = 0u64;
+    while let Some(frame) = body.frame().await {
+        let frame = frame.map_err(std::io::Error::other)?;
+        let Ok(data) = frame.into_data() else {
+            continue;
+        };
+        size += data.len() as u64;
+        if size > max_bytes {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::FileTooLarge,
+                "upload exceeds MAX_UPLOAD_BYTES",
+            ));
+        }
+        digests.update(&data);
+        file.write_all(&data).await?;
+    }
+    file.flush().await?;
+    file.sync_all().await?;
+    let (sha1_hex, sha256_hex, blake3_hex) = digests.finish();
+    Ok((size, sha1_hex, sha256_hex, blake3_hex))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::routing::get;
+    use sha1::{Digest, Sha1};
+    use tower::util::ServiceExt;
+
+    #[tokio::test]
+    async fn put_then_get_round_trips_and_updates_sidecar() {
+        let root = dunce::canonicalize("fake_hub").unwrap();
+        let repo_dir = root.join("tests_repo_upload");
+        let _ = tokio::fs::remove_dir_all(&repo_dir).await;
+        tokio::fs::create_dir_all(&repo_dir).await.unwrap();
+        let sc = json!({"version": 1, "entries": [
+            {"path": "keep.txt", "type": "file", "size": 1, "oid": "k"},
+        ]});
+        tokio::fs::write(repo_dir.join(".paths-info.json"), sc.to_string())
+            .await
+            .unwrap();
+        let mut state = AppState::for_tests(root);
+        let app = |state: AppState| {
+            Router::new()
+                .route(
+                    "/{*rest}",
+                    get(crate::resolve::resolve_catchall).put(resolve_put),
+                )
+                .with_state(state)
+        };
+        let put = |app: Router, uri: &str, body: &'static [u8], token: bool| {
+            let mut b = axum::http::Request::builder().method("PUT").uri(uri);
+            if token {
+                b = b.header("Authorization", "Bearer hf_x");
+            }
+            app.oneshot(b.body(Body::from(body)).unwrap())
+        };
+        let uri = "/tests_repo_upload/resolve/main/sub/dir/new.txt";
+
+        let resp = put(app(state.clone()), uri, b"hello", true).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        state.allow_writes = true;
+        let resp = put(app(state.clone()), uri, b"hello", false).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        for bad in [
+            "/tests_repo_upload/resolve/main/.paths-info.json",
+            "/tests_repo_upload/resolve/main/../escape.txt",
+        ] {
+            let resp = put(app(state.clone()), bad, b"x", true).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::FORBIDDEN, "{bad}");
+        }
+
+        put(app(state.clone()), uri, b"first", true).await.unwrap();
+        let resp = put(app(state.clone()), uri, b"hello", true).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let sha1 = hex::encode(Sha1::digest(b"hello"));
+        assert_eq!(resp.headers()["ETag"], format!("\"{sha1}\"").as_str());
+
+        let req = axum::http::Request::builder()
+            .uri(uri)
+            .body(Body::empty())
+            .unwrap();
+        let resp = app(state).oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["ETag"], format!("\"{sha1}\"").as_str());
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"hello");
+
+        let sc: Value = serde_json::from_str(
+            &tokio::fs::read_to_string(repo_dir.join(".paths-info.json"))
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+        let entries = sc["entries"].as_array().unwrap();
+        assert_eq!(entries.len(), 2);
+        let new = entries
+            .iter()
+            .find(|e| e["path"] == "sub/dir/new.txt")
+            .unwrap();
+        assert_eq!(new["size"], 5);
+        assert!(new.get("lfs").is_none());
+        assert_eq!(new["blake3"], blake3::hash(b"hello").to_hex().as_str());
+    }
+
+    #[tokio::test]
+    async fn oversized_upload_is_rejected_and_leaves_nothing_behind() {
+        let root = dunce::canonicalize("fake_hub")
+            .unwrap()
+            .join("tests_upload_cap");
+        let _ = tokio::fs::remove_dir_all(&root).await;
+        let repo_dir = root.join("org").join("repo");
+        tokio::fs::create_dir_all(&repo_dir).await.unwrap();
+        tokio::fs::write(repo_dir.join(".paths-info.json"), r#"{"entries": []}"#)
+            .await
+            .unwrap();
+        let mut state = AppState::for_tests(root.clone());
+        state.allow_writes = true;
+        state.max_upload_bytes = 4;
+        let app = Router::new()
+            .route("/{*rest}", axum::routing::put(resolve_put))
+            .with_state(state);
+        let put = |body: &'static [u8], chunked: bool| {
+            let b = axum::http::Request::builder()
+                .method("PUT")
+                .uri("/org/repo/resolve/main/w.bin")
+                .header("Authorization", "Bearer hf_x");
+            let body = if chunked {
+                // No Content-Length: the cap is enforced while streaming
+                Body::from_stream(async_stream::stream! {
+                    for c in body.chunks(2) {
+                        yield Ok::<_, std::io::Error>(c.to_vec());
+                    }
+                })
+            } else {
+                Body::from(body)
+            };
+            app.clone().oneshot(b.body(body).unwrap())
+        };
+
+        for chunked in [false, true] {
+            let resp = put(b"hello", chunked).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE, "{chunked}");
+        }
+        assert!(!repo_dir.join("w.bin").exists());
+        let mut staged = tokio::fs::read_dir(root.join(UPLOAD_STAGING_DIR))
+            .await
+            .unwrap();
+        assert!(staged.next_entry().await.unwrap().is_none());
+
+        let resp = put(b"abcd", true).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        assert_eq!(std::fs::read(repo_dir.join("w.bin")).unwrap(), b"abcd");
+    }
+}
@@ -0,0 +1,29 @@
+use axum::body::{Body, Bytes};
+use axum::http::HeaderMap;
+use http_body_util::{BodyExt, LengthLimitError, Limited};
+
+use crate::error::AppError;
+
+// Collect a request body of at most `limit` bytes. A declared Content-Length over the
+// limit is rejected without reading; otherwise the read stops as soon as it crosses it.
+pub async fn read_body_limited(
+    headers: &HeaderMap,
+    body: Body,
+    limit: usize,
+) -> Result<Bytes, AppError> {
+    if declared_length(headers).is_some_and(|cl| cl > limit) {
+        return Err(AppError::PayloadTooLarge);
+    }
+    match Limited::new(body, limit).collect().await {
+        Ok(collected) => Ok(collected.to_bytes()),
+        Err(e) if e.is::<LengthLimitError>() => Err(AppError::PayloadTooLarge),
+        Err(_) => Err(AppError::BadRequest("Invalid request body")),
+    }
+}
+
+pub fn declared_length(headers: &HeaderMap) -> Option<usize> {
+    headers
+        .get("content-length")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|s| s.parse::<usize>().ok())
+}
@@ -0,0 +1,30 @@
+// Shared by the server and `fetch_repo` (included there via `#[path]`), so it only depends on
+// external crates.
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+
+// The three digests a sidecar entry records for one file: sha1 (`oid`), sha256 (`lfs.oid`)
+// and `blake3`, fed in a single pass.
+#[derive(Default)]
+pub struct FileDigests {
+    sha1: Sha1,
+    sha256: Sha256,
+    blake3: blake3::Hasher,
+}
+
+impl FileDigests {
+    pub fn update(&mut self, data: &[u8]) {
+        self.sha1.update(data);
+        self.sha256.update(data);
+        self.blake3.update(data);
+    }
+
+    // Hex (sha1, sha256, blake3).
+    pub fn finish(self) -> (String, String, String) {
+        (
+            hex::encode(self.sha1.finalize()),
+            hex::encode(self.sha256.finalize()),
+            hex::encode(self.blake3.finalize().as_bytes()),
+        )
+    }
+}
@@ -1,26 +1,38 @@
+use std::collections::BTreeSet;
 use std::path::Path;
 
 use serde_json::{Value, json};
+use time::OffsetDateTime;
+use time::macros::format_description;
 
-use crate::utils::sidecar::get_sidecar_map;
+use crate::app_state::AppState;
+use crate::error::AppError;
+use crate::utils::repo_json::content_sha;
+use crate::utils::sidecar::load_sidecar_map;
 
 // Fast path: build full file entries from sidecar without hitting filesystem.
-// Returns None if sidecar missing/empty; caller should fall back to walking.
-pub async fn collect_paths_info_from_sidecar(base_dir: &Path) -> Option<Vec<Value>> {
-    let sc_map = get_sidecar_map(base_dir).await.ok()?;
+// A missing sidecar yields no entries; one that does not parse is `SidecarMalformed` and
+// entries without a size are `SidecarMissing`.
+pub async fn collect_paths_info_from_sidecar(
+    state: &AppState,
+    base_dir: &Path,
+) -> Result<Vec<Value>, AppError> {
+    let sc_map = load_sidecar_map(state, base_dir).await?;
     let mut out: Vec<Value> = Vec::with_capacity(sc_map.len());
     for (rel, v) in sc_map.iter() {
         let mut rec = serde_json::Map::new();
         rec.insert("path".to_string(), json!(rel));
         rec.insert("type".to_string(), json!("file"));
         // Require size present (either top-level or lfs.size); otherwise sidecar is incomplete.
-        let Some(size) = v.get("size").and_then(|x| x.as_i64()).or_else(|| {
-            v.get("lfs")
-                .and_then(|x| x.get("size"))
-                .and_then(|x| x.as_i64())
-        }) else {
-            return None;
-        };
+        let size = v
+            .get("size")
+            .and_then(|x| x.as_i64())
+            .or_else(|| {
+                v.get("lfs")
+                    .and_then(|x| x.get("size"))
+                    .and_then(|x| x.as_i64())
+            })
+            .ok_or(AppError::SidecarMissing("Sidecar missing or incomplete"))?;
         rec.insert("size".to_string(), json!(size));
         if let Some(oid) = v.get("oid").and_then(|x| x.as_str()) {
             rec.insert("oid".to_string(), json!(oid));
@@ -37,24 +49,90 @@ pub async fn collect_paths_info_from_sidecar(base_dir: &Path) -> Option<Vec<Valu
         }
         out.push(Value::Object(rec));
     }
-    Some(out)
+    Ok(out)
+}
+
+// Scope flat tree records to `sub` ("" = repo root). Recursive keeps every file below it;
+// otherwise direct file children stay and deeper paths collapse into one `directory` entry
+// per child folder. Paths remain repo-relative; output is sorted by path.
+pub fn scope_tree_records(records: Vec<Value>, sub: &str, recursive: bool) -> Vec<Value> {
+    let prefix = if sub.is_empty() {
+        String::new()
+    } else {
+        format!("{sub}/")
+    };
+    let mut dirs: BTreeSet<String> = BTreeSet::new();
+    let mut out: Vec<Value> = Vec::new();
+    for rec in records {
+        let Some(rest) = rec
+            .get("path")
+            .and_then(|v| v.as_str())
+            .and_then(|p| p.strip_prefix(&prefix))
+        else {
+            continue;
+        };
+        match rest.split_once('/') {
+            Some((child, _)) if !recursive => {
+                dirs.insert(format!("{prefix}{child}"));
+            }
+            _ => out.push(rec),
+        }
+    }
+    out.extend(
+        dirs.into_iter()
+            .map(|d| json!({"path": d, "type": "directory"})),
+    );
+    out.sort_by(|a, b| {
+        let pa = a["path"].as_str().unwrap_or("");
+        let pb = b["path"].as_str().unwrap_or("");
+        pa.cmp(pb)
+    });
+    out
 }
 
-// Fast path for repo siblings/total_size using sidecar only.
-// Returns None when sidecar missing/empty.
-pub async fn siblings_from_sidecar(root: &Path) -> Option<(Vec<Value>, u64)> {
-    let sc_map = get_sidecar_map(root).await.ok()?;
+// Stat each record's file (resolved via secure_join) and set `lastModified` to its real
+// mtime as ISO-8601 UTC; `null` when the file is listed but missing on disk.
+pub async fn add_last_modified(state: &AppState, base_dir: &Path, records: &mut [Value]) {
+    let fmt =
+        format_description!("[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond digits:3]Z");
+    for rec in records.iter_mut() {
+        let Some(rel) = rec.get("path").and_then(|v| v.as_str()) else {
+            continue;
+        };
+        let mut ts = Value::Null;
+        if let Some(p) = crate::utils::paths::secure_join(base_dir, rel, state.allow_symlink_escape)
+            && let Ok(md) = tokio::fs::metadata(&p).await
+            && let Ok(mtime) = md.modified()
+            && let Ok(s) = OffsetDateTime::from(mtime).format(&fmt)
+        {
+            ts = json!(s);
+        }
+        if let Some(obj) = rec.as_object_mut() {
+            obj.insert("lastModified".to_string(), ts);
+        }
+    }
+}
+
+// Fast path for repo siblings/total_size/content sha using sidecar only; errors as for
+// `collect_paths_info_from_sidecar`.
+pub async fn siblings_from_sidecar(
+    state: &AppState,
+    root: &Path,
+) -> Result<(Vec<Value>, u64, Option<String>), AppError> {
+    let sc_map = load_sidecar_map(state, root).await?;
     let mut items: Vec<Value> = Vec::with_capacity(sc_map.len());
     let mut total: u64 = 0;
     for (rel, v) in sc_map.iter() {
         items.push(json!({ "rfilename": rel }));
-        let Some(sz) = v.get("size").and_then(|x| x.as_i64()).or_else(|| {
-            v.get("lfs")
-                .and_then(|x| x.get("size"))
-                .and_then(|x| x.as_i64())
-        }) else {
-            return None;
-        };
+        let sz = v
+            .get("size")
+            .and_then(|x| x.as_i64())
+            .or_else(|| {
+                v.get("lfs")
+                    .and_then(|x| x.get("size"))
+                    .and_then(|x| x.as_i64())
+            })
+            .ok_or(AppError::SidecarMissing("Sidecar missing or incomplete"))?;
         if sz > 0 {
             total = total.saturating_add(sz as u64);
         }
@@ -65,5 +143,6 @@ pub async fn siblings_from_sidecar(root: &Path) -> Option<(Vec<Value>, u64)> {
             .unwrap_or("")
             .cmp(b["rfilename"].as_str().unwrap_or(""))
     });
-    Some((items, total))
+    let sha = content_sha(&sc_map);
+    Ok((items, total, sha))
 }
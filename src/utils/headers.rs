@@ -1,18 +1,97 @@
 use axum::http::{HeaderMap, HeaderValue};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
+
+// RFC 5987 attr-char: everything outside ALPHA / DIGIT / "!#$&+-.^_`|~" is escaped.
+const ATTR_CHAR_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'!')
+    .remove(b'#')
+    .remove(b'$')
+    .remove(b'&')
+    .remove(b'+')
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'^')
+    .remove(b'_')
+    .remove(b'`')
+    .remove(b'|')
+    .remove(b'~');
+
+// Content-Type by file extension; unknown extensions (including .safetensors/.bin) stay
+// application/octet-stream. A sidecar `content_type` overrides this per file.
+pub fn content_type_for(filename: &str) -> &'static str {
+    let name = filename.rsplit('/').next().unwrap_or(filename);
+    let Some((_, ext)) = name.rsplit_once('.') else {
+        return "application/octet-stream";
+    };
+    match ext.to_ascii_lowercase().as_str() {
+        "json" => "application/json",
+        "jsonl" => "application/x-ndjson",
+        "txt" => "text/plain",
+        "md" => "text/markdown",
+        "csv" => "text/csv",
+        "tsv" => "text/tab-separated-values",
+        "html" | "htm" => "text/html",
+        "py" => "text/x-python",
+        "yaml" | "yml" => "application/yaml",
+        "xml" => "application/xml",
+        "pdf" => "application/pdf",
+        "parquet" => "application/vnd.apache.parquet",
+        "gz" => "application/gzip",
+        "zip" => "application/zip",
+        "tar" => "application/x-tar",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "wav" => "audio/wav",
+        "mp3" => "audio/mpeg",
+        "flac" => "audio/flac",
+        "mp4" => "video/mp4",
+        _ => "application/octet-stream",
+    }
+}
+
+// Content-Type for a file stored with Content-Encoding `encoding`: the type of what the
+// client gets after decoding, taken from the stem when the name carries the encoding's own
+// suffix (`data.json.gz` + gzip -> application/json). Other names keep their extension.
+pub fn content_type_for_encoded(filename: &str, encoding: &str) -> &'static str {
+    let suffixes: &[&str] = match encoding {
+        "gzip" => &["gz"],
+        "br" => &["br"],
+        "zstd" => &["zst", "zstd"],
+        "deflate" => &["zz", "deflate"],
+        _ => &[],
+    };
+    match filename.rsplit_once('.') {
+        Some((stem, ext)) if suffixes.iter().any(|s| ext.eq_ignore_ascii_case(s)) => {
+            content_type_for(stem)
+        }
+        _ => content_type_for(filename),
+    }
+}
 
 // Build common headers for file responses.
 // Caller sets size to bytes in body (full size for GET, length for 206, total for HEAD).
-pub fn file_headers_common(revision: &str, size: u64) -> HeaderMap {
+pub fn file_headers_common(
+    filename: &str,
+    revision: &str,
+    size: u64,
+    cache_max_age: u64,
+) -> HeaderMap {
     let mut headers = HeaderMap::new();
+    set_cache_headers(&mut headers, cache_max_age);
     headers.insert(
         "Content-Length",
         HeaderValue::from_str(&size.to_string()).unwrap(),
     );
     headers.insert(
         "Content-Type",
-        HeaderValue::from_static("application/octet-stream"),
+        HeaderValue::from_static(content_type_for(filename)),
     );
-    headers.insert("Accept-Ranges", HeaderValue::from_static("bytes"));
+    // An empty file has no byte ranges to offer
+    let accept_ranges = if size == 0 { "none" } else { "bytes" };
+    headers.insert("Accept-Ranges", HeaderValue::from_static(accept_ranges));
     headers.insert(
         "x-repo-commit",
         HeaderValue::from_str(revision).unwrap_or(HeaderValue::from_static("-")),
@@ -24,9 +103,123 @@ pub fn file_headers_common(revision: &str, size: u64) -> HeaderMap {
     headers
 }
 
+// RESOLVE_CACHE_MAX_AGE: resolve URLs name a revision, so their bodies are cacheable as
+// immutable; 0 asks caches to revalidate every time. `Age: 0` since we are the origin.
+pub fn set_cache_headers(headers: &mut HeaderMap, max_age: u64) {
+    let value = if max_age == 0 {
+        HeaderValue::from_static("no-cache")
+    } else {
+        HeaderValue::from_str(&format!("public, max-age={max_age}, immutable")).unwrap()
+    };
+    headers.insert("Cache-Control", value);
+    headers.insert("Age", HeaderValue::from_static("0"));
+}
+
 pub fn set_content_range(headers: &mut HeaderMap, start: u64, end: u64, total: u64) {
     headers.insert(
         "Content-Range",
         HeaderValue::from_str(&format!("bytes {start}-{end}/{total}")).unwrap(),
     );
 }
+
+// Content-Disposition from the last segment of `filename`. Non-ASCII names get an
+// ASCII fallback in `filename` plus the exact name in `filename*=UTF-8''...`.
+pub fn set_content_disposition(headers: &mut HeaderMap, filename: &str) {
+    let name = filename.rsplit('/').next().unwrap_or(filename);
+    if name.is_empty() {
+        return;
+    }
+    let fallback: String = name
+        .chars()
+        .map(|c| match c {
+            '"' | '\\' => format!("\\{c}"),
+            c if c.is_ascii() && !c.is_ascii_control() => c.to_string(),
+            _ => "_".to_string(),
+        })
+        .collect();
+    let value = if name.is_ascii() {
+        format!("attachment; filename=\"{fallback}\"")
+    } else {
+        let encoded = utf8_percent_encode(name, ATTR_CHAR_ENCODE_SET);
+        format!("attachment; filename=\"{fallback}\"; filename*=UTF-8''{encoded}")
+    };
+    if let Ok(hv) = HeaderValue::from_str(&value) {
+        headers.insert("Content-Disposition", hv);
+    }
+}
+
+// Absolute base URL for hrefs, honoring a fronting proxy's X-Forwarded-Proto.
+pub fn request_base_url(headers: &HeaderMap) -> String {
+    let host = headers
+        .get("host")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("localhost");
+    let scheme = headers
+        .get("x-forwarded-proto")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("http");
+    format!("{scheme}://{host}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn disposition(filename: &str) -> String {
+        let mut headers = HeaderMap::new();
+        set_content_disposition(&mut headers, filename);
+        headers["Content-Disposition"].to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn content_disposition_ascii_and_quoting() {
+        assert_eq!(
+            disposition("sub/dir/model.safetensors"),
+            "attachment; filename=\"model.safetensors\""
+        );
+        assert_eq!(
+            disposition("we\"ird.bin"),
+            "attachment; filename=\"we\\\"ird.bin\""
+        );
+    }
+
+    #[test]
+    fn content_type_by_extension() {
+        assert_eq!(content_type_for("config.json"), "application/json");
+        assert_eq!(content_type_for("docs/README.MD"), "text/markdown");
+        assert_eq!(
+            content_type_for("a.b/model.safetensors"),
+            "application/octet-stream"
+        );
+        assert_eq!(content_type_for("Makefile"), "application/octet-stream");
+        assert_eq!(
+            content_type_for_encoded("data/rows.json.gz", "gzip"),
+            "application/json"
+        );
+        assert_eq!(
+            content_type_for_encoded("notes.md.zst", "zstd"),
+            "text/markdown"
+        );
+        // The suffix must belong to the declared encoding
+        assert_eq!(
+            content_type_for_encoded("rows.json", "gzip"),
+            "application/json"
+        );
+        assert_eq!(
+            content_type_for_encoded("archive.gz", "gzip"),
+            "application/octet-stream"
+        );
+        assert_eq!(
+            content_type_for("dir.json/weights"),
+            "application/octet-stream"
+        );
+    }
+
+    #[test]
+    fn content_disposition_non_ascii() {
+        assert_eq!(
+            disposition("docs/模型.md"),
+            "attachment; filename=\"__.md\"; filename*=UTF-8''%E6%A8%A1%E5%9E%8B.md"
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::BTreeMap;
+
+use axum::http::HeaderMap;
+use percent_encoding::{AsciiSet, CONTROLS, utf8_percent_encode};
+use serde_json::Value;
+
+// Escape characters that would break a path inside an href attribute.
+const HREF_ENCODE_SET: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'%')
+    .add(b'?')
+    .add(b'#')
+    .add(b'"')
+    .add(b'\'')
+    .add(b'<')
+    .add(b'>');
+
+// True when the Accept header ranks text/html strictly above application/json.
+// Missing Accept (or a tie) means JSON.
+pub fn prefers_html(headers: &HeaderMap) -> bool {
+    let Some(accept) = headers.get("accept").and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+    let mut html_q: f32 = 0.0;
+    let mut json_q: f32 = 0.0;
+    for part in accept.split(',') {
+        let mut it = part.split(';');
+        let mime = it.next().unwrap_or("").trim().to_ascii_lowercase();
+        let q = it
+            .filter_map(|p| p.trim().strip_prefix("q="))
+            .find_map(|v| v.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        match mime.as_str() {
+            "text/html" => html_q = html_q.max(q),
+            "application/json" => json_q = json_q.max(q),
+            "*/*" | "application/*" => json_q = json_q.max(q * 0.999),
+            _ => {}
+        }
+    }
+    html_q > json_q
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Render the immediate children of `dir` (from paths-info file records) as an HTML index.
+// `base_href` is the resolve prefix, e.g. "/org/repo/resolve/main".
+pub fn render_html_listing(base_href: &str, dir: &str, records: &[Value]) -> String {
+    let prefix = if dir.is_empty() {
+        String::new()
+    } else {
+        format!("{dir}/")
+    };
+    // name -> Some(size) for files, None for sub-directories
+    let mut children: BTreeMap<String, Option<u64>> = BTreeMap::new();
+    for rec in records {
+        if rec.get("type").and_then(|v| v.as_str()) != Some("file") {
+            continue;
+        }
+        let Some(rest) = rec
+            .get("path")
+            .and_then(|v| v.as_str())
+            .and_then(|p| p.strip_prefix(&prefix))
+        else {
+            continue;
+        };
+        match rest.split_once('/') {
+            Some((sub, _)) => {
+                children.entry(sub.to_string()).or_insert(None);
+            }
+            None => {
+                let size = rec.get("size").and_then(|v| v.as_u64()).unwrap_or(0);
+                children.insert(rest.to_string(), Some(size));
+            }
+        }
+    }
+
+    let title = html_escape(&format!("Index of /{prefix}"));
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{title}</title></head>\n<body><h1>{title}</h1>\n<table>\n"
+    );
+    if !dir.is_empty() {
+        let parent = dir.rsplit_once('/').map(|(p, _)| p).unwrap_or("");
+        let href = if parent.is_empty() {
+            format!("{base_href}/")
+        } else {
+            format!("{base_href}/{parent}/")
+        };
+        out.push_str(&format!(
+            "<tr><td><a href=\"{}\">../</a></td><td></td></tr>\n",
+            utf8_percent_encode(&href, HREF_ENCODE_SET)
+        ));
+    }
+    for (name, size) in &children {
+        let (label, href, size_cell) = match size {
+            Some(sz) => (
+                name.clone(),
+                format!("{base_href}/{prefix}{name}"),
+                sz.to_string(),
+            ),
+            None => (
+                format!("{name}/"),
+                format!("{base_href}/{prefix}{name}/"),
+                String::new(),
+            ),
+        };
+        out.push_str(&format!(
+            "<tr><td><a href=\"{}\">{}</a></td><td>{}</td></tr>\n",
+            utf8_percent_encode(&href, HREF_ENCODE_SET),
+            html_escape(&label),
+            size_cell
+        ));
+    }
+    out.push_str("</table>\n</body></html>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use serde_json::json;
+
+    #[test]
+    fn accept_negotiation() {
+        let mut h = HeaderMap::new();
+        assert!(!prefers_html(&h));
+        h.insert(
+            "accept",
+            HeaderValue::from_static("text/html,application/xhtml+xml,*/*;q=0.8"),
+        );
+        assert!(prefers_html(&h));
+        h.insert("accept", HeaderValue::from_static("application/json"));
+        assert!(!prefers_html(&h));
+        h.insert("accept", HeaderValue::from_static("*/*"));
+        assert!(!prefers_html(&h));
+    }
+
+    #[test]
+    fn lists_immediate_children() {
+        let recs = vec![
+            json!({"path": "sub", "type": "directory"}),
+            json!({"path": "sub/a b.txt", "type": "file", "size": 3}),
+            json!({"path": "sub/deep/x.bin", "type": "file", "size": 9}),
+        ];
+        let html = render_html_listing("/org/repo/resolve/main", "sub", &recs);
+        assert!(
+            html.contains("href=\"/org/repo/resolve/main/sub/a%20b.txt\">a b.txt</a></td><td>3<")
+        );
+        assert!(html.contains("href=\"/org/repo/resolve/main/sub/deep/\">deep/</a>"));
+        assert!(html.contains("href=\"/org/repo/resolve/main/\">../</a>"));
+        assert!(!html.contains("x.bin"));
+    }
+}
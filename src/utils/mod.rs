@@ -1,5 +1,11 @@
+pub mod body;
+pub mod digest;
 pub mod fs_walk;
 pub mod headers;
+pub mod listing;
 pub mod paths;
+pub mod prng;
+pub mod refs;
 pub mod repo_json;
+pub mod safetensors;
 pub mod sidecar;
@@ -25,22 +25,100 @@ pub fn normalize_rel(rel: &str) -> Option<PathBuf> {
     Some(out)
 }
 
+// Percent-decode a raw URL path one segment at a time. None when a segment decodes to a `/`
+// (an encoded `%2F` must not add a separator) or to invalid UTF-8.
+pub fn decode_path_segments(raw: &str) -> Option<String> {
+    let mut out = Vec::new();
+    for seg in raw.split('/') {
+        let decoded = percent_encoding::percent_decode_str(seg)
+            .decode_utf8()
+            .ok()?;
+        if decoded.contains('/') {
+            return None;
+        }
+        out.push(decoded);
+    }
+    Some(out.join("/"))
+}
+
+// Control characters (NUL included) never name a repo file; on Unix neither does a raw
+// backslash, which `normalize_rel` would otherwise quietly turn into a separator.
+pub fn has_invalid_path_chars(path: &str) -> bool {
+    path.chars()
+        .any(|c| (c as u32) < 0x20 || (cfg!(unix) && c == '\\'))
+}
+
 // Join base + relative and ensure the result stays under base.
-pub fn secure_join(base: &Path, rel: &str) -> Option<PathBuf> {
-    let base_abs = dunce::canonicalize(base).ok()?;
-    let rel_norm = normalize_rel(rel)?;
+// The target need not exist: the deepest existing ancestor is canonicalized (resolving any
+// symlinks) and the remaining normalized segments are appended before the containment check.
+// `allow_symlink_escape` is AppState's ALLOW_SYMLINK_ESCAPE, see `try_secure_join`.
+pub fn secure_join(base: &Path, rel: &str, allow_symlink_escape: bool) -> Option<PathBuf> {
+    try_secure_join(base, rel, allow_symlink_escape).ok()
+}
+
+// Why `try_secure_join` refused a path: callers answer 403 for `Escapes`, 404 otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinError {
+    // base itself does not exist (or cannot be resolved)
+    BaseMissing,
+    // rel normalizes, or resolves through a symlink, to somewhere outside base
+    Escapes,
+}
+
+// Same as `secure_join`, but reports whether the rejection was a traversal attempt.
+// With `allow_symlink_escape`, only the lexical join (before symlinks are resolved) has to stay
+// under base; a symlink that lives under base may then point anywhere, and the un-resolved
+// path is returned. Security trade-off: any symlink placed under the root, including one
+// inside a repo, exposes its target to clients. `..` segments are still rejected lexically.
+pub fn try_secure_join(
+    base: &Path,
+    rel: &str,
+    allow_symlink_escape: bool,
+) -> Result<PathBuf, JoinError> {
+    let base_abs = dunce::canonicalize(base).map_err(|_| JoinError::BaseMissing)?;
+    let rel_norm = normalize_rel(rel).ok_or(JoinError::Escapes)?;
     let joined = base_abs.join(&rel_norm);
-    let joined_can = dunce::canonicalize(&joined).unwrap_or(joined);
+    let joined_can = canonicalize_existing_prefix(&joined).ok_or(JoinError::BaseMissing)?;
     if joined_can.starts_with(&base_abs) {
-        Some(joined_can)
+        Ok(joined_can)
+    } else if allow_symlink_escape && joined.starts_with(&base_abs) {
+        Ok(joined)
     } else {
-        None
+        Err(JoinError::Escapes)
+    }
+}
+
+// Canonicalize the longest existing prefix of `p` and re-append the missing tail.
+fn canonicalize_existing_prefix(p: &Path) -> Option<PathBuf> {
+    let mut existing = p;
+    let mut tail: Vec<&std::ffi::OsStr> = Vec::new();
+    loop {
+        if let Ok(can) = dunce::canonicalize(existing) {
+            let mut out = can;
+            for seg in tail.iter().rev() {
+                out.push(seg);
+            }
+            return Some(out);
+        }
+        tail.push(existing.file_name()?);
+        existing = existing.parent()?;
     }
 }
 
+// Metadata files the server reads but never serves as repo content.
+const SIDECAR_NAMES: &[&str] = &[
+    ".paths-info.json",
+    ".paths-info.json.gz",
+    ".refs.json",
+    ".commits.json",
+    ".repo-meta.json",
+];
+
 pub fn is_sidecar_path(p: &str) -> bool {
     let p = Path::new(p);
-    p.file_name().and_then(|s| s.to_str()) == Some(".paths-info.json")
+    p.file_name()
+        .and_then(|s| s.to_str())
+        .is_some_and(|name| SIDECAR_NAMES.contains(&name))
 }
 
 #[cfg(test)]
@@ -59,16 +137,66 @@ mod tests {
     #[test]
     fn secure_join_rejects_escape() {
         let base = Path::new(".");
-        let ok = secure_join(base, "src/main.rs");
+        let ok = secure_join(base, "src/main.rs", false);
         assert!(ok.is_some());
-        let bad = secure_join(base, "../..//etc/passwd");
+        let bad = secure_join(base, "../..//etc/passwd", false);
         assert!(bad.is_none());
     }
 
+    #[test]
+    fn secure_join_allows_missing_child() {
+        let base = Path::new(".");
+        let base_abs = dunce::canonicalize(base).unwrap();
+        let p = secure_join(base, "src/not-yet/created.bin", false).unwrap();
+        assert_eq!(p, base_abs.join("src").join("not-yet").join("created.bin"));
+        assert!(secure_join(base, "not-yet/../../escape", false).is_none());
+    }
+
+    #[test]
+    fn try_secure_join_classifies_rejections() {
+        let base = Path::new(".");
+        assert_eq!(
+            try_secure_join(base, "../../etc/passwd", false),
+            Err(JoinError::Escapes)
+        );
+        assert_eq!(
+            try_secure_join(Path::new("no-such-base-dir"), "x", false),
+            Err(JoinError::BaseMissing)
+        );
+        assert!(try_secure_join(base, "src/missing.rs", false).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_escape_is_opt_in() {
+        let base = PathBuf::from("fake_hub").join("tests_symlink_root");
+        let outside = PathBuf::from("fake_hub").join("tests_symlink_shared");
+        std::fs::create_dir_all(&base).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(outside.join("w.bin"), b"w").unwrap();
+        let link = base.join("linked-model");
+        let _ = std::fs::remove_file(&link);
+        std::os::unix::fs::symlink(dunce::canonicalize(&outside).unwrap(), &link).unwrap();
+
+        assert_eq!(
+            try_secure_join(&base, "linked-model/w.bin", false),
+            Err(JoinError::Escapes)
+        );
+        let p = try_secure_join(&base, "linked-model/w.bin", true).unwrap();
+        assert_eq!(std::fs::read(&p).unwrap(), b"w");
+        assert_eq!(
+            try_secure_join(&base, "linked-model/../../x", true),
+            Err(JoinError::Escapes)
+        );
+    }
+
     #[test]
     fn detect_sidecar_name() {
         assert!(is_sidecar_path(".paths-info.json"));
         assert!(is_sidecar_path("foo/.paths-info.json"));
+        assert!(is_sidecar_path(".refs.json"));
+        assert!(is_sidecar_path(".repo-meta.json"));
+        assert!(is_sidecar_path("org/m/.repo-meta.json"));
         assert!(!is_sidecar_path("paths-info.json"));
     }
 }
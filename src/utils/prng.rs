@@ -0,0 +1,14 @@
+// Shared by the server and `fetch_repo` (included there via `#[path]`), so it only depends on
+// std.
+
+// splitmix64: fast, well-distributed, not cryptographic. Advances `state` and returns the
+// next output.
+pub fn splitmix64_next(state: &mut u64) -> u64 {
+    let mut z = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    *state = z;
+    z ^= z >> 30;
+    z = z.wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z ^= z >> 27;
+    z = z.wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
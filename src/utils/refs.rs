@@ -0,0 +1,201 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use serde_json::{Value, json};
+use tokio::fs;
+
+use crate::caches::REFS_CACHE;
+use crate::utils::repo_json::fake_sha;
+
+// Parsed `.refs.json` documents kept in REFS_CACHE; one per repo that has the file.
+const REFS_CACHE_CAP: usize = 1024;
+
+// Build the refs document for a repo directory.
+// `.refs.json` (if present) overrides everything; otherwise synthesize a lone `main`
+// branch. Returns None only when `.refs.json` exists but is malformed.
+pub async fn refs_from_dir(repo_dir: &Path) -> Option<Value> {
+    if repo_dir.join(".refs.json").is_file() {
+        let parsed = cached_refs_json(repo_dir).await?;
+        let obj = parsed.as_object()?;
+        let mut out = serde_json::Map::new();
+        for key in ["branches", "tags", "converts"] {
+            let v = obj.get(key).cloned().unwrap_or_else(|| json!([]));
+            if !v.is_array() {
+                return None;
+            }
+            out.insert(key.to_string(), v);
+        }
+        if let Some(prs) = obj.get("pullRequests") {
+            out.insert("pullRequests".to_string(), prs.clone());
+        }
+        return Some(Value::Object(out));
+    }
+
+    Some(json!({
+        "branches": [{
+            "name": "main",
+            "ref": "refs/heads/main",
+            "targetCommit": fake_sha(None),
+        }],
+        "tags": [],
+        "converts": [],
+    }))
+}
+
+// `.refs.json` parsed at most once per (path, mtime, size); every resolve looks it up.
+async fn cached_refs_json(repo_dir: &Path) -> Option<Arc<Value>> {
+    let path = repo_dir.join(".refs.json");
+    let md = fs::metadata(&path).await.ok()?;
+    let mtime = md
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let key = (path, mtime, md.len());
+    if let Some(hit) = REFS_CACHE.read().await.inner.get(&key) {
+        return hit.doc.clone();
+    }
+    let doc = fs::read(&key.0)
+        .await
+        .ok()
+        .and_then(|data| serde_json::from_slice::<Value>(&data).ok())
+        .map(Arc::new);
+    REFS_CACHE
+        .write()
+        .await
+        .insert_bounded(key, doc.clone(), REFS_CACHE_CAP);
+    doc
+}
+
+// Target commit of the ref named `revision` (short name or full `refs/...` path) in
+// `.refs.json`. None when the file is absent, malformed, or lists no such ref.
+pub async fn ref_target_commit(repo_dir: &Path, revision: &str) -> Option<String> {
+    let parsed = cached_refs_json(repo_dir).await?;
+    ["branches", "tags", "converts"]
+        .iter()
+        .filter_map(|key| parsed.get(*key)?.as_array())
+        .flatten()
+        .find(|r| {
+            r.get("name").and_then(|v| v.as_str()) == Some(revision)
+                || r.get("ref").and_then(|v| v.as_str()) == Some(revision)
+        })
+        .and_then(|r| r.get("targetCommit")?.as_str())
+        .map(str::to_string)
+}
+
+// Commit history for `revision`. `.commits.json` (a JSON array of commit objects)
+// overrides the default single synthesized commit.
+// Returns None only when `.commits.json` exists but is malformed.
+pub async fn commits_from_dir(repo_dir: &Path, revision: &str, author: &str) -> Option<Vec<Value>> {
+    let sidecar = repo_dir.join(".commits.json");
+    if sidecar.is_file() {
+        let data = fs::read(&sidecar).await.ok()?;
+        let parsed: Value = serde_json::from_slice(&data).ok()?;
+        let arr = parsed.as_array()?;
+        if !arr.iter().all(|c| c.is_object()) {
+            return None;
+        }
+        return Some(arr.clone());
+    }
+    Some(vec![json!({
+        "id": fake_sha(Some(revision)),
+        "title": "Initial commit",
+        "message": "",
+        "authors": [{"user": author}],
+        "date": "1970-01-01T00:00:00.000Z",
+    })])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn synthesized_and_sidecar_refs() {
+        let dir = std::path::PathBuf::from("fake_hub/tests_repo_refs");
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+        // Directories never become tags; only `.refs.json` declares them
+        tokio::fs::create_dir_all(dir.join(".tags/v1.0"))
+            .await
+            .unwrap();
+
+        let v = refs_from_dir(&dir).await.unwrap();
+        assert_eq!(v["branches"][0]["name"], "main");
+        assert_eq!(v["branches"][0]["targetCommit"], fake_sha(None));
+        assert!(v["tags"].as_array().unwrap().is_empty());
+        assert!(v["converts"].as_array().unwrap().is_empty());
+
+        let sc =
+            json!({"branches": [{"name": "dev", "ref": "refs/heads/dev", "targetCommit": "abc"}]});
+        tokio::fs::write(dir.join(".refs.json"), sc.to_string())
+            .await
+            .unwrap();
+        let v = refs_from_dir(&dir).await.unwrap();
+        assert_eq!(v["branches"][0]["name"], "dev");
+        assert!(v["tags"].as_array().unwrap().is_empty());
+
+        tokio::fs::write(dir.join(".refs.json"), b"{not json")
+            .await
+            .unwrap();
+        assert!(refs_from_dir(&dir).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn ref_target_commit_follows_refs_json_edits() {
+        let dir = std::path::PathBuf::from("fake_hub/tests_repo_ref_target");
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        assert!(ref_target_commit(&dir, "v1").await.is_none());
+
+        let refs = |sha: &str| {
+            json!({"tags": [{"name": "v1", "ref": "refs/tags/v1", "targetCommit": sha}]})
+                .to_string()
+        };
+        tokio::fs::write(dir.join(".refs.json"), refs("abc"))
+            .await
+            .unwrap();
+        assert_eq!(ref_target_commit(&dir, "v1").await.as_deref(), Some("abc"));
+        assert_eq!(
+            ref_target_commit(&dir, "refs/tags/v1").await.as_deref(),
+            Some("abc")
+        );
+        // A rewrite changes the cache key (size here), so the new target is seen
+        tokio::fs::write(dir.join(".refs.json"), refs("abcdef"))
+            .await
+            .unwrap();
+        assert_eq!(
+            ref_target_commit(&dir, "v1").await.as_deref(),
+            Some("abcdef")
+        );
+    }
+
+    #[tokio::test]
+    async fn synthesized_and_sidecar_commits() {
+        let dir = std::path::PathBuf::from("fake_hub/tests_repo_commits");
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+
+        let v = commits_from_dir(&dir, "main", "local-user").await.unwrap();
+        assert_eq!(v.len(), 1);
+        assert_eq!(v[0]["id"], fake_sha(Some("main")));
+        assert_eq!(v[0]["date"], "1970-01-01T00:00:00.000Z");
+        assert_eq!(v[0]["authors"][0]["user"], "local-user");
+
+        let sc = json!([
+            {"id": "c2", "title": "second", "message": "", "authors": [], "date": "2024-01-02T00:00:00.000Z"},
+            {"id": "c1", "title": "first", "message": "", "authors": [], "date": "2024-01-01T00:00:00.000Z"},
+        ]);
+        tokio::fs::write(dir.join(".commits.json"), sc.to_string())
+            .await
+            .unwrap();
+        let v = commits_from_dir(&dir, "main", "local-user").await.unwrap();
+        assert_eq!(v.len(), 2);
+        assert_eq!(v[0]["id"], "c2");
+
+        tokio::fs::write(dir.join(".commits.json"), b"{}")
+            .await
+            .unwrap();
+        assert!(commits_from_dir(&dir, "main", "local-user").await.is_none());
+    }
+}
@@ -1,45 +1,228 @@
-use serde_json::{Value, json};
+use std::path::Path;
+use std::sync::Arc;
+
+use serde_json::{Map, Value, json};
+use sha1::{Digest, Sha1};
+
+use crate::caches::{REPO_META_CACHE, SidecarMap};
+
+// Parsed `.repo-meta.json` files kept in REPO_META_CACHE; one per repo that has the file.
+const REPO_META_CACHE_CAP: usize = 4096;
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum RepoKind {
     Model,
     Dataset,
+    Space,
 }
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
 pub enum RepoJsonFlavor {
     Minimal,
+    #[default]
     Rich,
 }
 
+// Fields `.repo-meta.json` may override in the generated repo JSON.
+const META_OVERRIDE_KEYS: [&str; 5] = ["pipeline_tag", "library_name", "tags", "gated", "private"];
+
+// Per-repo settings from the optional `.repo-meta.json`: `flavor` ("rich"|"minimal") fixes
+// the JSON shape, any of META_OVERRIDE_KEYS replace the generated values, and `author`
+// (with `pipeline_tag`/`library_name`) feeds the synthesized fields via `defaults`.
+#[derive(Clone, Debug, Default)]
+pub struct RepoMeta {
+    pub flavor: RepoJsonFlavor,
+    overrides: Map<String, Value>,
+    author: Option<String>,
+}
+
+// Synthesized author/pipeline/library fields (FAKE_HUB_AUTHOR, FAKE_HUB_PIPELINE_TAG,
+// FAKE_HUB_LIBRARY); unset fields keep the built-in GPT-2 flavoured values.
+#[derive(Clone, Debug, Default)]
+pub struct RepoDefaults {
+    pub author: Option<String>,
+    pub pipeline_tag: Option<String>,
+    pub library_name: Option<String>,
+}
+
+impl RepoMeta {
+    // `gated` is false, "auto" or "manual"; `true` in the file is read as "auto".
+    pub fn is_gated(&self) -> bool {
+        matches!(self.overrides.get("gated"), Some(Value::String(_)))
+    }
+
+    pub fn is_private(&self) -> bool {
+        self.overrides.get("private") == Some(&Value::Bool(true))
+    }
+
+    // Per-repo values from `.repo-meta.json` win over the global ones.
+    pub fn defaults(&self, global: &RepoDefaults) -> RepoDefaults {
+        let field = |k: &str| {
+            self.overrides
+                .get(k)
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+        };
+        RepoDefaults {
+            author: self.author.clone().or_else(|| global.author.clone()),
+            pipeline_tag: field("pipeline_tag").or_else(|| global.pipeline_tag.clone()),
+            library_name: field("library_name").or_else(|| global.library_name.clone()),
+        }
+    }
+
+    pub fn apply(&self, val: &mut Value) {
+        if let Some(obj) = val.as_object_mut() {
+            for (k, v) in &self.overrides {
+                obj.insert(k.clone(), v.clone());
+            }
+        }
+    }
+}
+
+// Default meta when the file is absent; None only when it exists but is malformed. The file
+// is parsed at most once per (path, mtime, size), since every repo request consults it.
+pub async fn read_repo_meta(repo_dir: &Path) -> Option<RepoMeta> {
+    let path = repo_dir.join(".repo-meta.json");
+    let md = match tokio::fs::metadata(&path).await {
+        Ok(md) => md,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Some(RepoMeta::default()),
+        Err(_) => return None,
+    };
+    let mtime = md
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let key = (path, mtime, md.len());
+    if let Some(hit) = REPO_META_CACHE.read().await.inner.get(&key) {
+        return hit.doc.as_deref().cloned();
+    }
+    let meta = tokio::fs::read(&key.0)
+        .await
+        .ok()
+        .and_then(|data| parse_repo_meta(&data))
+        .map(Arc::new);
+    REPO_META_CACHE
+        .write()
+        .await
+        .insert_bounded(key, meta.clone(), REPO_META_CACHE_CAP);
+    meta.as_deref().cloned()
+}
+
+fn parse_repo_meta(data: &[u8]) -> Option<RepoMeta> {
+    let parsed: Value = serde_json::from_slice(data).ok()?;
+    let obj = parsed.as_object()?;
+    let flavor = match obj.get("flavor") {
+        None => RepoJsonFlavor::default(),
+        Some(v) => match v.as_str()? {
+            "rich" => RepoJsonFlavor::Rich,
+            "minimal" => RepoJsonFlavor::Minimal,
+            _ => return None,
+        },
+    };
+    let mut overrides: Map<String, Value> = META_OVERRIDE_KEYS
+        .iter()
+        .filter_map(|k| Some((k.to_string(), obj.get(*k)?.clone())))
+        .collect();
+    match overrides.get("gated") {
+        None | Some(Value::Bool(false)) => {}
+        Some(Value::Bool(true)) => {
+            overrides.insert("gated".to_string(), json!("auto"));
+        }
+        Some(Value::String(s)) if s == "auto" || s == "manual" => {}
+        Some(_) => return None,
+    }
+    if overrides.get("private").is_some_and(|v| !v.is_boolean()) {
+        return None;
+    }
+    let author = match obj.get("author") {
+        None => None,
+        Some(v) => Some(v.as_str()?.to_string()),
+    };
+    Some(RepoMeta {
+        flavor,
+        overrides,
+        author,
+    })
+}
+
 pub fn fake_sha(revision: Option<&str>) -> String {
     revision
         .map(|r| format!("fakesha-{r}"))
         .unwrap_or_else(|| "fakesha1234567890".to_string())
 }
 
+// Git-like 40-hex sha folded from the sorted sidecar (path, oid, size) tuples, so it
+// changes whenever a file changes. None for an empty sidecar.
+pub fn content_sha(sc_map: &SidecarMap) -> Option<String> {
+    if sc_map.is_empty() {
+        return None;
+    }
+    let mut keys: Vec<&String> = sc_map.keys().collect();
+    keys.sort();
+    let mut hasher = Sha1::new();
+    for rel in keys {
+        let v = &sc_map[rel];
+        let oid = v
+            .get("lfs")
+            .and_then(|l| l.get("oid"))
+            .or_else(|| v.get("oid"))
+            .and_then(|x| x.as_str())
+            .unwrap_or("");
+        let size = v
+            .get("size")
+            .or_else(|| v.get("lfs").and_then(|l| l.get("size")))
+            .and_then(|x| x.as_i64())
+            .unwrap_or(0);
+        hasher.update(format!("{rel}\0{oid}\0{size}\n").as_bytes());
+    }
+    Some(hex::encode(hasher.finalize()))
+}
+
+// `content_sha` wins over the revision-based placeholder when present.
+pub fn repo_sha(revision: Option<&str>, content_sha: Option<&str>) -> String {
+    content_sha
+        .map(str::to_string)
+        .unwrap_or_else(|| fake_sha(revision))
+}
+
+// `defaults` None (or unset fields) keeps today's synthesized author/pipeline/library values.
 pub fn build_repo_json(
     kind: RepoKind,
     repo_id: &str,
-    revision: Option<&str>,
+    sha: &str,
     siblings: &[Value],
     total_size: u64,
     flavor: RepoJsonFlavor,
+    defaults: Option<&RepoDefaults>,
 ) -> Value {
-    let sha = fake_sha(revision);
+    let defaults = defaults.cloned().unwrap_or_default();
+    let author = defaults.author.as_deref().unwrap_or("local-user");
+    let pipeline_tag = defaults
+        .pipeline_tag
+        .as_deref()
+        .unwrap_or("text-generation");
+    let library_name = defaults.library_name.as_deref().unwrap_or("transformers");
+    // The gpt2 tag only describes the built-in defaults
+    let model_tags = if defaults.pipeline_tag.is_none() && defaults.library_name.is_none() {
+        json!(["transformers", "gpt2", "text-generation"])
+    } else {
+        json!([library_name, pipeline_tag])
+    };
     match (kind, flavor) {
         (RepoKind::Model, RepoJsonFlavor::Rich) => {
             json!({
                 "_id": format!("local/{}", repo_id),
                 "id": repo_id,
                 "private": false,
-                "pipeline_tag": "text-generation",
-                "library_name": "transformers",
-                "tags": ["transformers", "gpt2", "text-generation"],
+                "pipeline_tag": pipeline_tag,
+                "library_name": library_name,
+                "tags": model_tags,
                 "downloads": 0,
                 "likes": 0,
                 "modelId": repo_id,
-                "author": "local-user",
+                "author": author,
                 "sha": sha,
                 "lastModified": "1970-01-01T00:00:00.000Z",
                 "createdAt": "1970-01-01T00:00:00.000Z",
@@ -51,7 +234,7 @@ pub fn build_repo_json(
                 "cardData": {"language": "en", "tags": ["example"], "license": "mit"},
                 "transformersInfo": {
                     "auto_model": "AutoModelForCausalLM",
-                    "pipeline_tag": "text-generation",
+                    "pipeline_tag": pipeline_tag,
                     "processor": "AutoTokenizer",
                 },
                 "safetensors": {"parameters": {"F32": 0}, "total": 0},
@@ -91,7 +274,7 @@ pub fn build_repo_json(
                 "tags": ["dataset"],
                 "downloads": 0,
                 "likes": 0,
-                "author": "local-user",
+                "author": author,
                 "sha": sha,
                 "lastModified": "1970-01-01T00:00:00.000Z",
                 "createdAt": "1970-01-01T00:00:00.000Z",
@@ -102,6 +285,38 @@ pub fn build_repo_json(
                 "usedStorage": (total_size as i64),
             })
         }
+        (RepoKind::Space, RepoJsonFlavor::Minimal) => {
+            json!({
+                "_id": format!("local/spaces/{}", repo_id),
+                "id": repo_id,
+                "private": false,
+                "sdk": "gradio",
+                "sha": sha,
+                "siblings": siblings,
+                "usedStorage": (total_size as i64),
+            })
+        }
+        (RepoKind::Space, RepoJsonFlavor::Rich) => {
+            json!({
+                "_id": format!("local/spaces/{}", repo_id),
+                "id": repo_id,
+                "private": false,
+                "tags": ["gradio"],
+                "likes": 0,
+                "author": author,
+                "sha": sha,
+                "lastModified": "1970-01-01T00:00:00.000Z",
+                "createdAt": "1970-01-01T00:00:00.000Z",
+                "disabled": false,
+                "sdk": "gradio",
+                "runtime": {"stage": "RUNNING", "hardware": {"current": "cpu-basic", "requested": "cpu-basic"}},
+                "cardData": {"sdk": "gradio", "license": "mit"},
+                "siblings": siblings,
+                "models": [],
+                "datasets": [],
+                "usedStorage": (total_size as i64),
+            })
+        }
     }
 }
 
@@ -114,16 +329,133 @@ mod tests {
         let v = build_repo_json(
             RepoKind::Model,
             "foo/bar",
-            Some("main"),
+            &fake_sha(Some("main")),
             &[],
             123,
             RepoJsonFlavor::Minimal,
+            None,
         );
         assert_eq!(v["id"], "foo/bar");
         assert_eq!(v["modelId"], "foo/bar");
         assert_eq!(v["_id"], "local/foo/bar");
         assert_eq!(v["usedStorage"], 123);
         assert!(v.get("model-index").is_some());
+        assert_eq!(v["sha"], fake_sha(Some("main")));
+    }
+
+    #[test]
+    fn content_sha_tracks_sidecar_changes() {
+        let mut m = std::collections::HashMap::new();
+        assert!(content_sha(&SidecarMap::new(m.clone())).is_none());
+        m.insert("a.txt".to_string(), json!({"size": 1, "oid": "o1"}));
+        m.insert(
+            "b.bin".to_string(),
+            json!({"size": 9, "lfs": {"oid": "sha256:ab", "size": 9}}),
+        );
+        let s1 = content_sha(&SidecarMap::new(m.clone())).unwrap();
+        assert_eq!(s1.len(), 40);
+        assert!(s1.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(content_sha(&SidecarMap::new(m.clone())).unwrap(), s1);
+        m.insert("a.txt".to_string(), json!({"size": 1, "oid": "o2"}));
+        let s2 = content_sha(&SidecarMap::new(m)).unwrap();
+        assert_ne!(s1, s2);
+
+        let v = build_repo_json(
+            RepoKind::Model,
+            "foo/bar",
+            &repo_sha(Some("main"), Some(&s2)),
+            &[],
+            0,
+            RepoJsonFlavor::Rich,
+            None,
+        );
+        assert_eq!(v["sha"], s2);
+    }
+
+    #[tokio::test]
+    async fn repo_meta_overrides() {
+        let dir = std::path::PathBuf::from("fake_hub/tests_repo_meta_parse");
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let meta_path = dir.join(".repo-meta.json");
+        let _ = tokio::fs::remove_file(&meta_path).await;
+        assert_eq!(
+            read_repo_meta(&dir).await.unwrap().flavor,
+            RepoJsonFlavor::Rich
+        );
+
+        let body = json!({"flavor": "minimal", "tags": ["custom"], "gated": "auto", "likes": 9});
+        tokio::fs::write(&meta_path, body.to_string())
+            .await
+            .unwrap();
+        let meta = read_repo_meta(&dir).await.unwrap();
+        assert_eq!(meta.flavor, RepoJsonFlavor::Minimal);
+        // Parsed once, then served from the cache until the file changes
+        assert!(
+            REPO_META_CACHE
+                .read()
+                .await
+                .inner
+                .keys()
+                .any(|(p, _, _)| p == &meta_path)
+        );
+        let mut v = build_repo_json(RepoKind::Dataset, "ds/foo", "", &[], 0, meta.flavor, None);
+        meta.apply(&mut v);
+        assert_eq!(v["tags"], json!(["custom"]));
+        assert_eq!(v["gated"], "auto");
+        assert!(v.get("likes").is_none());
+
+        tokio::fs::write(&meta_path, r#"{"flavor": "fancy"}"#)
+            .await
+            .unwrap();
+        assert!(read_repo_meta(&dir).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn repo_defaults_layer_env_under_meta() {
+        let sha = fake_sha(None);
+        let plain = build_repo_json(
+            RepoKind::Model,
+            "a/b",
+            &sha,
+            &[],
+            0,
+            RepoJsonFlavor::Rich,
+            None,
+        );
+        assert_eq!(plain["author"], "local-user");
+        assert_eq!(plain["pipeline_tag"], "text-generation");
+        assert_eq!(
+            plain["tags"],
+            json!(["transformers", "gpt2", "text-generation"])
+        );
+
+        let dir = std::path::PathBuf::from("fake_hub/tests_repo_meta_defaults");
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let body = json!({"author": "acme", "pipeline_tag": "fill-mask"});
+        tokio::fs::write(dir.join(".repo-meta.json"), body.to_string())
+            .await
+            .unwrap();
+        let meta = read_repo_meta(&dir).await.unwrap();
+        let global = RepoDefaults {
+            author: Some("env-user".to_string()),
+            pipeline_tag: Some("text-classification".to_string()),
+            library_name: Some("timm".to_string()),
+        };
+        let d = meta.defaults(&global);
+        let v = build_repo_json(
+            RepoKind::Model,
+            "a/b",
+            &sha,
+            &[],
+            0,
+            RepoJsonFlavor::Rich,
+            Some(&d),
+        );
+        assert_eq!(v["author"], "acme");
+        assert_eq!(v["pipeline_tag"], "fill-mask");
+        assert_eq!(v["library_name"], "timm");
+        assert_eq!(v["transformersInfo"]["pipeline_tag"], "fill-mask");
+        assert_eq!(v["tags"], json!(["timm", "fill-mask"]));
     }
 
     #[test]
@@ -131,14 +463,31 @@ mod tests {
         let v = build_repo_json(
             RepoKind::Dataset,
             "ds/foo",
-            None,
+            "",
             &[],
             0,
             RepoJsonFlavor::Rich,
+            None,
         );
         assert_eq!(v["_id"], "local/datasets/ds/foo");
         assert_eq!(v["id"], "ds/foo");
         assert_eq!(v["tags"][0], "dataset");
         assert!(v.get("downloads").is_some());
     }
+
+    #[test]
+    fn space_rich_shape() {
+        let v = build_repo_json(
+            RepoKind::Space,
+            "org/demo",
+            "",
+            &[],
+            0,
+            RepoJsonFlavor::Rich,
+            None,
+        );
+        assert_eq!(v["_id"], "local/spaces/org/demo");
+        assert_eq!(v["sdk"], "gradio");
+        assert_eq!(v["runtime"]["stage"], "RUNNING");
+    }
 }
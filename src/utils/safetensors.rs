@@ -0,0 +1,181 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use serde_json::{Value, json};
+use tokio::io::AsyncReadExt;
+
+use crate::app_state::AppState;
+use crate::caches::{SAFETENSORS_CACHE, SafetensorsCounts};
+use crate::utils::paths::secure_join;
+
+// Header size cap from the safetensors format (100 MB); larger prefixes are not headers.
+const MAX_HEADER_LEN: u64 = 100 * 1024 * 1024;
+
+// Parsed headers kept in SAFETENSORS_CACHE; each is a handful of dtype counts.
+const CACHE_CAP: usize = 4096;
+
+// Element counts per dtype from one safetensors header; None when the file is not one.
+fn parse_header(header: &[u8]) -> Option<BTreeMap<String, u64>> {
+    let parsed: Value = serde_json::from_slice(header).ok()?;
+    let mut counts: BTreeMap<String, u64> = BTreeMap::new();
+    for (name, tensor) in parsed.as_object()? {
+        if name == "__metadata__" {
+            continue;
+        }
+        let dtype = tensor.get("dtype")?.as_str()?;
+        let mut elems: u64 = 1;
+        for dim in tensor.get("shape")?.as_array()? {
+            elems = elems.saturating_mul(dim.as_u64()?);
+        }
+        let slot = counts.entry(dtype.to_string()).or_default();
+        *slot = slot.saturating_add(elems);
+    }
+    Some(counts)
+}
+
+async fn read_counts(path: &Path) -> Option<BTreeMap<String, u64>> {
+    let mut f = tokio::fs::File::open(path).await.ok()?;
+    let mut len_buf = [0u8; 8];
+    f.read_exact(&mut len_buf).await.ok()?;
+    let len = u64::from_le_bytes(len_buf);
+    // The prefix is untrusted: never allocate more than the file could actually hold
+    let file_len = f.metadata().await.ok()?.len();
+    if len == 0 || len > MAX_HEADER_LEN || len > file_len.saturating_sub(8) {
+        return None;
+    }
+    let mut header = vec![0u8; len as usize];
+    f.read_exact(&mut header).await.ok()?;
+    parse_header(&header)
+}
+
+// Counts for one file, parsed at most once per (path, mtime, size).
+async fn file_counts(path: &Path) -> SafetensorsCounts {
+    let md = tokio::fs::metadata(path).await.ok()?;
+    let mtime = md
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let key = (path.to_path_buf(), mtime, md.len());
+    if let Some(hit) = SAFETENSORS_CACHE.read().await.inner.get(&key) {
+        return hit.counts.clone();
+    }
+    let counts = read_counts(path).await.map(Arc::new);
+    SAFETENSORS_CACHE
+        .write()
+        .await
+        .insert_bounded(key, counts.clone(), CACHE_CAP);
+    counts
+}
+
+// Summed `{"parameters": {dtype: n}, "total": n}` over the `*.safetensors` siblings of
+// `repo_dir`; None when none of them has a parseable header.
+pub async fn safetensors_summary(
+    state: &AppState,
+    repo_dir: &Path,
+    siblings: &[Value],
+) -> Option<Value> {
+    let mut params: BTreeMap<String, u64> = BTreeMap::new();
+    let mut found = false;
+    for s in siblings {
+        let Some(rel) = s.get("rfilename").and_then(|v| v.as_str()) else {
+            continue;
+        };
+        if !rel.ends_with(".safetensors") {
+            continue;
+        }
+        let Some(path) = secure_join(repo_dir, rel, state.allow_symlink_escape) else {
+            continue;
+        };
+        let Some(counts) = file_counts(&path).await else {
+            continue;
+        };
+        found = true;
+        for (dtype, n) in counts.iter() {
+            let slot = params.entry(dtype.clone()).or_default();
+            *slot = slot.saturating_add(*n);
+        }
+    }
+    if !found {
+        return None;
+    }
+    let total = params.values().fold(0u64, |acc, n| acc.saturating_add(*n));
+    Some(json!({"parameters": params, "total": total}))
+}
+
+// Replace the placeholder `safetensors` block of a rich model JSON; other shapes are left alone.
+pub async fn fill_safetensors(
+    state: &AppState,
+    val: &mut Value,
+    repo_dir: &Path,
+    siblings: &[Value],
+) {
+    if val.get("safetensors").is_none() {
+        return;
+    }
+    if let Some(summary) = safetensors_summary(state, repo_dir, siblings).await {
+        val["safetensors"] = summary;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn header_counts_sum_by_dtype() {
+        let header = json!({
+            "__metadata__": {"format": "pt"},
+            "a.weight": {"dtype": "F32", "shape": [2, 3], "data_offsets": [0, 24]},
+            "a.bias": {"dtype": "F32", "shape": [3], "data_offsets": [24, 36]},
+            "b": {"dtype": "U8", "shape": [4], "data_offsets": [36, 40]},
+            "scalar": {"dtype": "BF16", "shape": [], "data_offsets": [40, 42]},
+        });
+        let counts = parse_header(header.to_string().as_bytes()).unwrap();
+        assert_eq!(counts["F32"], 9);
+        assert_eq!(counts["U8"], 4);
+        assert_eq!(counts["BF16"], 1);
+        assert!(parse_header(b"not json").is_none());
+        assert!(parse_header(br#"{"x": {"shape": [1]}}"#).is_none());
+
+        // Two shards sum; a non-safetensors payload is skipped
+        let dir = dunce::canonicalize("fake_hub")
+            .unwrap()
+            .join("tests_safetensors_summary");
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let state = AppState::for_tests(dir.clone());
+        let bytes = header.to_string().into_bytes();
+        let mut file = (bytes.len() as u64).to_le_bytes().to_vec();
+        file.extend_from_slice(&bytes);
+        for name in ["a.safetensors", "b.safetensors"] {
+            tokio::fs::write(dir.join(name), &file).await.unwrap();
+        }
+        tokio::fs::write(dir.join("c.safetensors"), b"zeros")
+            .await
+            .unwrap();
+        let siblings: Vec<Value> = ["a.safetensors", "b.safetensors", "c.safetensors"]
+            .iter()
+            .map(|n| json!({"rfilename": n}))
+            .collect();
+        let summary = safetensors_summary(&state, &dir, &siblings).await.unwrap();
+        assert_eq!(
+            summary,
+            json!({"parameters": {"BF16": 2, "F32": 18, "U8": 8}, "total": 28})
+        );
+        assert!(
+            safetensors_summary(&state, &dir, &siblings[2..])
+                .await
+                .is_none()
+        );
+
+        // A length prefix past the end of the file is rejected before allocating
+        let mut truncated = (50u64 * 1024 * 1024).to_le_bytes().to_vec();
+        truncated.extend_from_slice(b"{}");
+        tokio::fs::write(dir.join("d.safetensors"), &truncated)
+            .await
+            .unwrap();
+        assert!(read_counts(&dir.join("d.safetensors")).await.is_none());
+    }
+}
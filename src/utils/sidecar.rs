@@ -1,54 +1,222 @@
-use std::io;
-use std::path::Path;
-use std::time::UNIX_EPOCH;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Instant, UNIX_EPOCH};
 
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
 use serde_json::{Value, json};
 use tokio::fs;
+use tracing::warn;
 
-use crate::caches::SidecarMap;
+use crate::app_state::AppState;
+use crate::caches::{SidecarEntry, SidecarMap};
+use crate::error::AppError;
 
-pub async fn get_sidecar_map(base_dir: &Path) -> io::Result<SidecarMap> {
-    let sidecar = base_dir.join(".paths-info.json");
-    if !sidecar.is_file() {
-        return Ok(Default::default());
+pub struct SidecarFile {
+    pub path: PathBuf,
+    pub gzip: bool,
+    pub mtime: u64,
+    pub size: u64,
+}
+
+// `<SIDECAR_DIR>/<repo path relative to the hub root>`, the stem external sidecars hang off.
+fn external_sidecar_stem(state: &AppState, base_dir: &Path) -> Option<PathBuf> {
+    let dir = state.sidecar_dir.as_deref()?;
+    let root = state.root.as_path();
+    let rel = match base_dir.strip_prefix(root) {
+        Ok(rel) => rel.to_path_buf(),
+        Err(_) => dunce::canonicalize(base_dir)
+            .ok()?
+            .strip_prefix(dunce::canonicalize(root).ok()?)
+            .ok()?
+            .to_path_buf(),
+    };
+    if rel.as_os_str().is_empty() {
+        return None;
+    }
+    Some(dir.join(rel))
+}
+
+// A repo directory holds a sidecar or a `.repo-meta.json`; org folders hold neither.
+pub fn is_repo_dir(state: &AppState, dir: &Path) -> bool {
+    dir.is_dir() && (find_sidecar(state, dir).is_some() || dir.join(".repo-meta.json").is_file())
+}
+
+// Locate the sidecar for a repo: `.paths-info.json` or `.paths-info.json.gz`, looked up
+// first as `<SIDECAR_DIR>/<repo>.paths-info.json[.gz]` when SIDECAR_DIR is set, then inside
+// the repo. When both variants exist the newer one (by mtime) wins; ties go to the plain file.
+pub fn find_sidecar(state: &AppState, base_dir: &Path) -> Option<SidecarFile> {
+    if let Some(stem) = external_sidecar_stem(state, base_dir) {
+        let with_suffix = |suffix: &str| {
+            let mut p = stem.clone().into_os_string();
+            p.push(suffix);
+            PathBuf::from(p)
+        };
+        let found = pick_sidecar(
+            with_suffix(".paths-info.json"),
+            with_suffix(".paths-info.json.gz"),
+        );
+        if found.is_some() {
+            return found;
+        }
     }
-    let md = sidecar.metadata()?;
-    let size = md.len();
-    let mtime = md
-        .modified()
-        .ok()
-        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
-        .map(|d| d.as_secs())
-        .unwrap_or(0);
+    pick_sidecar(
+        base_dir.join(".paths-info.json"),
+        base_dir.join(".paths-info.json.gz"),
+    )
+}
+
+fn pick_sidecar(plain: PathBuf, gz: PathBuf) -> Option<SidecarFile> {
+    let stat = |path: PathBuf, gzip: bool| {
+        let md = path.metadata().ok().filter(|m| m.is_file())?;
+        let mtime = md
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Some(SidecarFile {
+            path,
+            gzip,
+            mtime,
+            size: md.len(),
+        })
+    };
+    match (stat(plain, false), stat(gz, true)) {
+        (Some(plain), Some(gz)) => Some(if gz.mtime > plain.mtime { gz } else { plain }),
+        (plain, gz) => plain.or(gz),
+    }
+}
+
+async fn read_sidecar_text(sc: &SidecarFile) -> io::Result<String> {
+    if !sc.gzip {
+        return fs::read_to_string(&sc.path).await;
+    }
+    let raw = fs::read(&sc.path).await?;
+    tokio::task::spawn_blocking(move || {
+        let mut out = String::new();
+        GzDecoder::new(raw.as_slice()).read_to_string(&mut out)?;
+        Ok(out)
+    })
+    .await
+    .map_err(io::Error::other)?
+}
+
+// Sidecar format version written by fetch_repo and the write routes. Readers also accept
+// v0 (entries named by `filename` instead of `path`), upgraded in memory; a sidecar without
+// `version` is read as the current one.
+pub const SIDECAR_VERSION: u64 = 1;
+
+// Upgrade a parsed sidecar to SIDECAR_VERSION in place. Non-objects and non-numeric
+// versions are InvalidData; versions newer than SIDECAR_VERSION are Unsupported.
+fn migrate_sidecar(doc: &mut Value) -> io::Result<()> {
+    let Some(obj) = doc.as_object_mut() else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "malformed sidecar: not an object",
+        ));
+    };
+    let version = match obj.get("version") {
+        None => SIDECAR_VERSION,
+        Some(v) => v.as_u64().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "sidecar version is not a number",
+            )
+        })?,
+    };
+    if version > SIDECAR_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("sidecar version {version} is newer than supported ({SIDECAR_VERSION})"),
+        ));
+    }
+    if version == 0
+        && let Some(entries) = obj.get_mut("entries").and_then(|v| v.as_array_mut())
+    {
+        for e in entries.iter_mut().filter_map(|e| e.as_object_mut()) {
+            if !e.contains_key("path")
+                && let Some(name) = e.remove("filename")
+            {
+                e.insert("path".to_string(), name);
+            }
+        }
+    }
+    obj.insert("version".to_string(), json!(SIDECAR_VERSION));
+    Ok(())
+}
+
+pub async fn get_sidecar_map(state: &AppState, base_dir: &Path) -> io::Result<SidecarMap> {
+    let Some(sidecar) = find_sidecar(state, base_dir) else {
+        return Ok(Default::default());
+    };
+    // The file path (plain vs .gz) is part of the key, so switching variants invalidates.
     let key = (
-        dunce::canonicalize(&sidecar).unwrap_or(sidecar.clone()),
-        mtime,
-        size,
+        dunce::canonicalize(&sidecar.path).unwrap_or(sidecar.path.clone()),
+        sidecar.mtime,
+        sidecar.size,
     );
     {
         let cache = crate::caches::SIDECAR_CACHE.read().await;
-        if let Some(mp) = cache.inner.get(&key) {
-            return Ok(mp.clone());
+        if let Some(hit) = cache.inner.get(&key)
+            && hit.at.elapsed() < state.sidecar_cache_ttl
+        {
+            crate::metrics::SIDECAR_CACHE.hit();
+            return Ok(hit.map.clone());
         }
     }
-    let data = fs::read_to_string(&sidecar).await?;
-    let parsed: Value = serde_json::from_str(&data).unwrap_or(json!({}));
+    crate::metrics::SIDECAR_CACHE.miss();
+    let data = read_sidecar_text(&sidecar).await?;
+    // A sidecar that does not parse is an error, not an empty repo
+    let mut parsed: Value = serde_json::from_str(&data).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("malformed sidecar {}: {}", sidecar.path.display(), e),
+        )
+    })?;
+    migrate_sidecar(&mut parsed).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("sidecar {}: {}", sidecar.path.display(), e),
+        )
+    })?;
     let mut map: std::collections::HashMap<String, Value> = std::collections::HashMap::new();
     if let Some(entries) = parsed.get("entries").and_then(|v| v.as_array()) {
         for it in entries {
-            if it.get("type").and_then(|v| v.as_str()) == Some("file") {
-                if let Some(path) = it.get("path").and_then(|v| v.as_str()) {
-                    map.insert(path.to_string(), it.clone());
-                }
+            if it.get("type").and_then(|v| v.as_str()) == Some("file")
+                && let Some(path) = it.get("path").and_then(|v| v.as_str())
+            {
+                map.insert(path.to_string(), it.clone());
             }
         }
     }
     let mut cache = crate::caches::SIDECAR_CACHE.write().await;
     let arc_map: SidecarMap = std::sync::Arc::new(map);
-    cache.inner.insert(key, arc_map.clone());
+    cache.insert_bounded(
+        key,
+        SidecarEntry {
+            map: arc_map.clone(),
+            at: Instant::now(),
+        },
+        state.sidecar_cache_cap,
+    );
     Ok(arc_map)
 }
 
+// `get_sidecar_map` for request handlers: unreadable, unparseable or too-new sidecars
+// become a 500 `sidecar_malformed`; a missing one is still an empty map.
+pub async fn load_sidecar_map(state: &AppState, base_dir: &Path) -> Result<SidecarMap, AppError> {
+    get_sidecar_map(state, base_dir).await.map_err(|e| {
+        warn!(target: "fakehub", "load sidecar failed: {}", e);
+        if e.kind() == io::ErrorKind::Unsupported {
+            AppError::SidecarMalformed("Unsupported sidecar version")
+        } else {
+            AppError::SidecarMalformed("Sidecar malformed")
+        }
+    })
+}
+
 // Extract an ETag string from a sidecar map for a given relative path, verifying size.
 // Returns (etag, is_lfs) if available and consistent.
 pub fn etag_from_sidecar(
@@ -85,3 +253,345 @@ pub fn etag_from_sidecar(
     }
     None
 }
+
+// Deepest directory level searched for repos: root/datasets/{org}/{name}.
+const VERIFY_MAX_DEPTH: usize = 4;
+
+// Every repo (a directory holding a sidecar) under `root`, hidden directories skipped.
+pub async fn find_repo_dirs(state: &AppState, root: &Path) -> Vec<PathBuf> {
+    let mut repos = Vec::new();
+    let mut stack: Vec<(PathBuf, usize)> = vec![(root.to_path_buf(), 0)];
+    while let Some((dir, depth)) = stack.pop() {
+        if find_sidecar(state, &dir).is_some() {
+            repos.push(dir);
+            continue;
+        }
+        if depth >= VERIFY_MAX_DEPTH {
+            continue;
+        }
+        let Ok(mut rd) = fs::read_dir(&dir).await else {
+            continue;
+        };
+        while let Ok(Some(ent)) = rd.next_entry().await {
+            let hidden = ent.file_name().to_string_lossy().starts_with('.');
+            if !hidden && ent.file_type().await.is_ok_and(|t| t.is_dir()) {
+                stack.push((ent.path(), depth + 1));
+            }
+        }
+    }
+    repos
+}
+
+// SIDECAR_VERIFY self-check: warn about sidecar entries whose declared size differs from
+// the file on disk, or whose file is missing. Returns (repos checked, problem entries).
+pub async fn verify_sidecars(state: &AppState, root: &Path) -> (usize, usize) {
+    let repos = find_repo_dirs(state, root).await;
+    let mut problems = 0usize;
+    for dir in &repos {
+        problems += verify_repo_sidecar(state, dir).await;
+    }
+    (repos.len(), problems)
+}
+
+// PRELOAD warmup: parse every repo's sidecar into SIDECAR_CACHE with at most `concurrency`
+// loads in flight. Broken sidecars are logged and skipped. Returns (warmed, failed).
+pub async fn preload_sidecars(state: &AppState, root: &Path, concurrency: usize) -> (usize, usize) {
+    let repos = find_repo_dirs(state, root).await;
+    let permits = std::sync::Arc::new(tokio::sync::Semaphore::new(concurrency.max(1)));
+    let mut tasks = tokio::task::JoinSet::new();
+    for dir in repos {
+        let permits = permits.clone();
+        let state = state.clone();
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            match get_sidecar_map(&state, &dir).await {
+                Ok(_) => true,
+                Err(e) => {
+                    warn!(target: "fakehub", "[preload] skipping {}: {}", dir.display(), e);
+                    false
+                }
+            }
+        });
+    }
+    let (mut warmed, mut failed) = (0usize, 0usize);
+    while let Some(res) = tasks.join_next().await {
+        if res.unwrap_or(false) {
+            warmed += 1;
+        } else {
+            failed += 1;
+        }
+    }
+    (warmed, failed)
+}
+
+// (file count, summed size) over the map `get_sidecar_map` builds, so stats see the same
+// migrated entries as requests do and a malformed or too-new sidecar is an error here too.
+pub async fn sidecar_totals(state: &AppState, repo_dir: &Path) -> io::Result<(u64, u64)> {
+    let map = get_sidecar_map(state, repo_dir).await?;
+    let bytes = map
+        .values()
+        .map(|it| {
+            it.get("size")
+                .or_else(|| it.get("lfs").and_then(|l| l.get("size")))
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0)
+        })
+        .sum();
+    Ok((map.len() as u64, bytes))
+}
+
+// Serializes sidecar rewrites within the process.
+static SIDECAR_WRITE_LOCK: once_cell::sync::Lazy<tokio::sync::Mutex<()>> =
+    once_cell::sync::Lazy::new(|| tokio::sync::Mutex::new(()));
+
+// Insert `entry` into the repo sidecar, replacing any entry with the same `path`. The file
+// keeps its plain/gzip variant (plain `.paths-info.json` when there is none yet) and is
+// swapped in via a temp file + rename, upgraded to SIDECAR_VERSION. A malformed sidecar is
+// an InvalidData error.
+pub async fn upsert_sidecar_entry(
+    state: &AppState,
+    repo_dir: &Path,
+    entry: Value,
+) -> io::Result<()> {
+    let path = entry
+        .get("path")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "entry without path"))?
+        .to_string();
+    let _guard = SIDECAR_WRITE_LOCK.lock().await;
+    let (mut doc, target, gzip) = match find_sidecar(state, repo_dir) {
+        Some(sc) => {
+            let data = read_sidecar_text(&sc).await?;
+            let mut doc: Value = serde_json::from_str(&data).map_err(io::Error::other)?;
+            migrate_sidecar(&mut doc)?;
+            (doc, sc.path, sc.gzip)
+        }
+        None => (
+            json!({"version": SIDECAR_VERSION, "entries": []}),
+            repo_dir.join(".paths-info.json"),
+            false,
+        ),
+    };
+    let entries = doc
+        .get_mut("entries")
+        .and_then(|v| v.as_array_mut())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "entries missing"))?;
+    entries.retain(|e| e.get("path").and_then(|v| v.as_str()) != Some(path.as_str()));
+    entries.push(entry);
+
+    let text = serde_json::to_string_pretty(&doc).map_err(io::Error::other)?;
+    let bytes = if gzip {
+        tokio::task::spawn_blocking(move || {
+            let mut enc = GzEncoder::new(Vec::new(), Compression::default());
+            enc.write_all(text.as_bytes())?;
+            enc.finish()
+        })
+        .await
+        .map_err(io::Error::other)??
+    } else {
+        text.into_bytes()
+    };
+    let tmp = target.with_extension(format!("tmp-{}", std::process::id()));
+    fs::write(&tmp, bytes).await?;
+    if let Err(e) = fs::rename(&tmp, &target).await {
+        let _ = fs::remove_file(&tmp).await;
+        return Err(e);
+    }
+    Ok(())
+}
+
+async fn verify_repo_sidecar(state: &AppState, repo_dir: &Path) -> usize {
+    let sc_map = match get_sidecar_map(state, repo_dir).await {
+        Ok(m) => m,
+        Err(e) => {
+            warn!(target: "fakehub", "[sidecar-verify] {}: unreadable sidecar: {}", repo_dir.display(), e);
+            return 1;
+        }
+    };
+    let mut rels: Vec<&String> = sc_map.keys().collect();
+    rels.sort();
+    let mut problems = 0usize;
+    for rel in rels {
+        let entry = &sc_map[rel];
+        let declared = entry
+            .get("size")
+            .or_else(|| entry.get("lfs").and_then(|l| l.get("size")))
+            .and_then(|v| v.as_u64());
+        let Some(path) =
+            crate::utils::paths::secure_join(repo_dir, rel, state.allow_symlink_escape)
+        else {
+            warn!(target: "fakehub", "[sidecar-verify] {}: {} escapes the repo", repo_dir.display(), rel);
+            problems += 1;
+            continue;
+        };
+        match fs::metadata(&path).await {
+            Err(_) => {
+                warn!(target: "fakehub", "[sidecar-verify] {}: {} listed but missing on disk", repo_dir.display(), rel);
+                problems += 1;
+            }
+            Ok(md) => {
+                if let Some(d) = declared
+                    && d != md.len()
+                {
+                    warn!(
+                        target: "fakehub",
+                        "[sidecar-verify] {}: {} declares size {} but file has {}",
+                        repo_dir.display(), rel, d, md.len()
+                    );
+                    problems += 1;
+                }
+            }
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    #[tokio::test]
+    async fn preload_warms_each_repo_and_skips_broken_sidecars() {
+        let state = AppState::for_tests(PathBuf::from("fake_hub"));
+        let root = dunce::canonicalize("fake_hub")
+            .unwrap()
+            .join("tests_preload");
+        let _ = std::fs::remove_dir_all(&root);
+        for (rel, body) in [
+            ("org/a", r#"{"entries": []}"#),
+            ("datasets/org/b", r#"{"entries": []}"#),
+            ("bad", "not json"),
+        ] {
+            std::fs::create_dir_all(root.join(rel)).unwrap();
+            std::fs::write(root.join(rel).join(".paths-info.json"), body).unwrap();
+        }
+        assert_eq!(preload_sidecars(&state, &root, 1).await, (2, 1));
+        let cache = crate::caches::SIDECAR_CACHE.read().await;
+        for rel in ["org/a", "datasets/org/b"] {
+            let dir = root.join(rel);
+            assert!(
+                cache.inner.keys().any(|(p, _, _)| p.starts_with(&dir)),
+                "{rel}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn loads_gzip_sidecar_and_prefers_newer_variant() {
+        let state = AppState::for_tests(PathBuf::from("fake_hub"));
+        let dir = PathBuf::from("fake_hub").join("tests_repo_sidecar_gz");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let sc = json!({"version": 1, "entries": [
+            {"path": "a.bin", "type": "file", "size": 1, "oid": "gz"},
+        ]});
+        let mut enc = GzEncoder::new(Vec::new(), Compression::default());
+        enc.write_all(sc.to_string().as_bytes()).unwrap();
+        std::fs::write(dir.join(".paths-info.json.gz"), enc.finish().unwrap()).unwrap();
+
+        let map = get_sidecar_map(&state, &dir).await.unwrap();
+        assert_eq!(map["a.bin"]["oid"], "gz");
+
+        // An older plain file loses to the gz variant.
+        let plain = dir.join(".paths-info.json");
+        std::fs::write(
+            &plain,
+            r#"{"entries":[{"path":"a.bin","type":"file","oid":"plain"}]}"#,
+        )
+        .unwrap();
+        let old = std::time::SystemTime::now() - std::time::Duration::from_secs(60);
+        std::fs::File::options()
+            .write(true)
+            .open(&plain)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        let found = find_sidecar(&state, &dir).unwrap();
+        assert!(found.gzip);
+        assert_eq!(
+            get_sidecar_map(&state, &dir).await.unwrap()["a.bin"]["oid"],
+            "gz"
+        );
+    }
+
+    #[tokio::test]
+    async fn malformed_sidecar_is_an_error_not_an_empty_repo() {
+        let state = AppState::for_tests(PathBuf::from("fake_hub"));
+        let dir = PathBuf::from("fake_hub").join("tests_repo_sidecar_malformed");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        // No sidecar at all: still an empty map
+        assert!(get_sidecar_map(&state, &dir).await.unwrap().is_empty());
+
+        std::fs::write(dir.join(".paths-info.json"), r#"{"entries": []}"#).unwrap();
+        assert!(get_sidecar_map(&state, &dir).await.unwrap().is_empty());
+
+        std::fs::write(
+            dir.join(".paths-info.json"),
+            r#"{"entries": [{"path": "a.bin", "ty"#,
+        )
+        .unwrap();
+        let err = get_sidecar_map(&state, &dir).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(matches!(
+            load_sidecar_map(&state, &dir).await,
+            Err(AppError::SidecarMalformed(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn sidecar_versions_migrate_or_refuse() {
+        let state = AppState::for_tests(PathBuf::from("fake_hub"));
+        let dir = PathBuf::from("fake_hub").join("tests_repo_sidecar_versions");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let write = |doc: Value| std::fs::write(dir.join(".paths-info.json"), doc.to_string());
+
+        write(json!({"version": 1, "entries": [
+            {"path": "a.bin", "type": "file", "size": 1, "oid": "v1"},
+        ]}))
+        .unwrap();
+        assert_eq!(
+            get_sidecar_map(&state, &dir).await.unwrap()["a.bin"]["oid"],
+            "v1"
+        );
+
+        write(json!({"version": 0, "entries": [
+            {"filename": "sub/b.bin", "type": "file", "size": 2, "oid": "v0"},
+        ]}))
+        .unwrap();
+        let map = get_sidecar_map(&state, &dir).await.unwrap();
+        assert_eq!(map["sub/b.bin"]["oid"], "v0");
+        assert_eq!(map["sub/b.bin"]["path"], "sub/b.bin");
+
+        write(json!({"version": 99, "entries": []})).unwrap();
+        let err = get_sidecar_map(&state, &dir).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        assert!(matches!(
+            load_sidecar_map(&state, &dir).await,
+            Err(AppError::SidecarMalformed("Unsupported sidecar version"))
+        ));
+    }
+
+    #[tokio::test]
+    async fn verify_reports_size_drift_and_missing_files() {
+        let state = AppState::for_tests(PathBuf::from("fake_hub"));
+        let root = PathBuf::from("fake_hub").join("tests_sidecar_verify_root");
+        let repo = root.join("org").join("repo");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&repo).unwrap();
+        std::fs::write(repo.join("ok.txt"), b"abc").unwrap();
+        std::fs::write(repo.join("drift.txt"), b"longer now").unwrap();
+        let sc = json!({"entries": [
+            {"path": "ok.txt", "type": "file", "size": 3},
+            {"path": "drift.txt", "type": "file", "size": 2},
+            {"path": "gone.bin", "type": "file", "lfs": {"oid": "sha256:x", "size": 9}},
+        ]});
+        std::fs::write(repo.join(".paths-info.json"), sc.to_string()).unwrap();
+
+        assert_eq!(verify_sidecars(&state, &root).await, (1, 2));
+    }
+}